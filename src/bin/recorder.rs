@@ -0,0 +1,279 @@
+//! Headless recorder: runs the capture and recording pipeline without a window.
+//!
+//! ```text
+//! recorder --source rtsp://192.168.1.10:554/stream --profile archive \
+//!          --output recordings/door.mkv --segment 600
+//! recorder --source test --audio test --audio-rate 44100 --audio-channels 2 --duration 5
+//! recorder --audio pulse --audio-only opus --output notes/standup
+//! recorder --audio pulse --monitor --monitor-latency 20
+//! recorder --audio alsa:hw:1,0 --audio pulse:alsa_output.pci.monitor --audio-tracks
+//! recorder --audio pulse --audio-only flac --loudness
+//! recorder --source device --audio alsa --av-offset -120
+//! recorder --source device --framerate 30000/1001 --no-audio
+//! recorder --source device --rotate 90 --crop 0,120,0,120
+//! ```
+//!
+//! Ctrl+C finishes the file properly; a second Ctrl+C exits immediately.
+//! Microphones with a preset saved from the UI get its gain and processing,
+//! and the camera gets its saved image adjustments.
+use std::{
+	fs,
+	io::{self, Write},
+	path::PathBuf,
+	process,
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		Arc, Mutex,
+	},
+	thread,
+	time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use clap::Parser;
+use druid_camera::media::{
+	audio::{AudioInput, AudioLayout, AudioSettings, SampleFormat},
+	balance::{CameraAdjustments, ImageAdjustments},
+	level::AudioLevel,
+	loudness::Loudness,
+	mode::FrameRate,
+	monitor::MonitorSettings,
+	processing::ProcessingPresets,
+	recorder::Recorder,
+	recording::{AudioFormat, Profile, RecordingSettings},
+	source::CaptureSource,
+	sync::AvOffset,
+	transform::{Crop, Rotation, Scoped, Transforms},
+};
+
+/// Record a camera without opening a window.
+#[derive(Debug, Parser)]
+#[clap(name = "recorder")]
+struct Args {
+	/// Capture source: `device`, `test`, an rtsp:// or http:// URL, udp://address:port
+	/// or `gst:` followed by a pipeline description.
+	#[clap(short, long, default_value = "device")]
+	source: CaptureSource,
+	/// Recording framerate, e.g. 30, 30000/1001 or 29.97.
+	#[clap(long, default_value = "24", conflicts_with = "audio_only")]
+	framerate: FrameRate,
+	/// Encoder preset: preview, balanced or archive.
+	#[clap(short, long, default_value = "preview")]
+	profile: Profile,
+	/// File to write; with `--audio-only` its extension is replaced.
+	#[clap(short, long, default_value = ".media/druid.mkv")]
+	output: PathBuf,
+	/// Stop after this many seconds instead of waiting for Ctrl+C.
+	#[clap(short, long)]
+	duration: Option<u64>,
+	/// Start a new numbered file every this many seconds.
+	#[clap(long)]
+	segment: Option<u64>,
+	/// Audio source: alsa, pulse, pipewire or test, optionally followed by
+	/// `:device`. Repeat to capture several inputs at once.
+	#[clap(long, default_value = "alsa")]
+	audio: Vec<AudioInput>,
+	/// Record every audio input to its own track instead of mixing them.
+	#[clap(long, conflicts_with = "no_audio")]
+	audio_tracks: bool,
+	/// Record video only.
+	#[clap(long, conflicts_with = "audio")]
+	no_audio: bool,
+	/// Audio sample rate in Hz.
+	#[clap(long, default_value = "48000")]
+	audio_rate: u32,
+	/// Number of audio channels.
+	#[clap(long, default_value = "1")]
+	audio_channels: u32,
+	/// Raw audio sample format: S16LE, S24LE, S32LE or F32LE.
+	#[clap(long, default_value = "S16LE")]
+	audio_format: SampleFormat,
+	/// Record only audio as opus, flac, wav or m4a, without opening the camera.
+	#[clap(long, conflicts_with = "no_audio")]
+	audio_only: Option<AudioFormat>,
+	/// Play the captured audio while recording.
+	#[clap(long, conflicts_with = "no_audio")]
+	monitor: bool,
+	/// Monitor volume, 1.0 for unchanged.
+	#[clap(long, default_value = "1.0")]
+	monitor_volume: f64,
+	/// Audio buffered before the sound card, in milliseconds.
+	#[clap(long, default_value = "40")]
+	monitor_latency: u64,
+	/// Ignore the presets saved for the microphones and the image adjustments
+	/// saved for the camera.
+	#[clap(long)]
+	no_presets: bool,
+	/// Measure the loudness after EBU R128 and report the integrated LUFS.
+	#[clap(long, conflicts_with = "no_audio")]
+	loudness: bool,
+	/// Record the audio this many milliseconds later than the video, earlier
+	/// when negative.
+	#[clap(long, default_value = "0", allow_hyphen_values = true)]
+	av_offset: i64,
+	/// Swap left and right.
+	#[clap(long, conflicts_with = "audio_only")]
+	mirror: bool,
+	/// Swap top and bottom.
+	#[clap(long, conflicts_with = "audio_only")]
+	flip: bool,
+	/// Rotate clockwise by 90, 180 or 270 degrees.
+	#[clap(long, default_value = "0", conflicts_with = "audio_only")]
+	rotate: Rotation,
+	/// Pixels cut off the camera image as left,top,right,bottom; even values
+	/// only.
+	#[clap(long, default_value = "0,0,0,0", conflicts_with = "audio_only")]
+	crop: Crop,
+}
+
+fn main() -> Result<()> {
+	let args = Args::parse();
+	let mut recording = RecordingSettings {
+		location: args.output,
+		profile: args.profile,
+		segment: args.segment.map(Duration::from_secs),
+		audio_only: None,
+	};
+	if let Some(format) = args.audio_only {
+		recording = recording.audio_only(format);
+	}
+	if let Some(directory) = recording.location.parent() {
+		fs::create_dir_all(directory)?;
+	}
+
+	let interrupted = Arc::new(AtomicBool::new(false));
+	let handler_interrupted = interrupted.clone();
+	ctrlc::set_handler(move || {
+		if handler_interrupted.swap(true, Ordering::Relaxed) {
+			process::exit(130);
+		}
+	})?;
+
+	let mut inputs = args.audio.clone();
+	let mut adjustments = ImageAdjustments::default();
+	if !args.no_presets {
+		if let Some(path) = CameraAdjustments::default_path() {
+			adjustments = CameraAdjustments::load(&path)?.get(&args.source);
+		}
+		if let Some(path) = ProcessingPresets::default_path() {
+			let presets = ProcessingPresets::load(&path)?;
+			for input in &mut inputs {
+				if presets.apply_to(input) {
+					eprintln!("using the preset of {}", input);
+				}
+			}
+		}
+	}
+	let audio = (!args.no_audio).then(|| AudioSettings {
+		inputs,
+		layout: if args.audio_tracks { AudioLayout::Tracks } else { AudioLayout::Mixed },
+		rate: args.audio_rate,
+		channels: args.audio_channels,
+		format: args.audio_format,
+	});
+	let monitor = args.monitor.then(|| MonitorSettings {
+		volume: args.monitor_volume,
+		latency: Duration::from_millis(args.monitor_latency),
+		..MonitorSettings::default()
+	});
+	let level = Arc::new(Mutex::new(AudioLevel::default()));
+	let latest_level = level.clone();
+	let loudness = Arc::new(Mutex::new(Loudness::default()));
+	let latest_loudness = loudness.clone();
+	let mut builder = Recorder::builder()
+		.source(args.source.clone())
+		.framerate(args.framerate)
+		.audio(audio.clone())
+		.recording(&recording)
+		.monitor(monitor)
+		.av_offset(AvOffset(args.av_offset))
+		.image_adjustments(adjustments)
+		.transforms(Transforms {
+			mirror: Scoped::everywhere(args.mirror),
+			flip: Scoped::everywhere(args.flip),
+			rotation: Scoped::everywhere(args.rotate),
+			crop: Scoped::everywhere(args.crop),
+		})
+		.on_level(Arc::new(move |level: &AudioLevel| {
+			*latest_level.lock().unwrap() = level.clone();
+		}));
+	if args.loudness {
+		builder = builder.on_loudness(Arc::new(move |loudness: &Loudness| {
+			*latest_loudness.lock().unwrap() = *loudness;
+		}));
+	}
+	let recorder = builder.build()?;
+	recorder.record(true);
+	let captured = match recording.audio_only {
+		Some(format) => {
+			let sources = audio.as_ref().map(AudioSettings::sources).unwrap_or_default();
+			format!("{} audio as {}", sources, format)
+		}
+		None => format!("{} ({} profile)", args.source, recording.profile),
+	};
+	eprintln!(
+		"recording {} to {}, press Ctrl+C to stop",
+		captured,
+		match recording.segment {
+			Some(_) => recording.segment_pattern(),
+			None => recording.location.display().to_string(),
+		}
+	);
+
+	let limit = args.duration.map(Duration::from_secs);
+	let started = Instant::now();
+	let mut printed = Instant::now();
+	while !interrupted.load(Ordering::Relaxed) && limit.map_or(true, |limit| started.elapsed() < limit)
+	{
+		thread::sleep(Duration::from_millis(100));
+		if printed.elapsed() >= Duration::from_secs(1) {
+			printed = Instant::now();
+			let status = recorder.status.status();
+			let size = recorded_size(&recording);
+			// A muted or unplugged mic shows as a peak stuck at the bottom.
+			let peaks = level.lock().unwrap().peak.clone();
+			let peak = peaks.into_iter().fold(f64::NEG_INFINITY, f64::max);
+			eprint!(
+				"\r{:>8.1} s {:>6.1} fps {:>6} dropped {:>10.1} MiB {:>6.1} dB peak",
+				status.elapsed,
+				status.fps,
+				status.dropped_frames,
+				size as f64 / (1024.0 * 1024.0),
+				peak.max(-99.9)
+			);
+			if args.loudness {
+				let short_term = loudness.lock().unwrap().short_term;
+				eprint!(" {:>6.1} LUFS", short_term.max(-99.9));
+			}
+			io::stderr().flush()?;
+		}
+	}
+
+	eprintln!("\nfinishing recording...");
+	recorder.finish(Duration::from_secs(10))?;
+	eprintln!("done");
+	if args.loudness {
+		let loudness = *loudness.lock().unwrap();
+		eprintln!(
+			"loudness: {:.1} LUFS integrated, {:.1} LU range",
+			loudness.integrated, loudness.range
+		);
+	}
+	Ok(())
+}
+
+/// Bytes written so far, summed over the segments when splitting.
+fn recorded_size(recording: &RecordingSettings) -> u64 {
+	match recording.segment {
+		// splitmuxsink numbers the segments from 0 without gaps.
+		Some(_) => {
+			let pattern = recording.segment_pattern();
+			(0..)
+				.map(|index| pattern.replace("%05d", &format!("{:05}", index)))
+				.map_while(|segment| fs::metadata(segment).ok())
+				.map(|metadata| metadata.len())
+				.sum()
+		}
+		None => fs::metadata(&recording.location).map_or(0, |metadata| metadata.len()),
+	}
+}
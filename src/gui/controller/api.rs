@@ -0,0 +1,276 @@
+// HTTP/JSON remote control API.
+
+// Requests are translated into the same commands the UI submits, so scripts
+// driving a test rig go through exactly the code path of the buttons.
+//
+//   GET  /state          recorder status
+//   GET  /recordings     files in the recording directory
+//   POST /record/start   start or resume recording
+//   POST /record/pause   pause recording, the file stays open until the app
+//                        stops the recorder
+//   POST /snapshot       save the current preview frame as JPEG
+//   POST /source         switch the capture source, e.g. {"kind": "rtsp", "url": "..."}
+//
+// The API only listens on the loopback interface unless it is exposed to the
+// LAN, which requires a token sent as `Authorization: Bearer <token>`.
+// Responses carry no CORS headers, requests from web pages of other origins are
+// rejected, and POST requests must be sent as `application/json` so browsers
+// cannot submit them as simple cross-origin form posts.
+use std::{
+	fs, io,
+	net::{IpAddr, Ipv4Addr, TcpStream},
+	path::{Path, PathBuf},
+	time::UNIX_EPOCH,
+};
+
+use druid::{ExtEventSink, Selector, Target};
+use serde::{Deserialize, Serialize};
+use time::{macros::format_description, OffsetDateTime};
+use url::{Host, Url};
+
+use crate::{
+	gui::{
+		controller::cmd,
+		data::{
+			source::{SourceForm, SourceKind},
+			video::VideoError,
+		},
+	},
+	media::{
+		http::{self, HttpServer, Request},
+		status::StatusTracker,
+	},
+};
+
+/// Settings of the remote control API.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiSettings {
+	/// Port the API listens on.
+	pub port: u16,
+	/// Listen on all interfaces instead of only on localhost.
+	pub lan: bool,
+	/// Token every request has to send, required when listening on the LAN.
+	pub token: String,
+}
+
+impl Default for ApiSettings {
+	fn default() -> Self {
+		Self { port: 8082, lan: false, token: String::new() }
+	}
+}
+
+/// Capture source as posted to `/source`.
+///
+/// Custom pipeline descriptions are deliberately not accepted, they would let
+/// any client run arbitrary GStreamer elements.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum SourceRequest {
+	Device,
+	Rtsp { url: String, latency: Option<u32>, tcp: Option<bool> },
+	HttpMjpeg { url: String },
+	UdpRtp { address: Option<String>, port: u16, latency: Option<u32> },
+}
+
+impl From<SourceRequest> for SourceForm {
+	fn from(request: SourceRequest) -> Self {
+		let mut form = SourceForm::default();
+		match request {
+			SourceRequest::Device => form.kind = SourceKind::Device,
+			SourceRequest::Rtsp { url, latency, tcp } => {
+				form.kind = SourceKind::Rtsp;
+				form.url = url;
+				form.latency = latency.unwrap_or(form.latency);
+				form.tcp = tcp.unwrap_or(form.tcp);
+			}
+			SourceRequest::HttpMjpeg { url } => {
+				form.kind = SourceKind::HttpMjpeg;
+				form.url = url;
+			}
+			SourceRequest::UdpRtp { address, port, latency } => {
+				form.kind = SourceKind::UdpRtp;
+				form.address = address.unwrap_or(form.address);
+				form.port = port;
+				form.latency = latency.unwrap_or(form.latency);
+			}
+		}
+		form
+	}
+}
+
+/// Entry of `/recordings`.
+#[derive(Debug, Serialize)]
+struct Recording {
+	name: String,
+	size: u64,
+	/// Seconds since the Unix epoch.
+	modified: u64,
+}
+
+/// Running remote control API.
+pub struct ApiServer {
+	server: HttpServer,
+}
+
+impl ApiServer {
+	/// Serve the API, submitting commands to `event_sink`.
+	///
+	/// Recordings and snapshots are looked up in and written to `directory`.
+	pub fn start(
+		settings: &ApiSettings,
+		status: StatusTracker,
+		directory: PathBuf,
+		event_sink: ExtEventSink,
+	) -> Result<Self, VideoError> {
+		let token = settings.token.trim().to_string();
+		let ip = if settings.lan {
+			if token.is_empty() {
+				return Err(VideoError::Config(
+					"a token is required to expose the remote API on the LAN".to_string(),
+				));
+			}
+			IpAddr::V4(Ipv4Addr::UNSPECIFIED)
+		} else {
+			IpAddr::V4(Ipv4Addr::LOCALHOST)
+		};
+		let server = HttpServer::start_on(ip, settings.port, move |request, mut stream| {
+			let result = if !local_origin(&request) {
+				error(&mut stream, "403 Forbidden", "cross-origin requests are not allowed")
+			} else if !authorized(&request, &token) {
+				error(&mut stream, "401 Unauthorized", "missing or wrong token")
+			} else if request.method == "POST" && !is_json(&request) {
+				error(&mut stream, "415 Unsupported Media Type", "expected application/json")
+			} else {
+				handle(&request, &mut stream, &status, &directory, &event_sink)
+			};
+			if let Err(err) = result {
+				log::debug!("API request {} {} failed: {}", request.method, request.path, err);
+			}
+		})?;
+		Ok(ApiServer { server })
+	}
+
+	/// Base URL of the API.
+	pub fn url(&self) -> String {
+		self.server.url()
+	}
+}
+
+/// Path for a new snapshot in `directory`, named after the current time.
+pub fn snapshot_path(directory: &Path) -> PathBuf {
+	let now = OffsetDateTime::now_utc();
+	let name = now
+		.format(format_description!("[year][month][day]-[hour][minute][second]"))
+		.unwrap_or_else(|_| now.unix_timestamp().to_string());
+	directory.join(format!("snapshot-{}.jpg", name))
+}
+
+/// Whether `request` carries `token`, always true without a token.
+fn authorized(request: &Request, token: &str) -> bool {
+	token.is_empty()
+		|| request
+			.header("authorization")
+			.and_then(|value| value.strip_prefix("Bearer "))
+			.map_or(false, |sent| constant_time_eq(sent.trim().as_bytes(), token.as_bytes()))
+}
+
+/// Compare without returning early, so the time taken does not tell how much of
+/// a guessed token was right.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+	a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// Whether `request` was not sent by a web page, or by one served from this
+/// machine.
+fn local_origin(request: &Request) -> bool {
+	let origin = match request.header("origin") {
+		Some(origin) => origin,
+		None => return true,
+	};
+	match Url::parse(origin).ok().as_ref().and_then(Url::host) {
+		Some(Host::Domain(domain)) => domain.eq_ignore_ascii_case("localhost"),
+		Some(Host::Ipv4(ip)) => ip.is_loopback(),
+		Some(Host::Ipv6(ip)) => ip.is_loopback(),
+		None => false,
+	}
+}
+
+/// Whether the body of `request` is declared as JSON.
+fn is_json(request: &Request) -> bool {
+	request.header("content-type").map_or(false, |value| {
+		value.split(';').next().unwrap_or_default().trim().eq_ignore_ascii_case("application/json")
+	})
+}
+
+fn handle(
+	request: &Request,
+	stream: &mut TcpStream,
+	status: &StatusTracker,
+	directory: &Path,
+	event_sink: &ExtEventSink,
+) -> io::Result<()> {
+	let submit = |selector: Selector, stream: &mut TcpStream| match event_sink.submit_command(
+		selector,
+		(),
+		Target::Auto,
+	) {
+		Ok(()) => json(stream, "200 OK", &status.status()),
+		Err(err) => error(stream, "503 Service Unavailable", &err.to_string()),
+	};
+
+	match (request.method.as_str(), request.path.as_str()) {
+		("GET", "/state") => json(stream, "200 OK", &status.status()),
+		("GET", "/recordings") => json(stream, "200 OK", &recordings(directory)?),
+		("POST", "/record/start") => submit(cmd::PLAY_RESUME, stream),
+		("POST", "/record/pause") => submit(cmd::PLAY_PAUSE, stream),
+		("POST", "/snapshot") => {
+			let path = snapshot_path(directory);
+			match event_sink.submit_command(cmd::SNAPSHOT, path.clone(), Target::Auto) {
+				Ok(()) => json(stream, "200 OK", &serde_json::json!({ "path": path })),
+				Err(err) => error(stream, "503 Service Unavailable", &err.to_string()),
+			}
+		}
+		("POST", "/source") => match serde_json::from_slice::<SourceRequest>(&request.body) {
+			Ok(source) => {
+				let form = SourceForm::from(source);
+				match event_sink.submit_command(cmd::SELECT_SOURCE, form, Target::Auto) {
+					Ok(()) => json(stream, "200 OK", &status.status()),
+					Err(err) => error(stream, "503 Service Unavailable", &err.to_string()),
+				}
+			}
+			Err(err) => error(stream, "400 Bad Request", &err.to_string()),
+		},
+		_ => error(stream, "404 Not Found", "unknown endpoint"),
+	}
+}
+
+fn recordings(directory: &Path) -> io::Result<Vec<Recording>> {
+	let mut recordings = Vec::new();
+	for entry in fs::read_dir(directory)? {
+		let entry = entry?;
+		let metadata = entry.metadata()?;
+		if !metadata.is_file() {
+			continue;
+		}
+		recordings.push(Recording {
+			name: entry.file_name().to_string_lossy().into_owned(),
+			size: metadata.len(),
+			modified: metadata
+				.modified()
+				.ok()
+				.and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+				.map_or(0, |since| since.as_secs()),
+		});
+	}
+	recordings.sort_by(|a, b| a.name.cmp(&b.name));
+	Ok(recordings)
+}
+
+fn json(stream: &mut TcpStream, status: &str, body: &impl Serialize) -> io::Result<()> {
+	let body = serde_json::to_vec(body).map_err(io::Error::from)?;
+	http::respond(stream, status, http::content_type("state.json"), &body, false)
+}
+
+fn error(stream: &mut TcpStream, status: &str, message: &str) -> io::Result<()> {
+	json(stream, status, &serde_json::json!({ "error": message }))
+}
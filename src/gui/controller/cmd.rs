@@ -1,7 +1,12 @@
-use std::time::Duration;
+use std::{path::PathBuf, time::Duration};
 
 use druid::{ImageBuf, Selector};
 
+use crate::{
+	gui::data::source::SourceForm,
+	media::{level::AudioLevel, loudness::Loudness, output::OutputHealth, spectrum::Spectrum},
+};
+
 // Playback state
 
 pub const PLAYBACK_PLAYING: Selector<Duration> = Selector::new("app.playback-playing");
@@ -27,3 +32,52 @@ pub const PLAY_RATE: Selector<f64> = Selector::new("app.play-rate");
 //Video Frame
 
 pub const VIDEO_FRAME: Selector<ImageBuf> = Selector::new("app.video-frame");
+
+// Audio
+
+/// Latest levels of the captured audio.
+pub const AUDIO_LEVEL: Selector<AudioLevel> = Selector::new("app.audio-level");
+/// Latest spectrum of the captured audio.
+pub const AUDIO_SPECTRUM: Selector<Spectrum> = Selector::new("app.audio-spectrum");
+/// Latest loudness of the captured audio.
+pub const AUDIO_LOUDNESS: Selector<Loudness> = Selector::new("app.audio-loudness");
+/// Show the spectrum with the settings of the form.
+pub const SET_SPECTRUM: Selector = Selector::new("app.set-spectrum");
+/// Shift the recorded audio against the video by the offset in the form.
+pub const SET_AV_OFFSET: Selector = Selector::new("app.set-av-offset");
+/// Start, restart or stop the audio monitor as set in the form.
+pub const SET_MONITOR: Selector = Selector::new("app.set-monitor");
+
+// Capture source
+
+pub const SET_SOURCE: Selector = Selector::new("app.set-source");
+/// Load and apply the image adjustments saved for the current source.
+pub const LOAD_ADJUSTMENTS: Selector = Selector::new("app.load-adjustments");
+/// Replace the source settings and switch to them.
+pub const SELECT_SOURCE: Selector<SourceForm> = Selector::new("app.select-source");
+/// List the modes the current source offers.
+pub const PROBE_MODES: Selector = Selector::new("app.probe-modes");
+/// Ask the source for the selected mode.
+pub const SET_MODE: Selector = Selector::new("app.set-mode");
+
+// Capture
+
+pub const SNAPSHOT: Selector<PathBuf> = Selector::new("app.snapshot");
+/// Rebuild the recorder for the selected capture kind.
+pub const SET_CAPTURE: Selector = Selector::new("app.set-capture");
+
+// Streaming
+
+pub const RTSP_START: Selector = Selector::new("app.rtsp-start");
+pub const RTSP_STOP: Selector = Selector::new("app.rtsp-stop");
+pub const HLS_START: Selector = Selector::new("app.hls-start");
+pub const HLS_STOP: Selector = Selector::new("app.hls-stop");
+pub const WEBRTC_START: Selector = Selector::new("app.webrtc-start");
+pub const WEBRTC_STOP: Selector = Selector::new("app.webrtc-stop");
+pub const MJPEG_START: Selector = Selector::new("app.mjpeg-start");
+pub const MJPEG_STOP: Selector = Selector::new("app.mjpeg-stop");
+pub const API_START: Selector = Selector::new("app.api-start");
+pub const API_STOP: Selector = Selector::new("app.api-stop");
+pub const OUTPUT_ADD: Selector = Selector::new("app.output-add");
+pub const OUTPUT_REMOVE: Selector<u32> = Selector::new("app.output-remove");
+pub const OUTPUT_HEALTH: Selector<(u32, OutputHealth)> = Selector::new("app.output-health");
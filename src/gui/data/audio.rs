@@ -0,0 +1,263 @@
+use std::{path::PathBuf, sync::Arc, time::Duration};
+
+use druid::{Data, Lens};
+
+use crate::media::{
+	audio::{AudioInput, AudioLayout, AudioSettings, AudioSource},
+	error::VideoError,
+	monitor::MonitorSettings,
+	processing::{AudioProcessing, Compressor, Limiter, NoiseGate, ProcessingPresets},
+	spectrum::SpectrumSettings,
+};
+
+/// Kind of audio source picked in the UI.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Data)]
+pub enum AudioSourceKind {
+	Alsa,
+	Pulse,
+	PipeWire,
+	Test,
+}
+
+impl From<AudioSourceKind> for AudioSource {
+	fn from(kind: AudioSourceKind) -> Self {
+		match kind {
+			AudioSourceKind::Alsa => AudioSource::Device,
+			AudioSourceKind::Pulse => AudioSource::Pulse,
+			AudioSourceKind::PipeWire => AudioSource::PipeWire,
+			AudioSourceKind::Test => AudioSource::Test,
+		}
+	}
+}
+
+/// Editable dynamics processing of an input, thresholds in dBFS.
+#[derive(Clone, Debug, Data, Lens)]
+pub struct ProcessingForm {
+	pub gate: bool,
+	pub gate_threshold: f64,
+	pub gate_ratio: f64,
+	pub compressor: bool,
+	pub compressor_threshold: f64,
+	pub compressor_ratio: f64,
+	pub limiter: bool,
+	pub limiter_ceiling: f64,
+}
+
+impl Default for ProcessingForm {
+	fn default() -> Self {
+		AudioProcessing::default().into()
+	}
+}
+
+impl From<AudioProcessing> for ProcessingForm {
+	fn from(processing: AudioProcessing) -> Self {
+		let AudioProcessing { gate, compressor, limiter } = processing;
+		Self {
+			gate: gate.enabled,
+			gate_threshold: gate.threshold_db,
+			gate_ratio: gate.ratio,
+			compressor: compressor.enabled,
+			compressor_threshold: compressor.threshold_db,
+			compressor_ratio: compressor.ratio,
+			limiter: limiter.enabled,
+			limiter_ceiling: limiter.ceiling_db,
+		}
+	}
+}
+
+impl ProcessingForm {
+	/// Processing described by the form.
+	pub fn to_processing(&self) -> AudioProcessing {
+		AudioProcessing {
+			gate: NoiseGate {
+				enabled: self.gate,
+				threshold_db: self.gate_threshold,
+				ratio: self.gate_ratio,
+			},
+			compressor: Compressor {
+				enabled: self.compressor,
+				threshold_db: self.compressor_threshold,
+				ratio: self.compressor_ratio,
+			},
+			limiter: Limiter { enabled: self.limiter, ceiling_db: self.limiter_ceiling },
+		}
+	}
+}
+
+/// Editable audio input.
+///
+/// Gain, mute, pan and processing apply while recording; source and device
+/// once the recorder is rebuilt.
+#[derive(Clone, Debug, Data, Lens)]
+pub struct AudioInputForm {
+	pub kind: AudioSourceKind,
+	/// Device name, the default device when empty.
+	pub device: String,
+	pub gain_db: f64,
+	pub muted: bool,
+	/// From -1.0 (left) to 1.0 (right).
+	pub pan: f64,
+	pub processing: ProcessingForm,
+	/// Show the processing controls.
+	pub expanded: bool,
+	/// Outcome of the last preset load or save, shown beside the buttons.
+	pub preset_status: String,
+}
+
+impl Default for AudioInputForm {
+	fn default() -> Self {
+		Self {
+			kind: AudioSourceKind::Alsa,
+			device: String::new(),
+			gain_db: 0.0,
+			muted: false,
+			pan: 0.0,
+			processing: ProcessingForm::default(),
+			expanded: false,
+			preset_status: String::new(),
+		}
+	}
+}
+
+impl AudioInputForm {
+	/// Audio input described by the form.
+	pub fn to_input(&self) -> AudioInput {
+		AudioInput {
+			device: (!self.device.trim().is_empty()).then(|| self.device.trim().to_string()),
+			gain_db: self.gain_db,
+			muted: self.muted,
+			pan: self.pan as f32,
+			processing: self.processing.to_processing(),
+			..AudioInput::new(self.kind.into())
+		}
+	}
+
+	/// Take gain and processing from the preset saved for this microphone.
+	/// Returns whether there was one.
+	pub fn load_preset(&mut self) -> Result<bool, VideoError> {
+		let presets = ProcessingPresets::load(&presets_path()?)?;
+		let mut input = self.to_input();
+		if !presets.apply_to(&mut input) {
+			return Ok(false);
+		}
+		self.gain_db = input.gain_db;
+		self.processing = input.processing.into();
+		Ok(true)
+	}
+
+	/// Save gain and processing as the preset of this microphone.
+	pub fn save_preset(&self) -> Result<(), VideoError> {
+		let path = presets_path()?;
+		let mut presets = ProcessingPresets::load(&path)?;
+		presets.store(&self.to_input());
+		presets.save(&path)
+	}
+
+	/// Whether `other` captures from the same device.
+	pub fn same_device(&self, other: &Self) -> bool {
+		self.kind == other.kind && self.device == other.device
+	}
+}
+
+/// Where microphone presets are kept.
+fn presets_path() -> Result<PathBuf, VideoError> {
+	ProcessingPresets::default_path()
+		.ok_or_else(|| VideoError::Config("no configuration directory for presets".to_string()))
+}
+
+/// Editable audio capture settings.
+#[derive(Clone, Debug, Data, Lens)]
+pub struct AudioForm {
+	pub inputs: Arc<Vec<AudioInputForm>>,
+	/// Record every input to its own track instead of mixing them.
+	pub tracks: bool,
+	/// Record in stereo, which panning needs.
+	pub stereo: bool,
+}
+
+impl Default for AudioForm {
+	fn default() -> Self {
+		Self { inputs: Arc::new(vec![AudioInputForm::default()]), tracks: false, stereo: false }
+	}
+}
+
+impl AudioForm {
+	/// Audio settings described by the form.
+	pub fn to_settings(&self) -> AudioSettings {
+		AudioSettings {
+			inputs: self.inputs.iter().map(AudioInputForm::to_input).collect(),
+			layout: if self.tracks { AudioLayout::Tracks } else { AudioLayout::Mixed },
+			channels: if self.stereo { 2 } else { 1 },
+			..AudioSettings::default()
+		}
+	}
+}
+
+/// Editable spectrum display settings.
+#[derive(Clone, Debug, Data, Lens)]
+pub struct SpectrumForm {
+	pub bands: u32,
+	/// Lowest frequency shown, in Hz.
+	pub min_freq: f64,
+	/// Highest frequency shown, in Hz.
+	pub max_freq: f64,
+	/// Scroll the spectrum over time instead of drawing bars.
+	pub waterfall: bool,
+}
+
+impl Default for SpectrumForm {
+	fn default() -> Self {
+		let settings = SpectrumSettings::default();
+		Self {
+			bands: settings.bands,
+			min_freq: settings.min_freq,
+			max_freq: settings.max_freq,
+			waterfall: false,
+		}
+	}
+}
+
+impl SpectrumForm {
+	/// Analyzer settings described by the form.
+	pub fn to_settings(&self) -> SpectrumSettings {
+		SpectrumSettings {
+			bands: self.bands,
+			min_freq: self.min_freq,
+			max_freq: self.max_freq,
+			..SpectrumSettings::default()
+		}
+	}
+}
+
+/// Editable audio monitor settings.
+#[derive(Clone, Debug, Data, Lens)]
+pub struct MonitorForm {
+	/// Play the captured audio.
+	pub listening: bool,
+	/// Loudness of the monitor, 1.0 for unchanged.
+	pub volume: f64,
+	/// Audio buffered before the sound card, in milliseconds.
+	pub latency: u64,
+}
+
+impl Default for MonitorForm {
+	fn default() -> Self {
+		let settings = MonitorSettings::default();
+		Self {
+			listening: false,
+			volume: settings.volume,
+			latency: settings.latency.as_millis() as u64,
+		}
+	}
+}
+
+impl MonitorForm {
+	/// Monitor settings described by the form, `None` when not listening.
+	pub fn to_settings(&self) -> Option<MonitorSettings> {
+		self.listening.then(|| MonitorSettings {
+			volume: self.volume,
+			latency: Duration::from_millis(self.latency),
+			..MonitorSettings::default()
+		})
+	}
+}
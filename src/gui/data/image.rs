@@ -0,0 +1,177 @@
+use std::path::PathBuf;
+
+use druid::{Data, Lens, Point};
+
+use crate::media::{
+	balance::{CameraAdjustments, ImageAdjustments},
+	error::VideoError,
+	source::CaptureSource,
+	transform::{Crop, Rotation, Scoped, Transforms},
+};
+
+/// Editable image adjustments, applied while dragging.
+#[derive(Clone, Debug, Data, Lens)]
+pub struct ImageForm {
+	pub brightness: f64,
+	pub contrast: f64,
+	pub saturation: f64,
+	pub hue: f64,
+}
+
+impl Default for ImageForm {
+	fn default() -> Self {
+		ImageAdjustments::default().into()
+	}
+}
+
+impl From<ImageAdjustments> for ImageForm {
+	fn from(adjustments: ImageAdjustments) -> Self {
+		let ImageAdjustments { brightness, contrast, saturation, hue } = adjustments;
+		Self { brightness, contrast, saturation, hue }
+	}
+}
+
+impl ImageForm {
+	/// Adjustments described by the form.
+	pub fn to_adjustments(&self) -> ImageAdjustments {
+		ImageAdjustments {
+			brightness: self.brightness,
+			contrast: self.contrast,
+			saturation: self.saturation,
+			hue: self.hue,
+		}
+	}
+
+	/// Adjustments saved for `camera`, neutral if there are none.
+	pub fn load_for(camera: &CaptureSource) -> Result<Self, VideoError> {
+		Ok(CameraAdjustments::load(&adjustments_path()?)?.get(camera).into())
+	}
+
+	/// Save the form as the adjustments of `camera`.
+	pub fn save_for(&self, camera: &CaptureSource) -> Result<(), VideoError> {
+		let path = adjustments_path()?;
+		let mut cameras = CameraAdjustments::load(&path)?;
+		cameras.set(camera, self.to_adjustments());
+		cameras.save(&path)
+	}
+}
+
+/// Editable transforms, each with a toggle for recording it as well.
+#[derive(Clone, Debug, Data, Lens)]
+pub struct TransformForm {
+	pub mirror: bool,
+	pub mirror_recording: bool,
+	pub flip: bool,
+	pub flip_recording: bool,
+	/// Quarter turns clockwise.
+	pub quarter_turns: i32,
+	pub rotation_recording: bool,
+	/// Crop insets in camera pixels, rounded to even values.
+	pub crop_left: f64,
+	pub crop_top: f64,
+	pub crop_right: f64,
+	pub crop_bottom: f64,
+	pub crop_recording: bool,
+	/// Whether dragging over the preview selects the crop.
+	pub selecting: bool,
+	/// Shape of the crop selection.
+	pub aspect: CropAspect,
+}
+
+impl Default for TransformForm {
+	fn default() -> Self {
+		Transforms::default().into()
+	}
+}
+
+impl From<Transforms> for TransformForm {
+	fn from(transforms: Transforms) -> Self {
+		let Transforms { mirror, flip, rotation, crop } = transforms;
+		Self {
+			mirror: mirror.value,
+			mirror_recording: mirror.recording,
+			flip: flip.value,
+			flip_recording: flip.recording,
+			quarter_turns: rotation.value.quarter_turns(),
+			rotation_recording: rotation.recording,
+			crop_left: crop.value.left.into(),
+			crop_top: crop.value.top.into(),
+			crop_right: crop.value.right.into(),
+			crop_bottom: crop.value.bottom.into(),
+			crop_recording: crop.recording,
+			selecting: false,
+			aspect: CropAspect::default(),
+		}
+	}
+}
+
+impl TransformForm {
+	/// Transforms described by the form.
+	pub fn to_transforms(&self) -> Transforms {
+		let even = |inset: f64| (inset.max(0.0) / 2.0).round() as u32 * 2;
+		let crop = Crop {
+			left: even(self.crop_left),
+			top: even(self.crop_top),
+			right: even(self.crop_right),
+			bottom: even(self.crop_bottom),
+		};
+		Transforms {
+			mirror: Scoped { value: self.mirror, recording: self.mirror_recording },
+			flip: Scoped { value: self.flip, recording: self.flip_recording },
+			rotation: Scoped {
+				value: Rotation::from_quarter_turns(self.quarter_turns),
+				recording: self.rotation_recording,
+			},
+			crop: Scoped { value: crop, recording: self.crop_recording },
+		}
+	}
+}
+
+/// Shape a crop selection keeps.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Data)]
+pub enum CropAspect {
+	Free,
+	/// 16:9
+	Wide,
+	/// 4:3
+	Standard,
+	/// 1:1
+	Square,
+}
+
+impl Default for CropAspect {
+	fn default() -> Self {
+		Self::Free
+	}
+}
+
+impl CropAspect {
+	/// Width over height, `None` for any shape.
+	pub fn ratio(self) -> Option<f64> {
+		match self {
+			CropAspect::Free => None,
+			CropAspect::Wide => Some(16.0 / 9.0),
+			CropAspect::Standard => Some(4.0 / 3.0),
+			CropAspect::Square => Some(1.0),
+		}
+	}
+
+	/// Corner opposite `anchor` of the largest rectangle of this shape inside
+	/// the one spanned by `anchor` and `corner`.
+	pub fn fit(self, anchor: Point, corner: Point) -> Point {
+		let ratio = match self.ratio() {
+			Some(ratio) => ratio,
+			None => return corner,
+		};
+		let (dx, dy) = (corner.x - anchor.x, corner.y - anchor.y);
+		let width = dx.abs().min(dy.abs() * ratio);
+		Point::new(anchor.x + width.copysign(dx), anchor.y + (width / ratio).copysign(dy))
+	}
+}
+
+/// Where the adjustments of every camera are kept.
+fn adjustments_path() -> Result<PathBuf, VideoError> {
+	CameraAdjustments::default_path().ok_or_else(|| {
+		VideoError::Config("no configuration directory for camera adjustments".to_string())
+	})
+}
@@ -1,3 +1,7 @@
+pub mod audio;
+pub mod image;
+pub mod output;
+pub mod source;
 pub mod video;
 
 use druid::{Data, Lens};
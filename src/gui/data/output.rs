@@ -0,0 +1,70 @@
+use druid::{Data, Lens};
+
+use crate::media::output::{SrtMode, StreamTarget};
+
+/// Kind of network output picked in the UI.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Data)]
+pub enum OutputKind {
+	Srt,
+	Rtp,
+	Custom,
+}
+
+/// Editable settings of the next output to add.
+#[derive(Clone, Debug, Data, Lens)]
+pub struct OutputForm {
+	pub kind: OutputKind,
+	pub host: String,
+	pub port: u16,
+	/// Wait for SRT callers instead of connecting out.
+	pub listener: bool,
+	pub passphrase: String,
+	/// SRT latency in milliseconds.
+	pub latency: u32,
+	/// Multicast time to live for RTP.
+	pub ttl: u32,
+	/// gst-launch fragment of a custom output.
+	pub description: String,
+}
+
+impl Default for OutputForm {
+	fn default() -> Self {
+		Self {
+			kind: OutputKind::Srt,
+			host: "127.0.0.1".into(),
+			port: 9000,
+			listener: true,
+			passphrase: String::new(),
+			latency: 125,
+			ttl: 1,
+			description: String::new(),
+		}
+	}
+}
+
+impl OutputForm {
+	/// Output target described by the form.
+	pub fn to_target(&self) -> StreamTarget {
+		match self.kind {
+			OutputKind::Srt => StreamTarget::Srt {
+				mode: if self.listener { SrtMode::Listener } else { SrtMode::Caller },
+				host: self.host.clone(),
+				port: self.port,
+				passphrase: self.passphrase.clone(),
+				latency: self.latency,
+			},
+			OutputKind::Rtp => {
+				StreamTarget::Rtp { host: self.host.clone(), port: self.port, ttl: self.ttl }
+			}
+			OutputKind::Custom => StreamTarget::Custom { description: self.description.clone() },
+		}
+	}
+}
+
+/// Attached output as listed in the UI.
+#[derive(Clone, Debug, Data, Lens)]
+pub struct OutputRow {
+	pub id: u32,
+	pub label: String,
+	pub health: String,
+}
@@ -0,0 +1,66 @@
+use druid::{Data, Lens};
+
+use crate::media::source::{CaptureSource, RtspTransport};
+
+/// Kind of capture source picked in the UI.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Data)]
+pub enum SourceKind {
+	Device,
+	Rtsp,
+	HttpMjpeg,
+	UdpRtp,
+	Custom,
+}
+
+/// Editable capture source settings.
+///
+/// Every field is kept while switching kinds so the user does not lose a URL
+/// when looking at another source type.
+#[derive(Clone, Debug, Data, Lens)]
+pub struct SourceForm {
+	pub kind: SourceKind,
+	pub url: String,
+	pub address: String,
+	pub port: u16,
+	/// Jitterbuffer latency in milliseconds.
+	pub latency: u32,
+	/// Receive RTSP over TCP instead of UDP.
+	pub tcp: bool,
+	/// gst-launch fragment of a custom source.
+	pub description: String,
+}
+
+impl Default for SourceForm {
+	fn default() -> Self {
+		Self {
+			kind: SourceKind::Device,
+			url: String::new(),
+			address: "0.0.0.0".into(),
+			port: 5000,
+			latency: 200,
+			tcp: true,
+			description: "videotestsrc is-live=true pattern=ball".into(),
+		}
+	}
+}
+
+impl SourceForm {
+	/// Capture source described by the form.
+	pub fn to_source(&self) -> CaptureSource {
+		match self.kind {
+			SourceKind::Device => CaptureSource::Device,
+			SourceKind::Rtsp => CaptureSource::Rtsp {
+				url: self.url.clone(),
+				latency: self.latency,
+				transport: if self.tcp { RtspTransport::Tcp } else { RtspTransport::Udp },
+			},
+			SourceKind::HttpMjpeg => CaptureSource::HttpMjpeg { url: self.url.clone() },
+			SourceKind::UdpRtp => CaptureSource::UdpRtp {
+				address: self.address.clone(),
+				port: self.port,
+				latency: self.latency,
+			},
+			SourceKind::Custom => CaptureSource::Custom { description: self.description.clone() },
+		}
+	}
+}
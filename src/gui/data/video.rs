@@ -1,81 +1,51 @@
-use druid::{widget::Image, Data, ExtEventError, ExtEventSink, Lens};
-use gst::prelude::*;
-use gstreamer as gst;
-use gstreamer::query::Uri;
-use thiserror::Error;
+use std::sync::Arc;
 
-#[derive(Debug, Error)]
-pub enum VideoError {
-	#[error("{0}")]
-	Glib(#[from] glib::Error),
-	#[error("{0}")]
-	Bool(#[from] glib::BoolError),
-	#[error("failed to get the gstreamer bus")]
-	Bus,
-	#[error("{0}")]
-	StateChange(#[from] gst::StateChangeError),
-	#[error("failed to cast gstreamer element")]
-	Cast,
-	#[error("{0}")]
-	Io(#[from] std::io::Error),
-	#[error("invalid URI")]
-	Uri,
-	#[error("failed to get media capabilities")]
-	Caps,
-	#[error("failed to query media duration or position")]
-	Duration,
-	#[error("failed to sync with playback")]
-	Sync,
-	#[error("{0}")]
-	ExtEventError(#[from] ExtEventError),
+use druid::{widget::{FillStrat, Image}, Data, ExtEventSink, ImageBuf, Lens, Point};
+
+pub use crate::media::{error::VideoError, playback::Position};
+use crate::{
+	gui::{
+		controller::api::{ApiServer, ApiSettings},
+		data::{
+			audio::{AudioForm, MonitorForm, SpectrumForm},
+			image::{ImageForm, TransformForm},
+			output::{OutputForm, OutputRow},
+			source::SourceForm,
+		},
+		widgets::meter::LevelMeter,
+	},
+	media::{
+		hls::HlsSettings, level::AudioLevel, loudness::Loudness, mjpeg::MjpegSettings,
+		mode::VideoMode, recorder::Recorder, recording::AudioFormat, rtsp::RtspSettings,
+		spectrum::Spectrum, webrtc::WebRtcSettings,
+	},
+};
 
-	#[error("{0}")]
-	PadLinkError(#[from] gst::PadLinkError),
-	#[error("{0}")]
-	FlowError(#[from] gstreamer::FlowError),
-	#[error("{0}")]
-	Other(#[from] anyhow::Error),
-}
 
 /// `CameraView` widget
 pub struct VideoView {
 	pub image: Image,
+	/// How the preview frame fills the widget.
+	pub fill: FillStrat,
+	/// Latest preview frame, painted by hand while zoomed.
+	pub frame: ImageBuf,
+	/// Anchor and opposite corner of the crop being dragged, in frame pixels.
+	pub selection: Option<(Point, Point)>,
+	/// Digital zoom of the preview, 1.0 for the whole frame.
+	pub zoom: f64,
+	/// Frame pixel in the middle of the zoomed preview.
+	pub center: Point,
+	/// Last mouse position while dragging the zoomed preview around.
+	pub pan: Option<Point>,
+	/// Size recorded frames are scaled to while the zoom is recorded.
+	pub scaled: Option<(u32, u32)>,
+	/// Shown instead of the image while recording audio only.
+	pub meter: LevelMeter,
 	pub player: Option<VideoPlayer>,
 	pub event: Option<ExtEventSink>,
 	// pub state: VideoViewState,
 }
 
-/// Position in the media.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub enum Position {
-	/// Position based on time.
-	///
-	/// Not the most accurate format for videos.
-	Time(std::time::Duration),
-	/// Position based on nth frame.
-	Frame(u64),
-}
-
-impl From<Position> for gst::GenericFormattedValue {
-	fn from(pos: Position) -> Self {
-		match pos {
-			Position::Time(t) => gst::ClockTime::from_nseconds(t.as_nanos() as _).into(),
-			Position::Frame(f) => gst::format::Default(f).into(),
-		}
-	}
-}
-
-impl From<std::time::Duration> for Position {
-	fn from(t: std::time::Duration) -> Self {
-		Position::Time(t)
-	}
-}
-
-impl From<u64> for Position {
-	fn from(f: u64) -> Self {
-		Position::Frame(f)
-	}
-}
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Data)]
 pub enum VideoPlayerState {
 	Playing,
@@ -91,18 +61,110 @@ pub enum VideoRate {
 	I5,
 	I20,
 }
-#[derive(Clone, Debug, Data, Lens)]
+/// What the recorder captures.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Data)]
+pub enum CaptureKind {
+	/// Camera and microphone into Matroska.
+	Video,
+	Opus,
+	Flac,
+	Wav,
+	M4a,
+}
+
+impl Default for CaptureKind {
+	fn default() -> Self {
+		Self::Video
+	}
+}
+
+impl CaptureKind {
+	/// Format of an audio-only recording, `None` when recording video.
+	pub fn audio_only(self) -> Option<AudioFormat> {
+		match self {
+			CaptureKind::Video => None,
+			CaptureKind::Opus => Some(AudioFormat::Opus),
+			CaptureKind::Flac => Some(AudioFormat::Flac),
+			CaptureKind::Wav => Some(AudioFormat::Wav),
+			CaptureKind::M4a => Some(AudioFormat::M4a),
+		}
+	}
+}
+
+#[derive(Clone, Debug, Default, Data, Lens)]
 pub struct VideoViewState {
 
 	pub camara_record: bool,
+	/// Capture kind picked in the UI, applied with `SET_CAPTURE`.
+	pub capture: CaptureKind,
+	/// Capture kind of the running recorder.
+	pub capturing: CaptureKind,
+	/// Latest levels of the captured audio.
+	#[data(same_fn = "PartialEq::eq")]
+	pub level: AudioLevel,
+	/// Audio inputs, gain, mute and pan applied while recording, everything
+	/// else with `SET_CAPTURE`.
+	pub audio: AudioForm,
+	/// Latest spectrum of the captured audio.
+	#[data(same_fn = "PartialEq::eq")]
+	pub spectrum: Spectrum,
+	pub spectrum_form: SpectrumForm,
+	/// Latest loudness of the captured audio, if `ebur128level` is installed.
+	#[data(same_fn = "PartialEq::eq")]
+	pub loudness: Option<Loudness>,
+	/// Audio delay against video in milliseconds, applied with
+	/// `SET_AV_OFFSET` and whenever the recorder is rebuilt.
+	pub av_offset: i64,
+	/// Monitor settings, listening and latency applied with `SET_MONITOR`.
+	pub monitor: MonitorForm,
+	pub source: SourceForm,
+	/// Color correction of the source, applied while dragging.
+	pub image: ImageForm,
+	/// Mirror, flip, rotation and crop of the preview and the recording; the
+	/// recording keeps its transform while recording.
+	pub transform: TransformForm,
+	/// Record the digital zoom of the preview as well.
+	pub zoom_recording: bool,
+	/// Show the image adjustment panel beside the preview.
+	pub adjusting: bool,
+	/// Modes offered by the current source, filled on request.
+	pub source_modes: Arc<Vec<VideoMode>>,
+	/// Index into `source_modes`, `None` for automatic negotiation.
+	pub source_mode: Option<usize>,
+	#[data(same_fn = "PartialEq::eq")]
+	pub rtsp: RtspSettings,
+	/// URL of the running RTSP server, if any.
+	pub rtsp_url: Option<String>,
+	#[data(same_fn = "PartialEq::eq")]
+	pub hls: HlsSettings,
+	/// URL of the HLS viewer page while publishing.
+	pub hls_url: Option<String>,
+	#[data(same_fn = "PartialEq::eq")]
+	pub webrtc: WebRtcSettings,
+	/// URL of the WebRTC viewer page while publishing.
+	pub webrtc_url: Option<String>,
+	#[data(same_fn = "PartialEq::eq")]
+	pub mjpeg: MjpegSettings,
+	/// URL of the MJPEG stream while serving.
+	pub mjpeg_url: Option<String>,
+	#[data(same_fn = "PartialEq::eq")]
+	pub api: ApiSettings,
+	/// URL of the remote control API while serving.
+	pub api_url: Option<String>,
+	pub output: OutputForm,
+	pub outputs: Arc<Vec<OutputRow>>,
 }
 
 /// Video player which handles multimedia playback.
+///
+/// Wraps the GUI-free [`Recorder`], forwarding its preview frames and output
+/// health to the UI.
 pub struct VideoPlayer {
-	pub bus: gst::Bus,
-	pub pipeline: gst::Pipeline,
-
+	pub recorder: Recorder,
+	pub api: Option<ApiServer>,
+	pub event_sink: ExtEventSink,
 
 	pub paused: bool,
 	pub muted: bool,
 }
+
@@ -0,0 +1,265 @@
+use std::{error::Error, fmt::Display, str::FromStr, sync::Arc};
+
+use druid::{
+	text::ParseFormatter,
+	widget::{
+		Button, Checkbox, CrossAxisAlignment, Either, Flex, Label, List, Slider, TextBox,
+		ViewSwitcher,
+	},
+	Data, Lens, Widget, WidgetExt,
+};
+use druid_widget_nursery::DropdownSelect;
+
+use crate::gui::{
+	controller::cmd,
+	data::{
+		audio::{
+			AudioForm, AudioInputForm, AudioSourceKind, MonitorForm, ProcessingForm, SpectrumForm,
+		},
+		video::VideoViewState,
+		AppState,
+	},
+	widgets::{empty::Empty, spectrum::SpectrumView, theme},
+};
+
+pub fn panel_widget() -> impl Widget<AppState> {
+	// A new view starts with an empty history.
+	let view = ViewSwitcher::new(
+		|data: &VideoViewState, _| data.spectrum_form.waterfall,
+		|waterfall, _, _| {
+			let view = if *waterfall { SpectrumView::waterfall() } else { SpectrumView::bars() };
+			view.lens(VideoViewState::spectrum).boxed()
+		},
+	)
+	.fix_height(theme::grid(12.0))
+	.expand_width();
+
+	let apply = Button::new("Apply")
+		.on_click(|ctx, _: &mut SpectrumForm, _env| ctx.submit_command(cmd::SET_SPECTRUM));
+
+	let settings = Flex::row()
+		.cross_axis_alignment(CrossAxisAlignment::Center)
+		.with_child(Label::new("Bands"))
+		.with_spacer(theme::grid(0.5))
+		.with_child(number_widget(SpectrumForm::bands))
+		.with_spacer(theme::grid(1.0))
+		.with_child(Label::new("Hz"))
+		.with_spacer(theme::grid(0.5))
+		.with_child(number_widget(SpectrumForm::min_freq))
+		.with_spacer(theme::grid(0.5))
+		.with_child(Label::new("to"))
+		.with_spacer(theme::grid(0.5))
+		.with_child(number_widget(SpectrumForm::max_freq))
+		.with_spacer(theme::grid(1.0))
+		.with_child(Checkbox::new("Waterfall").lens(SpectrumForm::waterfall))
+		.with_spacer(theme::grid(1.0))
+		.with_child(apply)
+		.lens(VideoViewState::spectrum_form);
+
+	Flex::column()
+		.cross_axis_alignment(CrossAxisAlignment::Start)
+		.with_child(inputs_widget())
+		.with_spacer(theme::grid(1.0))
+		.with_child(view)
+		.with_spacer(theme::grid(1.0))
+		.with_child(settings)
+		.with_spacer(theme::grid(1.0))
+		.with_child(loudness_widget())
+		.with_spacer(theme::grid(1.0))
+		.with_child(monitor_widget().lens(VideoViewState::monitor))
+		.lens(AppState::video)
+}
+
+/// Audio inputs; gain, mute, pan and processing apply while dragging, the rest
+/// once the recorder is rebuilt.
+fn inputs_widget() -> impl Widget<VideoViewState> {
+	let inputs = List::new(|| {
+		let kind = DropdownSelect::new(vec![
+			("ALSA", AudioSourceKind::Alsa),
+			("PulseAudio", AudioSourceKind::Pulse),
+			("PipeWire", AudioSourceKind::PipeWire),
+			("Test tone", AudioSourceKind::Test),
+		])
+		.lens(AudioInputForm::kind);
+		let device = TextBox::new()
+			.with_placeholder("default device")
+			.lens(AudioInputForm::device)
+			.fix_width(theme::grid(16.0));
+		let gain = Label::dynamic(|input: &AudioInputForm, _| format!("{:+.0} dB", input.gain_db));
+		let processing = Either::new(
+			|input: &AudioInputForm, _| input.expanded,
+			processing_widget(),
+			Empty,
+		);
+		let input = Flex::row()
+			.cross_axis_alignment(CrossAxisAlignment::Center)
+			.with_child(kind)
+			.with_spacer(theme::grid(1.0))
+			.with_child(device)
+			.with_spacer(theme::grid(1.0))
+			.with_child(Slider::new().with_range(-30.0, 20.0).lens(AudioInputForm::gain_db))
+			.with_child(gain.fix_width(theme::grid(6.0)))
+			.with_spacer(theme::grid(1.0))
+			.with_child(Checkbox::new("Mute").lens(AudioInputForm::muted))
+			.with_spacer(theme::grid(1.0))
+			.with_child(Label::new("Pan"))
+			.with_child(Slider::new().with_range(-1.0, 1.0).lens(AudioInputForm::pan))
+			.with_spacer(theme::grid(1.0))
+			.with_child(Checkbox::new("Processing").lens(AudioInputForm::expanded));
+		Flex::column()
+			.cross_axis_alignment(CrossAxisAlignment::Start)
+			.with_child(input)
+			.with_child(processing)
+	})
+	.lens(AudioForm::inputs);
+
+	let add = Button::new("Add input").on_click(|_ctx, form: &mut AudioForm, _env| {
+		Arc::make_mut(&mut form.inputs).push(AudioInputForm::default())
+	});
+	let remove = Button::new("Remove input")
+		.on_click(|_ctx, form: &mut AudioForm, _env| {
+			Arc::make_mut(&mut form.inputs).pop();
+		})
+		.disabled_if(|form: &AudioForm, _| form.inputs.len() <= 1);
+	let layout = Flex::row()
+		.cross_axis_alignment(CrossAxisAlignment::Center)
+		.with_child(add)
+		.with_spacer(theme::grid(1.0))
+		.with_child(remove)
+		.with_spacer(theme::grid(1.0))
+		.with_child(Checkbox::new("Stereo").lens(AudioForm::stereo))
+		.with_spacer(theme::grid(1.0))
+		.with_child(Checkbox::new("Separate tracks").lens(AudioForm::tracks))
+		.lens(VideoViewState::audio);
+
+	// Rebuilding the recorder finishes the current file.
+	let apply = Button::new("Apply inputs")
+		.on_click(|ctx, _: &mut VideoViewState, _env| ctx.submit_command(cmd::SET_CAPTURE))
+		.disabled_if(|data: &VideoViewState, _| data.camara_record);
+
+	// Shifting mid-file would make the timestamps jump.
+	let apply_offset = Button::new("Apply")
+		.on_click(|ctx, _: &mut VideoViewState, _env| ctx.submit_command(cmd::SET_AV_OFFSET))
+		.disabled_if(|data: &VideoViewState, _| data.camara_record);
+	let offset = Flex::row()
+		.cross_axis_alignment(CrossAxisAlignment::Center)
+		.with_child(Label::new("Audio delay (ms)"))
+		.with_spacer(theme::grid(0.5))
+		.with_child(number_widget(VideoViewState::av_offset))
+		.with_spacer(theme::grid(1.0))
+		.with_child(apply_offset);
+
+	Flex::column()
+		.cross_axis_alignment(CrossAxisAlignment::Start)
+		.with_child(inputs.lens(VideoViewState::audio))
+		.with_spacer(theme::grid(1.0))
+		.with_child(Flex::row().with_child(layout).with_spacer(theme::grid(1.0)).with_child(apply))
+		.with_spacer(theme::grid(1.0))
+		.with_child(offset)
+}
+
+/// Noise gate, compressor and limiter of an input, and its microphone preset.
+fn processing_widget() -> impl Widget<AudioInputForm> {
+	let db = |value: fn(&ProcessingForm) -> f64| {
+		Label::dynamic(move |form: &ProcessingForm, _| format!("{:.0} dB", value(form)))
+			.fix_width(theme::grid(6.0))
+	};
+	let dynamics = Flex::row()
+		.cross_axis_alignment(CrossAxisAlignment::Center)
+		.with_child(Checkbox::new("Gate").lens(ProcessingForm::gate))
+		.with_child(Slider::new().with_range(-90.0, 0.0).lens(ProcessingForm::gate_threshold))
+		.with_child(db(|form| form.gate_threshold))
+		.with_spacer(theme::grid(1.0))
+		.with_child(Checkbox::new("Compressor").lens(ProcessingForm::compressor))
+		.with_child(Slider::new().with_range(-60.0, 0.0).lens(ProcessingForm::compressor_threshold))
+		.with_child(db(|form| form.compressor_threshold))
+		.with_child(Slider::new().with_range(1.0, 20.0).lens(ProcessingForm::compressor_ratio))
+		.with_child(
+			Label::dynamic(|form: &ProcessingForm, _| format!("{:.1}:1", form.compressor_ratio))
+				.fix_width(theme::grid(5.0)),
+		)
+		.with_spacer(theme::grid(1.0))
+		.with_child(Checkbox::new("Limiter").lens(ProcessingForm::limiter))
+		.with_child(Slider::new().with_range(-20.0, 0.0).lens(ProcessingForm::limiter_ceiling))
+		.with_child(db(|form| form.limiter_ceiling))
+		.lens(AudioInputForm::processing);
+
+	let load = Button::new("Load preset").on_click(|_ctx, input: &mut AudioInputForm, _env| {
+		input.preset_status = match input.load_preset() {
+			Ok(true) => format!("loaded the preset of {}", input.to_input()),
+			Ok(false) => format!("no preset for {}", input.to_input()),
+			Err(err) => err.to_string(),
+		};
+	});
+	let save = Button::new("Save preset").on_click(|_ctx, input: &mut AudioInputForm, _env| {
+		input.preset_status = match input.save_preset() {
+			Ok(()) => format!("saved as the preset of {}", input.to_input()),
+			Err(err) => err.to_string(),
+		};
+	});
+	let presets = Flex::row()
+		.cross_axis_alignment(CrossAxisAlignment::Center)
+		.with_child(load)
+		.with_spacer(theme::grid(1.0))
+		.with_child(save)
+		.with_spacer(theme::grid(1.0))
+		.with_child(Label::raw().lens(AudioInputForm::preset_status));
+
+	Flex::column()
+		.cross_axis_alignment(CrossAxisAlignment::Start)
+		.with_child(dynamics)
+		.with_spacer(theme::grid(0.5))
+		.with_child(presets)
+		.padding((theme::grid(2.0), theme::grid(0.5), 0.0, theme::grid(1.0)))
+}
+
+/// Loudness of the mix after EBU R128, blank without `ebur128level`.
+fn loudness_widget() -> impl Widget<VideoViewState> {
+	Label::dynamic(|data: &VideoViewState, _| match data.loudness {
+		Some(loudness) => format!(
+			"Loudness {} integrated, {} short-term, {:.1} LU range",
+			lufs(loudness.integrated),
+			lufs(loudness.short_term),
+			loudness.range
+		),
+		None => String::new(),
+	})
+}
+
+/// `value` in LUFS, a dash for silence.
+fn lufs(value: f64) -> String {
+	if value.is_finite() {
+		format!("{:.1} LUFS", value)
+	} else {
+		"- LUFS".to_string()
+	}
+}
+
+/// Listen-through controls; the volume applies while dragging.
+fn monitor_widget() -> impl Widget<MonitorForm> {
+	let apply = Button::new("Apply")
+		.on_click(|ctx, _: &mut MonitorForm, _env| ctx.submit_command(cmd::SET_MONITOR));
+
+	Flex::row()
+		.cross_axis_alignment(CrossAxisAlignment::Center)
+		.with_child(Checkbox::new("Listen").lens(MonitorForm::listening))
+		.with_spacer(theme::grid(1.0))
+		.with_child(Slider::new().with_range(0.0, 2.0).lens(MonitorForm::volume))
+		.with_spacer(theme::grid(1.0))
+		.with_child(Label::new("Latency (ms)"))
+		.with_spacer(theme::grid(0.5))
+		.with_child(number_widget(MonitorForm::latency))
+		.with_spacer(theme::grid(1.0))
+		.with_child(apply)
+}
+
+/// Text box editing the number behind `lens`.
+fn number_widget<D, T, L>(lens: L) -> impl Widget<D>
+where
+	D: Data,
+	T: Data + Display + FromStr,
+	T::Err: Error + 'static,
+	L: Lens<D, T> + 'static,
+{
+	TextBox::new().with_formatter(ParseFormatter::new()).lens(lens).fix_width(theme::grid(8.0))
+}
@@ -0,0 +1,180 @@
+use druid::{
+	widget::{Button, Checkbox, CrossAxisAlignment, Flex, Label, Scroll, Slider, Stepper},
+	Env, EventCtx, Lens, LensExt, Widget, WidgetExt,
+};
+use druid_widget_nursery::DropdownSelect;
+
+use crate::{
+	gui::{
+		data::{
+			image::{CropAspect, ImageForm, TransformForm},
+			video::VideoViewState,
+		},
+		widgets::theme,
+	},
+	media::{balance::ImageAdjustments, transform::Rotation},
+};
+
+/// Side panel with the image adjustments and transforms of the source,
+/// previewed while dragging.
+pub fn panel_widget() -> impl Widget<VideoViewState> {
+	let sliders = Flex::column()
+		.cross_axis_alignment(CrossAxisAlignment::Start)
+		.with_child(slider_widget("Brightness", -1.0, 1.0, ImageForm::brightness))
+		.with_child(slider_widget("Contrast", 0.0, 2.0, ImageForm::contrast))
+		.with_child(slider_widget("Saturation", 0.0, 2.0, ImageForm::saturation))
+		.with_child(slider_widget("Hue", -1.0, 1.0, ImageForm::hue))
+		.with_spacer(theme::grid(1.0))
+		.with_child(
+			Button::new("Reset")
+				.on_click(|_ctx, form: &mut ImageForm, _env| *form = ImageForm::default()),
+		)
+		.lens(VideoViewState::image);
+
+	let presets = ImageAdjustments::PRESETS.iter().fold(
+		Flex::column().cross_axis_alignment(CrossAxisAlignment::Start),
+		|column, &(name, adjustments)| {
+			column.with_spacer(theme::grid(0.5)).with_child(
+				Button::new(name)
+					.on_click(move |_ctx, form: &mut ImageForm, _env| *form = adjustments.into())
+					.lens(VideoViewState::image),
+			)
+		},
+	);
+
+	let save = Button::new("Save for camera").on_click(|_ctx, data: &mut VideoViewState, _env| {
+		if let Err(err) = data.image.save_for(&data.source.to_source()) {
+			log::error!("failed to save the image adjustments: {}", err);
+		}
+	});
+
+	let panel = Flex::column()
+		.cross_axis_alignment(CrossAxisAlignment::Start)
+		.with_child(Label::new("Image"))
+		.with_spacer(theme::grid(1.0))
+		.with_child(sliders)
+		.with_spacer(theme::grid(1.0))
+		.with_child(Label::new("Presets"))
+		.with_child(presets)
+		.with_spacer(theme::grid(1.0))
+		.with_child(save)
+		.with_spacer(theme::grid(2.0))
+		.with_child(Label::new("Transform"))
+		.with_spacer(theme::grid(1.0))
+		.with_child(transform_widget())
+		.padding(theme::grid(1.0));
+	Scroll::new(panel).vertical().fix_width(theme::grid(24.0))
+}
+
+/// Mirror, flip, rotation and crop, each with a toggle to record it too.
+fn transform_widget() -> impl Widget<VideoViewState> {
+	let rotate = |turns: i32| {
+		move |_ctx: &mut EventCtx, form: &mut TransformForm, _env: &Env| {
+			form.quarter_turns = (form.quarter_turns + turns).rem_euclid(4);
+		}
+	};
+	let rotation = Flex::row()
+		.cross_axis_alignment(CrossAxisAlignment::Center)
+		.with_child(Button::new("-90°").on_click(rotate(-1)))
+		.with_spacer(theme::grid(0.5))
+		.with_child(Label::dynamic(|form: &TransformForm, _| {
+			Rotation::from_quarter_turns(form.quarter_turns).to_string()
+		}))
+		.with_spacer(theme::grid(0.5))
+		.with_child(Button::new("+90°").on_click(rotate(1)));
+	let crop = Flex::column()
+		.cross_axis_alignment(CrossAxisAlignment::Start)
+		.with_child(Label::new("Crop"))
+		.with_child(
+			Flex::row()
+				.cross_axis_alignment(CrossAxisAlignment::Center)
+				.with_child(Checkbox::new("Select").lens(TransformForm::selecting))
+				.with_spacer(theme::grid(0.5))
+				.with_child(
+					DropdownSelect::new(vec![
+						("Free", CropAspect::Free),
+						("16:9", CropAspect::Wide),
+						("4:3", CropAspect::Standard),
+						("1:1", CropAspect::Square),
+					])
+					.lens(TransformForm::aspect),
+				),
+		)
+		.with_child(inset_widget("Left", TransformForm::crop_left))
+		.with_child(inset_widget("Top", TransformForm::crop_top))
+		.with_child(inset_widget("Right", TransformForm::crop_right))
+		.with_child(inset_widget("Bottom", TransformForm::crop_bottom))
+		.with_child(Button::new("Full frame").on_click(|_ctx, form: &mut TransformForm, _env| {
+			form.crop_left = 0.0;
+			form.crop_top = 0.0;
+			form.crop_right = 0.0;
+			form.crop_bottom = 0.0;
+		}));
+
+	Flex::column()
+		.cross_axis_alignment(CrossAxisAlignment::Start)
+		.with_child(scoped_row(
+			Checkbox::new("Mirror").lens(TransformForm::mirror),
+			TransformForm::mirror_recording,
+		))
+		.with_child(scoped_row(
+			Checkbox::new("Flip").lens(TransformForm::flip),
+			TransformForm::flip_recording,
+		))
+		.with_child(scoped_row(rotation, TransformForm::rotation_recording))
+		.with_child(scoped_row(crop, TransformForm::crop_recording))
+		.with_spacer(theme::grid(1.0))
+		.with_child(Label::new("Wheel zooms, drag pans"))
+		.with_child(Checkbox::new("Record zoom").lens(VideoViewState::zoom_recording))
+}
+
+/// `control` beside a checkbox choosing whether the recording gets it too.
+/// The recording keeps its transform while recording, so the checkbox and a
+/// recorded control are locked meanwhile.
+fn scoped_row(
+	control: impl Widget<TransformForm> + 'static,
+	recording: impl Lens<TransformForm, bool> + Clone + 'static,
+) -> impl Widget<VideoViewState> {
+	let recorded = recording.clone();
+	let control = control.lens(VideoViewState::transform).disabled_if(
+		move |data: &VideoViewState, _| data.camara_record && recorded.get(&data.transform),
+	);
+	let record = Checkbox::new("Record")
+		.lens(VideoViewState::transform.then(recording))
+		.disabled_if(|data: &VideoViewState, _| data.camara_record);
+	Flex::row()
+		.cross_axis_alignment(CrossAxisAlignment::Start)
+		.with_flex_child(control, 1.0)
+		.with_spacer(theme::grid(0.5))
+		.with_child(record)
+		.padding((0.0, theme::grid(0.25)))
+}
+
+/// Crop inset behind `lens`, stepped in even pixels.
+fn inset_widget(
+	name: &str,
+	lens: impl Lens<TransformForm, f64> + Clone + 'static,
+) -> impl Widget<TransformForm> {
+	let value = Label::dynamic(|inset: &f64, _| format!("{} px", inset)).lens(lens.clone());
+	Flex::row()
+		.cross_axis_alignment(CrossAxisAlignment::Center)
+		.with_child(Label::new(name))
+		.with_flex_spacer(1.0)
+		.with_child(value)
+		.with_child(Stepper::new().with_range(0.0, 4096.0).with_step(2.0).lens(lens))
+}
+
+/// Labeled slider from `min` to `max` editing the value behind `lens`.
+fn slider_widget(
+	name: &str,
+	min: f64,
+	max: f64,
+	lens: impl Lens<ImageForm, f64> + Clone + 'static,
+) -> impl Widget<ImageForm> {
+	let value = Label::dynamic(|value: &f64, _| format!("{:.2}", value)).lens(lens.clone());
+	let title = Flex::row().with_child(Label::new(name)).with_flex_spacer(1.0).with_child(value);
+	Flex::column()
+		.cross_axis_alignment(CrossAxisAlignment::Start)
+		.with_child(title)
+		.with_child(Slider::new().with_range(min, max).lens(lens).expand_width())
+}
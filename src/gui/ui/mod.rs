@@ -1,14 +1,23 @@
+mod audio;
+mod image;
 mod playback;
+mod source;
+mod streaming;
 
 use druid::{
 	theme,
-	widget::{Align, Axis, CrossAxisAlignment, Flex, SizedBox, Tabs, TabsEdge},
+	widget::{Align, Axis, CrossAxisAlignment, Either, Flex, SizedBox, Tabs, TabsEdge},
 	Color, Data, ExtEventSink, Lens, UnitPoint, Widget, WidgetExt,
 };
 
 use crate::gui::{
-	data::{video, AppState},
+	data::{
+		video::{self, VideoViewState},
+		AppState,
+	},
 	widgets::{
+		empty::Empty,
+		meter::LevelMeter,
 		theme::{self as CustomTheme, ThemeScope},
 	},
 };
@@ -17,14 +26,16 @@ use crate::gui::{
 pub fn root_widget() -> impl Widget<AppState> {
 	let layout = Flex::column()
 		.cross_axis_alignment(CrossAxisAlignment::Start)
-		.with_flex_child(
-			video::VideoView::new()
-				.expand()
-				.lens(AppState::video),
-			1.0,
-		)
+		.with_flex_child(preview_widget().lens(AppState::video), 1.0)
 		.with_spacer(CustomTheme::grid(6.0))
-		.with_child(playback::panel_widget())
+		.with_child(
+			Tabs::new()
+				.with_edge(TabsEdge::Bottom)
+				.with_tab("Record", playback::panel_widget())
+				.with_tab("Source", source::panel_widget())
+				.with_tab("Audio", audio::panel_widget())
+				.with_tab("Stream", streaming::panel_widget()),
+		)
 		.background(theme::BACKGROUND_LIGHT);
 
 	let sized = SizedBox::new(layout)
@@ -39,3 +50,24 @@ pub fn root_widget() -> impl Widget<AppState> {
 	ThemeScope::new(Align::centered(sized))
 	// ThemeScope::new(layout)
 }
+
+/// Camera preview with the microphone level beside it, so a muted mic shows
+/// before anything is recorded. Audio-only recordings show a large meter in
+/// place of the preview instead. The image adjustments open to the right.
+fn preview_widget() -> impl Widget<VideoViewState> {
+	let meter = Either::new(
+		|video: &VideoViewState, _| video.capturing.audio_only().is_some(),
+		Empty,
+		LevelMeter::vertical().fix_width(CustomTheme::grid(3.0)).lens(VideoViewState::level),
+	);
+	let adjustments = Either::new(
+		|video: &VideoViewState, _| video.adjusting && video.capturing.audio_only().is_none(),
+		image::panel_widget(),
+		Empty,
+	);
+	Flex::row()
+		.cross_axis_alignment(CrossAxisAlignment::Fill)
+		.with_flex_child(video::VideoView::new().expand(), 1.0)
+		.with_child(meter)
+		.with_child(adjustments)
+}
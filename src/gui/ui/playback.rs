@@ -1,4 +1,4 @@
-use std::time::Duration;
+use std::{path::Path, time::Duration};
 
 use druid::{
 	widget::{
@@ -11,9 +11,9 @@ use druid::{
 use druid_widget_nursery::DropdownSelect;
 
 use crate::gui::{
-	controller::cmd,
+	controller::{api, cmd},
 	data::{
-		video::{VideoPlayer, VideoPlayerState, VideoRate, VideoViewState},
+		video::{CaptureKind, VideoPlayer, VideoPlayerState, VideoRate, VideoViewState},
 		AppState,
 	},
 	widgets::{
@@ -25,20 +25,47 @@ use crate::gui::{
 
 pub fn panel_widget() -> impl Widget<AppState> {
 
-	let controls = Flex::row().with_child(Either::new(
-		|video: &VideoViewState, _| !video.camara_record,
-		Button::new("Start Record").on_click(|ctx, state: &mut VideoViewState, _env| {
-			state.camara_record = true;
-			ctx.submit_command(cmd::PLAY_RESUME)
-		}),
-		Button::new("Stop Record").on_click(|ctx, state: &mut VideoViewState, _env| {
-			state.camara_record = false;
-			ctx.submit_command(cmd::PLAY_PAUSE)
-		}),
-	));
+	let controls = Flex::row()
+		.with_child(Either::new(
+			|video: &VideoViewState, _| !video.camara_record,
+			Button::new("Start Record").on_click(|ctx, state: &mut VideoViewState, _env| {
+				state.camara_record = true;
+				ctx.submit_command(cmd::PLAY_RESUME)
+			}),
+			Button::new("Stop Record").on_click(|ctx, state: &mut VideoViewState, _env| {
+				state.camara_record = false;
+				ctx.submit_command(cmd::PLAY_PAUSE)
+			}),
+		))
+		.with_spacer(theme::grid(1.0))
+		.with_child(Button::new("Snapshot").on_click(|ctx, _: &mut VideoViewState, _env| {
+			ctx.submit_command(cmd::SNAPSHOT.with(api::snapshot_path(Path::new(".media"))))
+		}));
+
+	let capture = Flex::row()
+		.with_child(
+			DropdownSelect::new(vec![
+				("Video", CaptureKind::Video),
+				("Opus", CaptureKind::Opus),
+				("FLAC", CaptureKind::Flac),
+				("WAV", CaptureKind::Wav),
+				("M4A", CaptureKind::M4a),
+			])
+			.lens(VideoViewState::capture),
+		)
+		.with_spacer(theme::grid(1.0))
+		.with_child(
+			Button::new("Apply")
+				.on_click(|ctx, _: &mut VideoViewState, _env| ctx.submit_command(cmd::SET_CAPTURE))
+				.disabled_if(|video: &VideoViewState, _| {
+					video.camara_record || video.capture == video.capturing
+				}),
+		);
 
 	Flex::column()
 		.with_child(controls)
+		.with_spacer(theme::grid(1.0))
+		.with_child(capture)
 		.lens(AppState::video)
 	// .controller(PlaybackController::new())
 }
@@ -0,0 +1,127 @@
+use druid::{
+	text::ParseFormatter,
+	widget::{Button, Checkbox, CrossAxisAlignment, Flex, Label, TextBox, ViewSwitcher},
+	Widget, WidgetExt,
+};
+use druid_widget_nursery::DropdownSelect;
+
+use crate::gui::{
+	controller::cmd,
+	data::{
+		source::{SourceForm, SourceKind},
+		video::VideoViewState,
+		AppState,
+	},
+	widgets::theme,
+};
+
+pub fn panel_widget() -> impl Widget<AppState> {
+	let kind = DropdownSelect::new(vec![
+		("Camera", SourceKind::Device),
+		("RTSP", SourceKind::Rtsp),
+		("HTTP MJPEG", SourceKind::HttpMjpeg),
+		("UDP RTP", SourceKind::UdpRtp),
+		("GStreamer", SourceKind::Custom),
+	])
+	.lens(SourceForm::kind);
+
+	let details = ViewSwitcher::new(
+		|form: &SourceForm, _| form.kind,
+		|kind, _, _| match kind {
+			SourceKind::Device => Label::new("Local camera").boxed(),
+			SourceKind::Rtsp => Flex::row()
+				.with_flex_child(url_widget(), 1.0)
+				.with_spacer(theme::grid(1.0))
+				.with_child(latency_widget())
+				.with_spacer(theme::grid(1.0))
+				.with_child(Checkbox::new("TCP").lens(SourceForm::tcp))
+				.boxed(),
+			SourceKind::HttpMjpeg => url_widget().boxed(),
+			SourceKind::UdpRtp => Flex::row()
+				.with_child(TextBox::new().lens(SourceForm::address))
+				.with_spacer(theme::grid(1.0))
+				.with_child(
+					TextBox::new()
+						.with_formatter(ParseFormatter::new())
+						.lens(SourceForm::port)
+						.fix_width(theme::grid(8.0)),
+				)
+				.with_spacer(theme::grid(1.0))
+				.with_child(latency_widget())
+				.boxed(),
+			SourceKind::Custom => TextBox::new()
+				.with_placeholder("gst-launch description")
+				.expand_width()
+				.lens(SourceForm::description)
+				.boxed(),
+		},
+	);
+
+	let apply = Button::new("Apply")
+		.on_click(|ctx, _: &mut SourceForm, _env| ctx.submit_command(cmd::SET_SOURCE));
+
+	let source = Flex::row()
+		.cross_axis_alignment(CrossAxisAlignment::Center)
+		.with_child(kind)
+		.with_spacer(theme::grid(1.0))
+		.with_flex_child(details, 1.0)
+		.with_spacer(theme::grid(1.0))
+		.with_child(apply)
+		.lens(VideoViewState::source);
+
+	Flex::column()
+		.cross_axis_alignment(CrossAxisAlignment::Start)
+		.with_child(source)
+		.with_spacer(theme::grid(1.0))
+		.with_child(mode_widget())
+		.with_spacer(theme::grid(1.0))
+		.with_child(Checkbox::new("Adjust image").lens(VideoViewState::adjusting))
+		.lens(AppState::video)
+}
+
+/// Mode selector, filled by querying the source.
+fn mode_widget() -> impl Widget<VideoViewState> {
+	let probe = Button::new("Modes")
+		.on_click(|ctx, _: &mut VideoViewState, _env| ctx.submit_command(cmd::PROBE_MODES));
+
+	// Rebuilt whenever a new list of modes arrives.
+	let modes = ViewSwitcher::new(
+		|data: &VideoViewState, _| data.source_modes.clone(),
+		|modes, _, _| {
+			if modes.is_empty() {
+				return Label::new("Automatic").boxed();
+			}
+			let mut options = vec![("Automatic".to_string(), None)];
+			options.extend(modes.iter().enumerate().map(|(i, mode)| (mode.to_string(), Some(i))));
+			DropdownSelect::new(options).lens(VideoViewState::source_mode).boxed()
+		},
+	);
+
+	let apply = Button::new("Apply mode")
+		.on_click(|ctx, _: &mut VideoViewState, _env| ctx.submit_command(cmd::SET_MODE))
+		.disabled_if(|data: &VideoViewState, _| data.source_modes.is_empty());
+
+	Flex::row()
+		.cross_axis_alignment(CrossAxisAlignment::Center)
+		.with_child(probe)
+		.with_spacer(theme::grid(1.0))
+		.with_child(modes)
+		.with_spacer(theme::grid(1.0))
+		.with_child(apply)
+}
+
+fn url_widget() -> impl Widget<SourceForm> {
+	TextBox::new().with_placeholder("URL").expand_width().lens(SourceForm::url)
+}
+
+fn latency_widget() -> impl Widget<SourceForm> {
+	Flex::row()
+		.with_child(Label::new("Latency (ms)"))
+		.with_spacer(theme::grid(0.5))
+		.with_child(
+			TextBox::new()
+				.with_formatter(ParseFormatter::new())
+				.lens(SourceForm::latency)
+				.fix_width(theme::grid(8.0)),
+		)
+}
@@ -0,0 +1,366 @@
+use std::path::PathBuf;
+
+use druid::{
+	lens::Map,
+	text::ParseFormatter,
+	widget::{
+		Button, Checkbox, CrossAxisAlignment, Either, Flex, Label, List, Scroll, TextBox, ViewSwitcher,
+	},
+	Widget, WidgetExt,
+};
+use druid_widget_nursery::DropdownSelect;
+
+use crate::gui::{
+	controller::cmd,
+	data::{
+		output::{OutputForm, OutputKind, OutputRow},
+		video::VideoViewState,
+		AppState,
+	},
+	widgets::theme,
+};
+
+pub fn panel_widget() -> impl Widget<AppState> {
+	Flex::column()
+		.cross_axis_alignment(CrossAxisAlignment::Start)
+		.with_child(rtsp_widget())
+		.with_spacer(theme::grid(2.0))
+		.with_child(hls_widget())
+		.with_spacer(theme::grid(2.0))
+		.with_child(webrtc_widget())
+		.with_spacer(theme::grid(2.0))
+		.with_child(mjpeg_widget())
+		.with_spacer(theme::grid(2.0))
+		.with_child(api_widget())
+		.with_spacer(theme::grid(2.0))
+		.with_child(outputs_widget())
+		.lens(AppState::video)
+}
+
+fn rtsp_widget() -> impl Widget<VideoViewState> {
+	let port = TextBox::new()
+		.with_formatter(ParseFormatter::new())
+		.lens(Map::new(
+			|video: &VideoViewState| video.rtsp.port,
+			|video: &mut VideoViewState, port| video.rtsp.port = port,
+		))
+		.fix_width(theme::grid(8.0));
+	let mount = TextBox::new().lens(Map::new(
+		|video: &VideoViewState| video.rtsp.mount.clone(),
+		|video: &mut VideoViewState, mount| video.rtsp.mount = mount,
+	));
+	let username = TextBox::new().with_placeholder("user").lens(Map::new(
+		|video: &VideoViewState| video.rtsp.username.clone(),
+		|video: &mut VideoViewState, username| video.rtsp.username = username,
+	));
+	let password = TextBox::new().with_placeholder("password").lens(Map::new(
+		|video: &VideoViewState| video.rtsp.password.clone(),
+		|video: &mut VideoViewState, password| video.rtsp.password = password,
+	));
+
+	let settings = Flex::row()
+		.with_child(Label::new("RTSP port"))
+		.with_spacer(theme::grid(1.0))
+		.with_child(port)
+		.with_spacer(theme::grid(1.0))
+		.with_child(mount)
+		.with_spacer(theme::grid(1.0))
+		.with_child(username)
+		.with_spacer(theme::grid(1.0))
+		.with_child(password)
+		.disabled_if(|video: &VideoViewState, _| video.rtsp_url.is_some());
+
+	let controls = Flex::row()
+		.with_child(Either::new(
+			|video: &VideoViewState, _| video.rtsp_url.is_none(),
+			Button::new("Serve RTSP").on_click(|ctx, _: &mut VideoViewState, _env| {
+				ctx.submit_command(cmd::RTSP_START)
+			}),
+			Button::new("Stop RTSP").on_click(|ctx, _: &mut VideoViewState, _env| {
+				ctx.submit_command(cmd::RTSP_STOP)
+			}),
+		))
+		.with_spacer(theme::grid(1.0))
+		.with_child(Label::dynamic(|video: &VideoViewState, _| {
+			video.rtsp_url.clone().unwrap_or_default()
+		}));
+
+	Flex::column()
+		.cross_axis_alignment(CrossAxisAlignment::Start)
+		.with_child(settings)
+		.with_spacer(theme::grid(1.0))
+		.with_child(controls)
+}
+
+fn hls_widget() -> impl Widget<VideoViewState> {
+	let directory = TextBox::new().lens(Map::new(
+		|video: &VideoViewState| video.hls.directory.display().to_string(),
+		|video: &mut VideoViewState, directory: String| {
+			video.hls.directory = PathBuf::from(directory)
+		},
+	));
+	let segment = TextBox::new()
+		.with_formatter(ParseFormatter::new())
+		.lens(Map::new(
+			|video: &VideoViewState| video.hls.segment_duration,
+			|video: &mut VideoViewState, duration| video.hls.segment_duration = duration,
+		))
+		.fix_width(theme::grid(5.0));
+	let length = TextBox::new()
+		.with_formatter(ParseFormatter::new())
+		.lens(Map::new(
+			|video: &VideoViewState| video.hls.playlist_length,
+			|video: &mut VideoViewState, length| video.hls.playlist_length = length,
+		))
+		.fix_width(theme::grid(5.0));
+	let port = TextBox::new()
+		.with_formatter(ParseFormatter::new())
+		.lens(Map::new(
+			|video: &VideoViewState| video.hls.http_port,
+			|video: &mut VideoViewState, port| video.hls.http_port = port,
+		))
+		.fix_width(theme::grid(8.0));
+
+	let settings = Flex::row()
+		.with_child(Label::new("HLS"))
+		.with_spacer(theme::grid(1.0))
+		.with_child(directory)
+		.with_spacer(theme::grid(1.0))
+		.with_child(Label::new("segment (s)"))
+		.with_child(segment)
+		.with_spacer(theme::grid(1.0))
+		.with_child(Label::new("segments"))
+		.with_child(length)
+		.with_spacer(theme::grid(1.0))
+		.with_child(Label::new("HTTP port"))
+		.with_child(port)
+		.disabled_if(|video: &VideoViewState, _| video.hls_url.is_some());
+
+	let controls = Flex::row()
+		.with_child(Either::new(
+			|video: &VideoViewState, _| video.hls_url.is_none(),
+			Button::new("Publish HLS").on_click(|ctx, _: &mut VideoViewState, _env| {
+				ctx.submit_command(cmd::HLS_START)
+			}),
+			Button::new("Stop HLS").on_click(|ctx, _: &mut VideoViewState, _env| {
+				ctx.submit_command(cmd::HLS_STOP)
+			}),
+		))
+		.with_spacer(theme::grid(1.0))
+		.with_child(Label::dynamic(|video: &VideoViewState, _| {
+			video.hls_url.clone().unwrap_or_default()
+		}));
+
+	Flex::column()
+		.cross_axis_alignment(CrossAxisAlignment::Start)
+		.with_child(settings)
+		.with_spacer(theme::grid(1.0))
+		.with_child(controls)
+		.with_spacer(theme::grid(1.0))
+		.with_child(Label::new(
+			"The viewer plays in Safari and mobile browsers only, elsewhere open \
+			 playlist.m3u8 in VLC or use WebRTC.",
+		))
+}
+
+fn webrtc_widget() -> impl Widget<VideoViewState> {
+	let port = TextBox::new()
+		.with_formatter(ParseFormatter::new())
+		.lens(Map::new(
+			|video: &VideoViewState| video.webrtc.port,
+			|video: &mut VideoViewState, port| video.webrtc.port = port,
+		))
+		.fix_width(theme::grid(8.0))
+		.disabled_if(|video: &VideoViewState, _| video.webrtc_url.is_some());
+
+	Flex::row()
+		.with_child(Label::new("WebRTC port"))
+		.with_spacer(theme::grid(1.0))
+		.with_child(port)
+		.with_spacer(theme::grid(1.0))
+		.with_child(Either::new(
+			|video: &VideoViewState, _| video.webrtc_url.is_none(),
+			Button::new("Publish WebRTC").on_click(|ctx, _: &mut VideoViewState, _env| {
+				ctx.submit_command(cmd::WEBRTC_START)
+			}),
+			Button::new("Stop WebRTC").on_click(|ctx, _: &mut VideoViewState, _env| {
+				ctx.submit_command(cmd::WEBRTC_STOP)
+			}),
+		))
+		.with_spacer(theme::grid(1.0))
+		.with_child(Label::dynamic(|video: &VideoViewState, _| {
+			video.webrtc_url.clone().unwrap_or_default()
+		}))
+}
+
+fn mjpeg_widget() -> impl Widget<VideoViewState> {
+	let port = TextBox::new()
+		.with_formatter(ParseFormatter::new())
+		.lens(Map::new(
+			|video: &VideoViewState| video.mjpeg.port,
+			|video: &mut VideoViewState, port| video.mjpeg.port = port,
+		))
+		.fix_width(theme::grid(8.0));
+	let quality = TextBox::new()
+		.with_formatter(ParseFormatter::new())
+		.lens(Map::new(
+			|video: &VideoViewState| video.mjpeg.quality,
+			|video: &mut VideoViewState, quality| video.mjpeg.quality = quality,
+		))
+		.fix_width(theme::grid(5.0));
+
+	Flex::row()
+		.with_child(
+			Flex::row()
+				.with_child(Label::new("MJPEG port"))
+				.with_spacer(theme::grid(1.0))
+				.with_child(port)
+				.with_spacer(theme::grid(1.0))
+				.with_child(Label::new("quality"))
+				.with_child(quality)
+				.disabled_if(|video: &VideoViewState, _| video.mjpeg_url.is_some()),
+		)
+		.with_spacer(theme::grid(1.0))
+		.with_child(Either::new(
+			|video: &VideoViewState, _| video.mjpeg_url.is_none(),
+			Button::new("Serve MJPEG").on_click(|ctx, _: &mut VideoViewState, _env| {
+				ctx.submit_command(cmd::MJPEG_START)
+			}),
+			Button::new("Stop MJPEG").on_click(|ctx, _: &mut VideoViewState, _env| {
+				ctx.submit_command(cmd::MJPEG_STOP)
+			}),
+		))
+		.with_spacer(theme::grid(1.0))
+		.with_child(Label::dynamic(|video: &VideoViewState, _| {
+			video.mjpeg_url.clone().unwrap_or_default()
+		}))
+}
+
+fn api_widget() -> impl Widget<VideoViewState> {
+	let port = TextBox::new()
+		.with_formatter(ParseFormatter::new())
+		.lens(Map::new(
+			|video: &VideoViewState| video.api.port,
+			|video: &mut VideoViewState, port| video.api.port = port,
+		))
+		.fix_width(theme::grid(8.0));
+	let lan = Checkbox::new("LAN").lens(Map::new(
+		|video: &VideoViewState| video.api.lan,
+		|video: &mut VideoViewState, lan| video.api.lan = lan,
+	));
+	let token = TextBox::new()
+		.with_placeholder("token")
+		.lens(Map::new(
+			|video: &VideoViewState| video.api.token.clone(),
+			|video: &mut VideoViewState, token| video.api.token = token,
+		))
+		.fix_width(theme::grid(16.0));
+
+	Flex::row()
+		.with_child(
+			Flex::row()
+				.with_child(Label::new("Remote API port"))
+				.with_spacer(theme::grid(1.0))
+				.with_child(port)
+				.with_spacer(theme::grid(1.0))
+				.with_child(lan)
+				.with_spacer(theme::grid(1.0))
+				.with_child(token)
+				.disabled_if(|video: &VideoViewState, _| video.api_url.is_some()),
+		)
+		.with_spacer(theme::grid(1.0))
+		.with_child(Either::new(
+			|video: &VideoViewState, _| video.api_url.is_none(),
+			Button::new("Enable API").on_click(|ctx, _: &mut VideoViewState, _env| {
+				ctx.submit_command(cmd::API_START)
+			}),
+			Button::new("Disable API").on_click(|ctx, _: &mut VideoViewState, _env| {
+				ctx.submit_command(cmd::API_STOP)
+			}),
+		))
+		.with_spacer(theme::grid(1.0))
+		.with_child(Label::dynamic(|video: &VideoViewState, _| {
+			video.api_url.clone().unwrap_or_default()
+		}))
+}
+
+fn outputs_widget() -> impl Widget<VideoViewState> {
+	let kind = DropdownSelect::new(vec![
+		("SRT", OutputKind::Srt),
+		("RTP/UDP", OutputKind::Rtp),
+		("GStreamer", OutputKind::Custom),
+	])
+	.lens(OutputForm::kind);
+
+	let details = ViewSwitcher::new(
+		|form: &OutputForm, _| form.kind,
+		|kind, _, _| match kind {
+			OutputKind::Srt => address_widget()
+				.with_spacer(theme::grid(1.0))
+				.with_child(Checkbox::new("listener").lens(OutputForm::listener))
+				.with_spacer(theme::grid(1.0))
+				.with_child(
+					TextBox::new().with_placeholder("passphrase").lens(OutputForm::passphrase),
+				)
+				.boxed(),
+			OutputKind::Rtp => address_widget()
+				.with_spacer(theme::grid(1.0))
+				.with_child(Label::new("multicast TTL"))
+				.with_child(
+					TextBox::new()
+						.with_formatter(ParseFormatter::new())
+						.lens(OutputForm::ttl)
+						.fix_width(theme::grid(5.0)),
+				)
+				.boxed(),
+			OutputKind::Custom => TextBox::new()
+				.with_placeholder("gst-launch description taking H.264")
+				.expand_width()
+				.lens(OutputForm::description)
+				.boxed(),
+		},
+	);
+
+	let form = Flex::row()
+		.with_child(kind)
+		.with_spacer(theme::grid(1.0))
+		.with_flex_child(details, 1.0)
+		.with_spacer(theme::grid(1.0))
+		.with_child(
+			Button::new("Add output")
+				.on_click(|ctx, _: &mut OutputForm, _env| ctx.submit_command(cmd::OUTPUT_ADD)),
+		)
+		.lens(VideoViewState::output);
+
+	let outputs = List::new(|| {
+		Flex::row()
+			.with_child(Label::dynamic(|row: &OutputRow, _| row.label.clone()))
+			.with_spacer(theme::grid(1.0))
+			.with_flex_child(
+				Label::dynamic(|row: &OutputRow, _| row.health.clone()).expand_width(),
+				1.0,
+			)
+			.with_child(Button::new("Remove").on_click(|ctx, row: &mut OutputRow, _env| {
+				ctx.submit_command(cmd::OUTPUT_REMOVE.with(row.id))
+			}))
+	})
+	.lens(VideoViewState::outputs);
+
+	Flex::column()
+		.cross_axis_alignment(CrossAxisAlignment::Start)
+		.with_child(form)
+		.with_spacer(theme::grid(1.0))
+		.with_child(Scroll::new(outputs).vertical().fix_height(theme::grid(12.0)))
+}
+
+fn address_widget() -> Flex<OutputForm> {
+	Flex::row()
+		.with_child(TextBox::new().with_placeholder("host").lens(OutputForm::host))
+		.with_spacer(theme::grid(1.0))
+		.with_child(
+			TextBox::new()
+				.with_formatter(ParseFormatter::new())
+				.lens(OutputForm::port)
+				.fix_width(theme::grid(8.0)),
+		)
+}
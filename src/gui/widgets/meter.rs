@@ -0,0 +1,217 @@
+use std::time::{Duration, Instant};
+
+use druid::{
+	widget::{prelude::*, Axis},
+	Point, Rect,
+};
+
+use crate::{
+	gui::widgets::theme,
+	media::level::{normalize, AudioLevel},
+};
+
+/// Decibels below full scale shown by the meter.
+const RANGE_DB: f64 = 60.0;
+/// Levels above this are drawn in the warning color.
+const WARNING_DB: f64 = -18.0;
+/// Levels above this are drawn in the danger color.
+const DANGER_DB: f64 = -6.0;
+/// Peaks at or above this count as clipping.
+const CLIP_DB: f64 = -0.1;
+/// How long the peak hold marker stays put before falling.
+const HOLD: Duration = Duration::from_millis(1500);
+/// How fast the peak hold marker falls afterwards.
+const FALL_DB_PER_SEC: f64 = 20.0;
+
+/// Highest recent peak of a channel.
+#[derive(Debug, Clone, Copy)]
+struct PeakHold {
+	db: f64,
+	since: Instant,
+}
+
+impl PeakHold {
+	/// Level of the marker at `now`, falling once the hold time is over.
+	fn at(&self, now: Instant) -> f64 {
+		match now.duration_since(self.since).checked_sub(HOLD) {
+			Some(falling) => self.db - falling.as_secs_f64() * FALL_DB_PER_SEC,
+			None => self.db,
+		}
+	}
+}
+
+/// Audio level meter with one bar per channel.
+///
+/// Bars show the RMS level solid and the peak level translucent, with a
+/// marker holding the highest recent peak. A channel that clipped lights up
+/// its clip indicator until the meter is clicked.
+#[derive(Debug)]
+pub struct LevelMeter {
+	axis: Axis,
+	holds: Vec<PeakHold>,
+	clipped: Vec<bool>,
+}
+
+impl Default for LevelMeter {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl LevelMeter {
+	/// Horizontal meter.
+	pub fn new() -> Self {
+		Self { axis: Axis::Horizontal, holds: Vec::new(), clipped: Vec::new() }
+	}
+
+	/// Vertical meter, as used next to the preview.
+	pub fn vertical() -> Self {
+		Self { axis: Axis::Vertical, ..Self::new() }
+	}
+
+	/// Follow the latest peaks, holding the highest for a while.
+	fn track(&mut self, level: &AudioLevel) {
+		let now = Instant::now();
+		let channels = level.peak.len();
+		self.holds.resize(channels, PeakHold { db: -RANGE_DB, since: now });
+		self.clipped.resize(channels, false);
+		for (i, &peak) in level.peak.iter().enumerate() {
+			if peak >= self.holds[i].at(now) {
+				self.holds[i] = PeakHold { db: peak, since: now };
+			}
+			self.clipped[i] |= peak >= CLIP_DB;
+		}
+	}
+
+	/// `rect` cut to the part showing `-RANGE_DB..=db`.
+	fn fill_to(&self, rect: Rect, db: f64) -> Rect {
+		let fraction = normalize(db, RANGE_DB);
+		match self.axis {
+			Axis::Horizontal => rect.with_size((rect.width() * fraction, rect.height())),
+			Axis::Vertical => {
+				Rect::new(rect.x0, rect.y1 - rect.height() * fraction, rect.x1, rect.y1)
+			}
+		}
+	}
+
+	/// Thin line across `rect` at `db`.
+	fn marker(&self, rect: Rect, db: f64) -> Rect {
+		let fraction = normalize(db, RANGE_DB);
+		match self.axis {
+			Axis::Horizontal => {
+				let x = rect.x0 + rect.width() * fraction;
+				Rect::from_points(Point::new(x - 1.0, rect.y0), Point::new(x + 1.0, rect.y1))
+			}
+			Axis::Vertical => {
+				let y = rect.y1 - rect.height() * fraction;
+				Rect::from_points(Point::new(rect.x0, y - 1.0), Point::new(rect.x1, y + 1.0))
+			}
+		}
+	}
+
+	/// Bar of channel `i` out of `channels`, leaving room for the clip
+	/// indicator at the loud end.
+	fn bar(&self, size: Size, i: usize, channels: usize) -> (Rect, Rect) {
+		let gap = theme::grid(0.5);
+		let clip = theme::grid(1.0);
+		let across = self.axis.minor(size);
+		let along = self.axis.major(size);
+		let thickness = (across - gap * (channels as f64 + 1.0)) / channels as f64;
+		let offset = gap + i as f64 * (thickness + gap);
+		match self.axis {
+			Axis::Horizontal => (
+				Rect::new(gap, offset, along - clip - 2.0 * gap, offset + thickness),
+				Rect::new(along - clip - gap, offset, along - gap, offset + thickness),
+			),
+			Axis::Vertical => (
+				Rect::new(offset, clip + 2.0 * gap, offset + thickness, along - gap),
+				Rect::new(offset, gap, offset + thickness, clip + gap),
+			),
+		}
+	}
+
+	/// Paint `rect` up to `db` in the color of each zone.
+	fn paint_zones(&self, ctx: &mut PaintCtx, rect: Rect, db: f64, alpha: f64, env: &Env) {
+		let zones = [
+			(DANGER_DB, env.get(theme::METER_DANGER)),
+			(WARNING_DB, env.get(theme::METER_WARNING)),
+			(-RANGE_DB, env.get(theme::METER_NORMAL)),
+		];
+		let mut top = db;
+		for (bottom, color) in zones {
+			if top > bottom {
+				let zone = self.fill_to(rect, top);
+				let below = self.fill_to(rect, bottom);
+				let zone = match self.axis {
+					Axis::Horizontal => Rect::new(below.x1, zone.y0, zone.x1, zone.y1),
+					Axis::Vertical => Rect::new(zone.x0, zone.y0, zone.x1, below.y0),
+				};
+				ctx.fill(zone, &color.with_alpha(alpha));
+				top = bottom;
+			}
+		}
+	}
+}
+
+impl Widget<AudioLevel> for LevelMeter {
+	fn event(&mut self, ctx: &mut EventCtx, event: &Event, _data: &mut AudioLevel, _env: &Env) {
+		if let Event::MouseDown(_) = event {
+			// Acknowledge the clipping.
+			self.clipped.iter_mut().for_each(|clipped| *clipped = false);
+			ctx.request_paint();
+		}
+	}
+
+	fn lifecycle(
+		&mut self,
+		_ctx: &mut LifeCycleCtx,
+		_event: &LifeCycle,
+		_data: &AudioLevel,
+		_env: &Env,
+	) {
+	}
+
+	fn update(
+		&mut self,
+		ctx: &mut UpdateCtx,
+		old_data: &AudioLevel,
+		data: &AudioLevel,
+		_env: &Env,
+	) {
+		if old_data != data {
+			self.track(data);
+			ctx.request_paint();
+		}
+	}
+
+	fn layout(
+		&mut self,
+		_ctx: &mut LayoutCtx,
+		bc: &BoxConstraints,
+		_data: &AudioLevel,
+		_env: &Env,
+	) -> Size {
+		bc.max()
+	}
+
+	fn paint(&mut self, ctx: &mut PaintCtx, data: &AudioLevel, env: &Env) {
+		let size = ctx.size();
+		ctx.fill(size.to_rect(), &env.get(theme::METER_BACKGROUND));
+		let channels = data.rms.len();
+		for i in 0..channels {
+			let (bar, clip) = self.bar(size, i, channels);
+			ctx.fill(bar, &env.get(theme::METER_TRACK));
+			if let Some(&peak) = data.peak.get(i) {
+				self.paint_zones(ctx, bar, peak, 0.4, env);
+			}
+			self.paint_zones(ctx, bar, data.rms[i], 1.0, env);
+			if let Some(hold) = self.holds.get(i) {
+				let marker = self.marker(bar, hold.at(Instant::now()));
+				ctx.fill(marker, &env.get(theme::METER_PEAK_HOLD));
+			}
+			let clipped = self.clipped.get(i).copied().unwrap_or_default();
+			let color = if clipped { theme::METER_CLIP } else { theme::METER_TRACK };
+			ctx.fill(clip, &env.get(color));
+		}
+	}
+}
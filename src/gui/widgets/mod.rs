@@ -4,6 +4,8 @@
 
 pub mod empty;
 pub mod icons;
+pub mod meter;
+pub mod spectrum;
 pub mod theme;
 pub mod video;
 // mod audio;
@@ -0,0 +1,132 @@
+use std::collections::VecDeque;
+
+use druid::{widget::prelude::*, Color, Rect};
+
+use crate::{
+	gui::widgets::theme,
+	media::{level::normalize, spectrum::Spectrum},
+};
+
+/// Spectra kept by the waterfall, 6 seconds at the recorder's interval.
+const HISTORY: usize = 120;
+
+/// Live audio spectrum, drawn as bars or as a waterfall.
+///
+/// The waterfall draws the latest spectrum at the top and scrolls older ones
+/// down, coloring each band by its magnitude.
+#[derive(Debug)]
+pub struct SpectrumView {
+	waterfall: bool,
+	/// Band magnitudes mapped to `0.0..=1.0`, newest first.
+	rows: VecDeque<Vec<f64>>,
+}
+
+impl SpectrumView {
+	/// Bar display of the latest spectrum.
+	pub fn bars() -> Self {
+		Self { waterfall: false, rows: VecDeque::new() }
+	}
+
+	/// Scrolling display of recent spectra.
+	pub fn waterfall() -> Self {
+		Self { waterfall: true, ..Self::bars() }
+	}
+
+	/// Remember `spectrum` as the newest row.
+	fn track(&mut self, spectrum: &Spectrum) {
+		let range = -f64::from(spectrum.threshold);
+		let row = spectrum.magnitudes.iter().map(|&db| normalize(f64::from(db), range)).collect();
+		self.rows.push_front(row);
+		self.rows.truncate(if self.waterfall { HISTORY } else { 1 });
+	}
+
+	fn paint_bars(&self, ctx: &mut PaintCtx, size: Size, env: &Env) {
+		let row = match self.rows.front() {
+			Some(row) if !row.is_empty() => row,
+			_ => return,
+		};
+		let width = size.width / row.len() as f64;
+		let gap = if width > 3.0 { 1.0 } else { 0.0 };
+		let color = env.get(theme::METER_NORMAL);
+		for (i, &fraction) in row.iter().enumerate() {
+			let x = i as f64 * width;
+			let top = size.height * (1.0 - fraction);
+			ctx.fill(Rect::new(x + gap, top, x + width - gap, size.height), &color);
+		}
+	}
+
+	fn paint_waterfall(&self, ctx: &mut PaintCtx, size: Size, env: &Env) {
+		let stops = [
+			(0.0, env.get(theme::METER_BACKGROUND)),
+			(0.5, env.get(theme::METER_NORMAL)),
+			(0.8, env.get(theme::METER_WARNING)),
+			(1.0, env.get(theme::METER_DANGER)),
+		];
+		let height = size.height / HISTORY as f64;
+		for (r, row) in self.rows.iter().enumerate() {
+			let width = size.width / row.len().max(1) as f64;
+			let y = r as f64 * height;
+			for (i, &fraction) in row.iter().enumerate() {
+				let x = i as f64 * width;
+				ctx.fill(Rect::new(x, y, x + width, y + height), &heat(&stops, fraction));
+			}
+		}
+	}
+}
+
+impl Widget<Spectrum> for SpectrumView {
+	fn event(&mut self, _ctx: &mut EventCtx, _event: &Event, _data: &mut Spectrum, _env: &Env) {}
+
+	fn lifecycle(
+		&mut self,
+		_ctx: &mut LifeCycleCtx,
+		_event: &LifeCycle,
+		_data: &Spectrum,
+		_env: &Env,
+	) {
+	}
+
+	fn update(&mut self, ctx: &mut UpdateCtx, old_data: &Spectrum, data: &Spectrum, _env: &Env) {
+		if old_data != data {
+			self.track(data);
+			ctx.request_paint();
+		}
+	}
+
+	fn layout(
+		&mut self,
+		_ctx: &mut LayoutCtx,
+		bc: &BoxConstraints,
+		_data: &Spectrum,
+		_env: &Env,
+	) -> Size {
+		bc.max()
+	}
+
+	fn paint(&mut self, ctx: &mut PaintCtx, _data: &Spectrum, env: &Env) {
+		let size = ctx.size();
+		ctx.fill(size.to_rect(), &env.get(theme::METER_BACKGROUND));
+		ctx.with_save(|ctx| {
+			ctx.clip(size.to_rect());
+			if self.waterfall {
+				self.paint_waterfall(ctx, size, env);
+			} else {
+				self.paint_bars(ctx, size, env);
+			}
+		});
+	}
+}
+
+/// Color at `fraction` of a gradient through `stops`.
+fn heat(stops: &[(f64, Color)], fraction: f64) -> Color {
+	let upper = stops.iter().position(|(at, _)| *at >= fraction).unwrap_or(stops.len() - 1);
+	if upper == 0 {
+		return stops[0].1;
+	}
+	let (low_at, low) = stops[upper - 1];
+	let (high_at, high) = stops[upper];
+	let t = ((fraction - low_at) / (high_at - low_at)).clamp(0.0, 1.0);
+	let (r0, g0, b0, a0) = low.as_rgba();
+	let (r1, g1, b1, a1) = high.as_rgba();
+	Color::rgba(r0 + (r1 - r0) * t, g0 + (g1 - g0) * t, b0 + (b1 - b0) * t, a0 + (a1 - a0) * t)
+}
@@ -95,6 +95,14 @@ pub const LINK_HOT_COLOR: Key<Color> = Key::new("app.link-hot-color");
 pub const LINK_ACTIVE_COLOR: Key<Color> = Key::new("app.link-active-color");
 pub const LINK_COLD_COLOR: Key<Color> = Key::new("app.link-cold-color");
 
+pub const METER_BACKGROUND: Key<Color> = Key::new("app.meter-background");
+pub const METER_TRACK: Key<Color> = Key::new("app.meter-track");
+pub const METER_NORMAL: Key<Color> = Key::new("app.meter-normal");
+pub const METER_WARNING: Key<Color> = Key::new("app.meter-warning");
+pub const METER_DANGER: Key<Color> = Key::new("app.meter-danger");
+pub const METER_PEAK_HOLD: Key<Color> = Key::new("app.meter-peak-hold");
+pub const METER_CLIP: Key<Color> = Key::new("app.meter-clip");
+
 fn setup(env: &mut Env, state: &AppState) {
 	match state.theme {
 		Theme::Light => setup_light_theme(env),
@@ -171,6 +179,11 @@ fn setup(env: &mut Env, state: &AppState) {
 	env.set(MENU_BUTTON_BG_INACTIVE, env.get(GREY_600));
 	env.set(MENU_BUTTON_FG_ACTIVE, env.get(GREY_000));
 	env.set(MENU_BUTTON_FG_INACTIVE, env.get(GREY_100));
+
+	env.set(METER_BACKGROUND, env.get(GREY_600));
+	env.set(METER_TRACK, env.get(GREY_500));
+	env.set(METER_PEAK_HOLD, env.get(GREY_100));
+	env.set(METER_CLIP, env.get(RED));
 }
 
 fn setup_light_theme(env: &mut Env) {
@@ -190,6 +203,10 @@ fn setup_light_theme(env: &mut Env) {
 	env.set(LINK_HOT_COLOR, Color::rgba(0.0, 0.0, 0.0, 0.06));
 	env.set(LINK_ACTIVE_COLOR, Color::rgba(0.0, 0.0, 0.0, 0.04));
 	env.set(LINK_COLD_COLOR, Color::rgba(0.0, 0.0, 0.0, 0.0));
+
+	env.set(METER_NORMAL, Color::rgb8(0x27, 0xae, 0x60));
+	env.set(METER_WARNING, Color::rgb8(0xf2, 0xa9, 0x00));
+	env.set(METER_DANGER, Color::rgb8(0xeb, 0x57, 0x57));
 }
 
 fn setup_dark_theme(env: &mut Env) {
@@ -209,4 +226,8 @@ fn setup_dark_theme(env: &mut Env) {
 	env.set(LINK_HOT_COLOR, Color::rgba(1.0, 1.0, 1.0, 0.05));
 	env.set(LINK_ACTIVE_COLOR, Color::rgba(1.0, 1.0, 1.0, 0.025));
 	env.set(LINK_COLD_COLOR, Color::rgba(1.0, 1.0, 1.0, 0.0));
+
+	env.set(METER_NORMAL, Color::rgb8(0x6f, 0xcf, 0x97));
+	env.set(METER_WARNING, Color::rgb8(0xf2, 0xc9, 0x4c));
+	env.set(METER_DANGER, Color::rgb8(0xff, 0x6b, 0x6b));
 }
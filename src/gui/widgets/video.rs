@@ -1,40 +1,287 @@
-use std::path::PathBuf;
-use anyhow::Error;
+use std::{fs, path::Path, sync::Arc};
+
 use druid::{
-	kurbo::Circle,
 	piet::{ImageFormat, InterpolationMode},
-	widget::{Controller, FillStrat, Image},
-	BoxConstraints, Color, Env, Event, EventCtx, ExtEventSink, ImageBuf, LayoutCtx, LifeCycle,
-	LifeCycleCtx, MouseButton, PaintCtx, RenderContext, Selector, SingleUse, Size, Target,
+	widget::{FillStrat, Image},
+	Affine, BoxConstraints, Cursor, Data, Env, Event, EventCtx, ExtEventSink, ImageBuf, LayoutCtx,
+	LifeCycle, LifeCycleCtx, MouseButton, PaintCtx, Point, Rect, RenderContext, Size, Target,
 	UpdateCtx, Widget,
 };
-use gst::prelude::*;
-use gstreamer as gst;
-use gstreamer::{event::Seek, Element, SeekFlags, SeekType, Caps, ElementFactory, Pipeline, State};
-use gstreamer_app as gst_app;
-use num_rational::Ratio;
-use num_traits::ToPrimitive;
 
 use crate::{
 	gui::{
-		controller::cmd,
-		data::video::{
-			Position, VideoError, VideoError::Duration, VideoPlayer, VideoPlayerState, VideoView,
-			VideoViewState,
+		controller::{
+			api::{ApiServer, ApiSettings},
+			cmd,
+		},
+		data::{
+			audio::MonitorForm,
+			image::ImageForm,
+			output::OutputRow,
+			video::{CaptureKind, VideoError, VideoPlayer, VideoView, VideoViewState},
 		},
+		widgets::{meter::LevelMeter, theme},
+	},
+	media::{
+		audio::AudioSettings,
+		builder::PreviewSink,
+		frame::Frame,
+		level::AudioLevel,
+		loudness::{self, Loudness},
+		output::{OutputHealth, StreamTarget},
+		recorder::Recorder,
+		recording::RecordingSettings,
+		source::CaptureSource,
+		spectrum::{Spectrum, SpectrumSettings},
+		sync::AvOffset,
+		transform::{Branch, Zoom},
 	},
-	media::thumbnail::Thumbnail,
 };
 
+/// Smallest crop selection taken, in frame pixels; anything smaller is
+/// most likely a click.
+const MIN_SELECTION: f64 = 16.0;
+/// Strongest digital zoom of the preview.
+const MAX_ZOOM: f64 = 8.0;
+/// Zoom change per pixel of mouse wheel movement, as an exponent.
+const ZOOM_SPEED: f64 = 0.002;
+/// Width of the minimap shown while zoomed.
+const MINIMAP_WIDTH: f64 = 120.0;
+
 impl VideoView {
 	/// Create new camera view
 	pub fn new() -> Self {
+		let fill = FillStrat::Fill;
 		let image_buf = ImageBuf::default();
-		let image = Image::new(image_buf)
-			.fill_mode(FillStrat::Fill)
+		let image = Image::new(image_buf.clone())
+			.fill_mode(fill)
 			.interpolation_mode(InterpolationMode::Bilinear);
 
-		Self { image, player: None, event: None }
+		Self {
+			image,
+			fill,
+			frame: image_buf,
+			selection: None,
+			zoom: 1.0,
+			center: Point::ZERO,
+			pan: None,
+			scaled: None,
+			meter: LevelMeter::new(),
+			player: None,
+			event: None,
+		}
+	}
+
+	/// Map from frame pixels to the widget, zoomed into `center`.
+	fn view_affine(&self, size: Size) -> Affine {
+		let frame = self.frame.size();
+		self.fill.affine_to_fill(size, frame)
+			* Affine::translate(frame.to_vec2() / 2.0)
+			* Affine::scale(self.zoom)
+			* Affine::translate(-self.center.to_vec2())
+	}
+
+	/// Keep the zoomed part of the preview inside the frame.
+	fn clamp_center(&mut self) {
+		let frame = self.frame.size();
+		let half = frame / (2.0 * self.zoom);
+		self.center = Point::new(
+			self.center.x.clamp(half.width, frame.width - half.width),
+			self.center.y.clamp(half.height, frame.height - half.height),
+		);
+	}
+
+	/// Zoom with the mouse wheel, keeping the pixel under the cursor in
+	/// place, and drag the zoomed preview around. A double click shows the
+	/// whole frame again. Returns whether the zoom or its center changed.
+	fn zoom_and_pan(&mut self, ctx: &mut EventCtx, event: &Event) -> bool {
+		let frame = self.frame.size();
+		if frame.is_empty() {
+			return false;
+		}
+		let to_frame = self.view_affine(ctx.size()).inverse();
+		match event {
+			Event::Wheel(mouse) => {
+				let factor = (-mouse.wheel_delta.y * ZOOM_SPEED).exp();
+				let zoom = (self.zoom * factor).clamp(1.0, MAX_ZOOM);
+				let under = to_frame * mouse.pos;
+				let unzoomed = self.fill.affine_to_fill(ctx.size(), frame).inverse() * mouse.pos;
+				self.center = under - (unzoomed.to_vec2() - frame.to_vec2() / 2.0) / zoom;
+				self.zoom = zoom;
+			}
+			Event::MouseDown(mouse) if mouse.button == MouseButton::Left && mouse.count == 2 => {
+				self.zoom = 1.0;
+			}
+			Event::MouseDown(mouse) if mouse.button == MouseButton::Left && self.zoom > 1.0 => {
+				self.pan = Some(mouse.pos);
+				ctx.set_active(true);
+				return false;
+			}
+			Event::MouseMove(mouse) if ctx.is_active() => {
+				let last = match self.pan.replace(mouse.pos) {
+					Some(last) => last,
+					None => return false,
+				};
+				self.center -= (to_frame * mouse.pos) - (to_frame * last);
+			}
+			Event::MouseUp(mouse) if mouse.button == MouseButton::Left && ctx.is_active() => {
+				self.pan = None;
+				ctx.set_active(false);
+				return false;
+			}
+			_ => return false,
+		}
+		self.clamp_center();
+		ctx.set_handled();
+		ctx.request_paint();
+		true
+	}
+
+	/// Drag a crop selection over the preview. Releasing the mouse crops the
+	/// preview and, unless recording, the recording to the selection.
+	fn select_crop(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut VideoViewState) {
+		let size = self.frame.size();
+		if size.is_empty() {
+			return;
+		}
+		let to_frame = self.view_affine(ctx.size()).inverse();
+		let frame_point = |pos: Point| {
+			let point = to_frame * pos;
+			Point::new(point.x.clamp(0.0, size.width), point.y.clamp(0.0, size.height))
+		};
+		match event {
+			Event::MouseDown(mouse) if mouse.button == MouseButton::Left => {
+				let anchor = frame_point(mouse.pos);
+				self.selection = Some((anchor, anchor));
+				ctx.set_active(true);
+				ctx.set_handled();
+			}
+			Event::MouseMove(mouse) => {
+				ctx.set_cursor(&Cursor::Crosshair);
+				if let (true, Some((anchor, corner))) = (ctx.is_active(), &mut self.selection) {
+					*corner = data.transform.aspect.fit(*anchor, frame_point(mouse.pos));
+					ctx.request_paint();
+				}
+			}
+			Event::MouseUp(mouse) if mouse.button == MouseButton::Left && ctx.is_active() => {
+				ctx.set_active(false);
+				ctx.request_paint();
+				let (anchor, corner) = match self.selection.take() {
+					Some(selection) => selection,
+					None => return,
+				};
+				let selected = Rect::from_points(anchor, corner);
+				if selected.width() < MIN_SELECTION || selected.height() < MIN_SELECTION {
+					return;
+				}
+				// The preview shows the camera through its own transform.
+				let preview = data.transform.to_transforms().get(Branch::Preview);
+				let frame = (size.width as u32, size.height as u32);
+				let crop = preview.crop_between((anchor.x, anchor.y), (corner.x, corner.y), frame);
+				let form = &mut data.transform;
+				form.crop_left = crop.left.into();
+				form.crop_top = crop.top.into();
+				form.crop_right = crop.right.into();
+				form.crop_bottom = crop.bottom.into();
+				form.crop_recording |= !data.camara_record;
+				form.selecting = false;
+			}
+			_ => {}
+		}
+	}
+
+	/// Whether the running recorder captures audio only.
+	fn audio_only(data: &VideoViewState) -> bool {
+		data.capturing.audio_only().is_some()
+	}
+
+	/// Apply the image adjustments of the form to the camera.
+	fn set_image_adjustments(&self, data: &VideoViewState) {
+		if let (Some(player), false) = (&self.player, Self::audio_only(data)) {
+			if let Err(err) = player.recorder.set_image_adjustments(&data.image.to_adjustments()) {
+				log::warn!("failed to adjust the image: {}", err);
+			}
+		}
+	}
+
+	/// Apply the transforms of the form to the preview and the recording.
+	fn set_transforms(&mut self, data: &VideoViewState) {
+		if let (Some(player), false) = (&self.player, Self::audio_only(data)) {
+			let preview = data.transform.to_transforms().get(Branch::Preview);
+			if let Err(err) = player.recorder.set_transform(Branch::Preview, &preview) {
+				log::warn!("failed to transform the preview: {}", err);
+			}
+		}
+		self.set_recording_transform(data);
+	}
+
+	/// Apply the transform of the form to the recording, unless recording.
+	///
+	/// A recorded zoom narrows the crop and scales the frames back to the size
+	/// they have without it, so it follows the preview while recording too.
+	fn set_recording_transform(&mut self, data: &VideoViewState) {
+		let player = match (&self.player, Self::audio_only(data)) {
+			(Some(player), false) => player,
+			_ => return,
+		};
+		let transforms = data.transform.to_transforms();
+		let mut recording = transforms.get(Branch::Recording);
+		let frame = self.frame.size();
+		let frame = (frame.width as u32, frame.height as u32);
+		let zoomed = data.zoom_recording && self.zoom > 1.0 && frame.0 > 0 && frame.1 > 0;
+		if data.camara_record && !zoomed && self.scaled.is_none() {
+			return;
+		}
+		let mut scaled = None;
+		if zoomed {
+			let preview = transforms.get(Branch::Preview);
+			let camera = preview.camera_size(frame);
+			let zoom = Zoom {
+				factor: self.zoom,
+				center: preview.to_camera((self.center.x, self.center.y), frame),
+			};
+			scaled = Some(recording.output_size(camera));
+			recording = zoom.narrow(&recording, camera);
+		}
+		if scaled != self.scaled {
+			if let Err(err) = player.recorder.scale_to(scaled) {
+				log::warn!("failed to scale the recording: {}", err);
+			}
+		}
+		if let Err(err) = player.recorder.set_transform(Branch::Recording, &recording) {
+			log::warn!("failed to transform the recording: {}", err);
+		}
+		self.scaled = scaled;
+	}
+
+	/// Preview frame drawn through the zoom, which `Image` cannot do.
+	fn paint_zoomed(&self, ctx: &mut PaintCtx) {
+		let size = ctx.size();
+		let view = self.view_affine(size);
+		let frame = self.frame.size().to_rect();
+		let image = self.frame.to_image(ctx.render_ctx);
+		ctx.with_save(|ctx| {
+			ctx.clip(size.to_rect());
+			ctx.transform(view);
+			ctx.draw_image(&image, frame, InterpolationMode::Bilinear);
+		});
+	}
+
+	/// Outline of the whole frame in the bottom right corner, with the part
+	/// in view marked.
+	fn paint_minimap(&self, ctx: &mut PaintCtx, env: &Env) {
+		let frame = self.frame.size();
+		let scale = MINIMAP_WIDTH / frame.width;
+		let margin = theme::grid(1.0);
+		let origin = (ctx.size() - frame * scale - Size::new(margin, margin)).to_vec2();
+		let to_map = Affine::translate(origin) * Affine::scale(scale);
+		let map = to_map.transform_rect_bbox(frame.to_rect());
+		let view = Rect::from_center_size(self.center, frame / self.zoom);
+		let view = to_map.transform_rect_bbox(view);
+		ctx.fill(map, &env.get(theme::GREY_700).with_alpha(0.6));
+		ctx.stroke(map, &env.get(theme::GREY_100), 1.0);
+		ctx.fill(view, &env.get(theme::BLUE_100).with_alpha(0.4));
+		ctx.stroke(view, &env.get(theme::BLUE_100), 1.0);
 	}
 }
 
@@ -42,25 +289,268 @@ impl Widget<VideoViewState> for VideoView {
 	fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut VideoViewState, env: &Env) {
 		if let Event::Command(command) = event {
 			if let Some(image_buf) = command.get(cmd::VIDEO_FRAME) {
+				let resized = image_buf.size() != self.frame.size();
+				self.frame = image_buf.to_owned();
 				self.image.set_image_data(image_buf.to_owned());
+				// A turned or cropped frame shows something else, start over.
+				if resized {
+					self.zoom = 1.0;
+					self.clamp_center();
+					self.set_recording_transform(data);
+				}
 				ctx.request_paint();
 			}
+			if let Some(level) = command.get(cmd::AUDIO_LEVEL) {
+				data.level = level.clone();
+			}
+			if let Some(spectrum) = command.get(cmd::AUDIO_SPECTRUM) {
+				data.spectrum = spectrum.clone();
+			}
+			if let Some(loudness) = command.get(cmd::AUDIO_LOUDNESS) {
+				data.loudness = Some(*loudness);
+			}
+			if command.is(cmd::SET_SPECTRUM) {
+				let analyzer = self.player.as_ref().and_then(|p| p.recorder.spectrum.as_ref());
+				if let Some(analyzer) = analyzer {
+					if let Err(err) = analyzer.set_settings(data.spectrum_form.to_settings()) {
+						log::error!("failed to change the spectrum: {}", err);
+					}
+				}
+			}
+			if command.is(cmd::SET_CAPTURE) {
+				// Finish the running recording before the new one opens its file.
+				self.player = None;
+				self.scaled = None;
+				let recording = recording_settings(data.capture);
+				let source = data.source.to_source();
+				let audio = data.audio.to_settings();
+				let spectrum = data.spectrum_form.to_settings();
+				let sink = ctx.get_external_handle();
+				match VideoPlayer::new(&recording, &source, audio, spectrum, sink) {
+					Ok(mut player) => {
+						if let Err(err) = player.set_monitor(&data.monitor) {
+							log::error!("failed to start the audio monitor: {}", err);
+							data.monitor.listening = false;
+						}
+						let offset = AvOffset(data.av_offset);
+						if data.capture == CaptureKind::Video {
+							if let Err(err) = player.recorder.set_av_offset(offset) {
+								log::error!("failed to shift the audio: {}", err);
+							}
+						}
+						self.player = Some(player);
+						data.capturing = data.capture;
+						ctx.submit_command(cmd::LOAD_ADJUSTMENTS);
+					}
+					Err(err) => {
+						log::error!("failed to start capturing {:?}: {}", data.capture, err)
+					}
+				}
+				// Servers and outputs went away with the previous recorder.
+				data.camara_record = false;
+				self.set_transforms(data);
+				data.level = Default::default();
+				data.spectrum = Default::default();
+				data.loudness = None;
+				data.rtsp_url = None;
+				data.hls_url = None;
+				data.webrtc_url = None;
+				data.mjpeg_url = None;
+				data.api_url = None;
+				data.outputs = Arc::new(Vec::new());
+				ctx.request_layout();
+			}
+			if command.is(cmd::SET_AV_OFFSET) {
+				if let Some(ref player) = self.player {
+					if let Err(err) = player.recorder.set_av_offset(AvOffset(data.av_offset)) {
+						log::error!("failed to shift the audio: {}", err);
+					}
+				}
+			}
+			if command.is(cmd::SET_MONITOR) {
+				if let Some(ref mut player) = self.player {
+					if let Err(err) = player.set_monitor(&data.monitor) {
+						log::error!("failed to start the audio monitor: {}", err);
+						data.monitor.listening = false;
+					}
+				}
+			}
 			if let Some(_) = command.get(cmd::PLAY_PAUSE) {
 				if let Some(ref player) = self.player {
-					player.pipeline.set_state(gst::State::Paused);
-
+					player.recorder.record(false);
 				}
+				data.camara_record = false;
 				// ctx.request_paint();
 			}
 			if let Some(_) = command.get(cmd::PLAY_RESUME) {
 				if let Some(ref player) = self.player {
-					player.pipeline.set_state(gst::State::Playing);
-
+					player.recorder.record(true);
+				}
+				data.camara_record = true;
+			}
+			if let Some(form) = command.get(cmd::SELECT_SOURCE) {
+				data.source = form.clone();
+				ctx.submit_command(cmd::SET_SOURCE);
+			}
+			if command.is(cmd::SET_SOURCE) {
+				if let Some(ref player) = self.player {
+					if let Err(err) = player.recorder.set_source(&data.source.to_source()) {
+						log::error!("failed to switch capture source: {}", err);
+					}
+				}
+				// Modes belong to the previous source.
+				data.source_modes = Arc::new(Vec::new());
+				data.source_mode = None;
+				ctx.submit_command(cmd::LOAD_ADJUSTMENTS);
+			}
+			if command.is(cmd::LOAD_ADJUSTMENTS) {
+				match ImageForm::load_for(&data.source.to_source()) {
+					Ok(image) => data.image = image,
+					Err(err) => log::warn!("failed to load the image adjustments: {}", err),
+				}
+				self.set_image_adjustments(data);
+			}
+			if command.is(cmd::PROBE_MODES) {
+				let source = self.player.as_ref().and_then(|p| p.recorder.source.as_deref());
+				if let Some(source) = source {
+					let modes = source.modes();
+					if modes.is_empty() {
+						log::warn!("capture source does not list any modes");
+					}
+					data.source_mode = source
+						.mode()
+						.and_then(|current| modes.iter().position(|mode| *mode == current));
+					data.source_modes = Arc::new(modes);
+				}
+			}
+			if command.is(cmd::SET_MODE) {
+				if let Some(ref player) = self.player {
+					let mode = data.source_mode.and_then(|i| data.source_modes.get(i)).cloned();
+					if let Err(err) = player.recorder.set_mode(mode) {
+						log::error!("failed to switch capture mode: {}", err);
+						data.source_mode = None;
+					}
+				}
+			}
+			if let Some(path) = command.get(cmd::SNAPSHOT) {
+				if let Some(ref player) = self.player {
+					player.recorder.snapshot(path);
+				}
+			}
+			if command.is(cmd::API_START) {
+				if let Some(ref mut player) = self.player {
+					match player.start_api(&data.api) {
+						Ok(url) => data.api_url = Some(url),
+						Err(err) => log::error!("failed to start remote control API: {}", err),
+					}
+				}
+			}
+			if command.is(cmd::API_STOP) {
+				if let Some(ref mut player) = self.player {
+					player.api = None;
+				}
+				data.api_url = None;
+			}
+			if command.is(cmd::RTSP_START) {
+				if let Some(ref mut player) = self.player {
+					match player.recorder.start_rtsp(&data.rtsp) {
+						Ok(url) => data.rtsp_url = Some(url),
+						Err(err) => log::error!("failed to start RTSP server: {}", err),
+					}
+				}
+			}
+			if command.is(cmd::RTSP_STOP) {
+				if let Some(ref mut player) = self.player {
+					player.recorder.stop_rtsp();
+				}
+				data.rtsp_url = None;
+			}
+			if command.is(cmd::HLS_START) {
+				if let Some(ref mut player) = self.player {
+					match player.recorder.start_hls(&data.hls) {
+						Ok(url) => data.hls_url = Some(url),
+						Err(err) => log::error!("failed to start HLS output: {}", err),
+					}
+				}
+			}
+			if command.is(cmd::HLS_STOP) {
+				if let Some(ref mut player) = self.player {
+					player.recorder.stop_hls();
+				}
+				data.hls_url = None;
+			}
+			if command.is(cmd::WEBRTC_START) {
+				if let Some(ref mut player) = self.player {
+					match player.recorder.start_webrtc(&data.webrtc) {
+						Ok(url) => data.webrtc_url = Some(url),
+						Err(err) => log::error!("failed to start WebRTC output: {}", err),
+					}
+				}
+			}
+			if command.is(cmd::WEBRTC_STOP) {
+				if let Some(ref mut player) = self.player {
+					player.recorder.webrtc = None;
+				}
+				data.webrtc_url = None;
+			}
+			if command.is(cmd::MJPEG_START) {
+				if let Some(ref mut player) = self.player {
+					match player.recorder.start_mjpeg(&data.mjpeg) {
+						Ok(url) => data.mjpeg_url = Some(url),
+						Err(err) => log::error!("failed to start MJPEG server: {}", err),
+					}
+				}
+			}
+			if command.is(cmd::MJPEG_STOP) {
+				if let Some(ref mut player) = self.player {
+					player.recorder.mjpeg = None;
+				}
+				data.mjpeg_url = None;
+			}
+			if command.is(cmd::OUTPUT_ADD) {
+				if let Some(ref mut player) = self.player {
+					let target = data.output.to_target();
+					match player.add_output(&target) {
+						Ok(id) => Arc::make_mut(&mut data.outputs).push(OutputRow {
+							id,
+							label: target.to_string(),
+							health: OutputHealth::Starting.to_string(),
+						}),
+						Err(err) => log::error!("failed to add output {}: {}", target, err),
+					}
+				}
+			}
+			if let Some(id) = command.get(cmd::OUTPUT_REMOVE) {
+				if let Some(ref mut player) = self.player {
+					player.recorder.remove_output(*id);
+				}
+				Arc::make_mut(&mut data.outputs).retain(|row| row.id != *id);
+			}
+			if let Some((id, health)) = command.get(cmd::OUTPUT_HEALTH) {
+				let outputs = Arc::make_mut(&mut data.outputs);
+				if let Some(row) = outputs.iter_mut().find(|row| row.id == *id) {
+					row.health = health.to_string();
 				}
 			}
 		}
 
-		self.image.event(ctx, event, data, env)
+		// The recording keeps its crop while recording.
+		let locked = data.camara_record && data.transform.crop_recording;
+		if data.transform.selecting && !locked && !Self::audio_only(data) {
+			self.select_crop(ctx, event, data);
+		} else if !Self::audio_only(data) {
+			if let Event::MouseMove(_) = event {
+				ctx.clear_cursor();
+			}
+			if self.zoom_and_pan(ctx, event) && data.zoom_recording {
+				self.set_recording_transform(data);
+			}
+		}
+		if Self::audio_only(data) {
+			self.meter.event(ctx, event, &mut data.level, env)
+		} else {
+			self.image.event(ctx, event, data, env)
+		}
 	}
 
 	fn lifecycle(
@@ -72,9 +562,16 @@ impl Widget<VideoViewState> for VideoView {
 	) {
 		match event {
 			LifeCycle::WidgetAdded => {
-				let uri = ".media/druid.mkv";
-				let player = VideoPlayer::new(uri, false, ctx.get_external_handle()).unwrap();
+				let recording = recording_settings(data.capturing);
+				let source = data.source.to_source();
+				let audio = data.audio.to_settings();
+				let spectrum = data.spectrum_form.to_settings();
+				let sink = ctx.get_external_handle();
+				let player =
+					VideoPlayer::new(&recording, &source, audio, spectrum, sink).unwrap();
 				self.player = Some(player);
+				self.set_transforms(data);
+				ctx.submit_command(cmd::LOAD_ADJUSTMENTS);
 			}
 			_ => {}
 		}
@@ -88,6 +585,44 @@ impl Widget<VideoViewState> for VideoView {
 		data: &VideoViewState,
 		env: &Env,
 	) {
+		if Self::audio_only(old_data) != Self::audio_only(data) {
+			ctx.request_layout();
+		}
+		if !old_data.image.same(&data.image) {
+			self.set_image_adjustments(data);
+		}
+		if old_data.transform.to_transforms() != data.transform.to_transforms() {
+			self.set_transforms(data);
+		}
+		if !data.transform.selecting && self.selection.take().is_some() {
+			ctx.request_paint();
+		}
+		if old_data.zoom_recording != data.zoom_recording {
+			self.set_recording_transform(data);
+		}
+		// Gain, mute and pan follow the controls, devices wait for `SET_CAPTURE`.
+		if !old_data.audio.inputs.same(&data.audio.inputs) {
+			if let Some(ref player) = self.player {
+				let inputs = old_data.audio.inputs.iter().zip(data.audio.inputs.iter());
+				for (i, (old, new)) in inputs.enumerate() {
+					if !old.same(new) && old.same_device(new) {
+						if let Err(err) = player.recorder.set_audio_input(i, &new.to_input()) {
+							log::warn!("failed to adjust audio input {}: {}", i, err);
+						}
+					}
+				}
+			}
+		}
+		// Volume follows the slider, everything else waits for `SET_MONITOR`.
+		if old_data.monitor.volume != data.monitor.volume {
+			let monitor = self.player.as_mut().and_then(|p| p.recorder.monitor.as_mut());
+			if let Some((monitor, _)) = monitor {
+				if let Err(err) = monitor.set_volume(data.monitor.volume) {
+					log::warn!("failed to change the monitor volume: {}", err);
+				}
+			}
+		}
+		self.meter.update(ctx, &old_data.level, &data.level, env);
 		self.image.update(ctx, old_data, data, env)
 	}
 
@@ -98,294 +633,132 @@ impl Widget<VideoViewState> for VideoView {
 		data: &VideoViewState,
 		env: &Env,
 	) -> Size {
-		self.image.layout(ctx, bc, data, env)
+		if Self::audio_only(data) {
+			self.meter.layout(ctx, bc, &data.level, env)
+		} else {
+			self.image.layout(ctx, bc, data, env)
+		}
 	}
 
 	fn paint(&mut self, ctx: &mut PaintCtx, data: &VideoViewState, env: &Env) {
-		self.image.paint(ctx, data, env);
+		if Self::audio_only(data) {
+			self.meter.paint(ctx, &data.level, env);
+		} else {
+			if self.zoom > 1.0 {
+				self.paint_zoomed(ctx);
+				self.paint_minimap(ctx, env);
+			} else {
+				self.image.paint(ctx, data, env);
+			}
+			if let Some((anchor, corner)) = self.selection {
+				let to_widget = self.view_affine(ctx.size());
+				let selected = Rect::from_points(to_widget * anchor, to_widget * corner);
+				ctx.fill(selected, &env.get(theme::BLUE_100).with_alpha(0.2));
+				ctx.stroke(selected, &env.get(theme::BLUE_100), 1.0);
+			}
+		}
 	}
 }
 
-
-impl Drop for VideoPlayer {
-	fn drop(&mut self) {
-		self.pipeline.set_state(gst::State::Null).expect("failed to set state");
+/// Default recording settings for `capture`, with their directory created.
+fn recording_settings(capture: CaptureKind) -> RecordingSettings {
+	let mut recording = RecordingSettings::default();
+	if let Some(format) = capture.audio_only() {
+		recording = recording.audio_only(format);
 	}
-}
-/** Framerate */
-#[derive(Debug, Clone, Copy)]
-pub enum FrameRate {
-	F24 = 24 as isize,
-	F30 = 30 as isize,
-}
-
-impl Default for FrameRate {
-	fn default() -> Self {
-		FrameRate::F24
+	if let Some(directory) = recording.location.parent() {
+		if let Err(err) = fs::create_dir_all(directory) {
+			log::error!("failed to create {}: {}", directory.display(), err);
+		}
 	}
+	recording
 }
-impl VideoPlayer {
-	/// Create a new video player from a given video which loads from `uri`.
-	///
-	/// If `live` is set then no duration is queried (as this will result in an
-	/// error and is non-sensical for live streams). Set `live` if the streaming
-	/// source is indefinite (e.g. a live stream). Note that this will cause the
-	/// duration to be zero.
-	pub fn new(uri: &str, live: bool, event_sink: ExtEventSink) -> Result<Self, VideoError> {
-		let rate = Ratio::new(FrameRate::default() as i32, 1);
-		// Pipeline creation
-		gstreamer::init().expect("cannot start gstreamer");
-		let main_pipeline = Pipeline::new(Some("recorder"));
-		// Video elements
-		#[cfg(target_os = "linux")]
-			let src_video = ElementFactory::make("v4l2src", Some("desktop-video-source"))
-			.expect("Unable to make desktop-video-source");
-		#[cfg(target_os = "macos")]
-			let src_video = ElementFactory::make("autovideosrc", Some("desktop-video-source"))
-			.expect("Unable to make desktop-video-source");
-
-		let video_tee = ElementFactory::make("tee", Some("video_tee")).unwrap();
-		let video_queue0 = ElementFactory::make("queue2", Some("video_queue0")).unwrap();
-
-		let video_queue1 = ElementFactory::make("queue2", Some("video_queue1")).unwrap();
-		let video_sink1 = ElementFactory::make("appsink", Some("video_sink")).unwrap();
-		let rate_video1 = ElementFactory::make("videorate", Some("desktop-video-framerate1"))
-			.expect("Unable to make desktop-video-framerate");
-		let convert_video1 = ElementFactory::make("videoconvert", Some("desktop-video-converter1"))
-			.expect("Unable to make desktop-video-converter");
-
-		let rate_video = ElementFactory::make("videorate", Some("desktop-video-framerate"))
-			.expect("Unable to make desktop-video-framerate");
-		let convert_video = ElementFactory::make("videoconvert", Some("desktop-video-converter"))
-			.expect("Unable to make desktop-video-converter");
-		let raw_video_caps = ElementFactory::make("capsfilter", Some("desktop-video-raw-caps"))
-			.expect("Unable to make desktop-video-raw-caps");
-		let encoder_video = ElementFactory::make("x264enc", Some("desktop-video-encoder"))
-			.expect("Unable to make desktop-video-encoder");
-		let encoder_video_caps =
-			ElementFactory::make("capsfilter", Some("desktop-video-encoder-caps"))
-				.expect("Unable to make desktop-video-encoder-caps");
-		let queue_video = ElementFactory::make("queue2", Some("desktop-video-queue-1"))
-			.expect("Unable to make desktop-video-queue-1");
-
-		// Audio elements
-		let src_audio = ElementFactory::make("alsasrc", Some("desktop-audio-source"))
-			.expect("Unable to make desktop-audio-source");
-		let raw_audio_caps = ElementFactory::make("capsfilter", Some("desktop-raw-audio-caps"))
-			.expect("Unable to make desktop-raw-audio-caps");
-		let queue_audio = ElementFactory::make("queue2", Some("desktop-audio-queue"))
-			.expect("Unable to make desktop-audio-queue");
-		let encoder_audio = ElementFactory::make("voaacenc", Some("desktop-audio-encoder"))
-			.expect("Unable to make desktop-audio-encoder");
-
-		// Mux and sink -- maybe sink, maybe rtmp
-		let muxer = ElementFactory::make("matroskamux", Some("mkv-muxer"))
-			.expect("Unable to make mkv-muxer"); // trying different muxer here
-		let sink = ElementFactory::make("filesink", Some("mkv-filesink"))
-			.expect("Unable to make mkv-filesink");
-		// Adding video elements
-		main_pipeline
-			.add_many(&[
-				&src_video,
-				&video_tee,
-				&video_queue0,
-				&rate_video,
-				&convert_video,
-				&raw_video_caps,
-				&encoder_video,
-				&encoder_video_caps,
-				&queue_video,
-
-				&video_queue1,
-				&rate_video1,
-				&convert_video1,
-				&video_sink1,
-			])
-			.expect("unable to add video elements to recording pipeline");
-		// Adding audio elements
-		main_pipeline
-			.add_many(&[&src_audio, &raw_audio_caps, &queue_audio, &encoder_audio])
-			.expect("unable to add audio elements to recording pipeline");
-		// Adding tail elements
-		main_pipeline
-			.add_many(&[&muxer, &sink])
-			.expect("unable to add audio elements to recording pipeline");
-
-		// Creating capsfilters
-		let raw_video_capsfilter = Caps::builder("video/x-raw")
-			.field("framerate", &(gstreamer::Fraction(rate)))
-			.build();
-		let encoded_video_capsfilter = Caps::builder("video/x-h264")
-			.field("profile", &"constrained-baseline")
-			.build();
-		let raw_audio_capsfilter = Caps::builder("audio/x-raw")
-			.field("framerate", &(gstreamer::Fraction(rate)))
-			.field("channels", 1)
-			.field("rate", 48000) // does not work
-			.build();
-		// Setting properties
-		// src_video.set_property("use-damage", true).unwrap();
-
-		unsafe {
-			src_video.set_data("num-buffers", 3000);
 
+impl VideoPlayer {
+	/// Create a recorder whose preview frames, audio levels, spectra and
+	/// loudness are sent to the UI as [`cmd::VIDEO_FRAME`],
+	/// [`cmd::AUDIO_LEVEL`], [`cmd::AUDIO_SPECTRUM`] and
+	/// [`cmd::AUDIO_LOUDNESS`]; loudness only if `ebur128level` is installed.
+	pub fn new(
+		recording: &RecordingSettings,
+		source: &CaptureSource,
+		audio: AudioSettings,
+		spectrum: SpectrumSettings,
+		event_sink: ExtEventSink,
+	) -> Result<Self, VideoError> {
+		let frame_sink = event_sink.clone();
+		let level_sink = event_sink.clone();
+		let spectrum_sink = event_sink.clone();
+		let loudness_sink = event_sink.clone();
+		let mut builder = Recorder::builder()
+			.source(source.clone())
+			.audio(audio)
+			.recording(recording)
+			.preview_sink(PreviewSink::Callback(Arc::new(move |frame: &Frame| {
+				let image = ImageBuf::from_raw(
+					frame.data.clone(),
+					ImageFormat::RgbaSeparate,
+					frame.width as _,
+					frame.height as _,
+				);
+				if let Err(err) = frame_sink.submit_command(cmd::VIDEO_FRAME, image, Target::Auto) {
+					log::debug!("failed to send preview frame: {}", err);
+				}
+			})))
+			.on_level(Arc::new(move |level: &AudioLevel| {
+				let _ = level_sink.submit_command(cmd::AUDIO_LEVEL, level.clone(), Target::Auto);
+			}))
+			.on_spectrum(
+				spectrum,
+				Arc::new(move |spectrum: &Spectrum| {
+					let spectrum = spectrum.clone();
+					let _ =
+						spectrum_sink.submit_command(cmd::AUDIO_SPECTRUM, spectrum, Target::Auto);
+				}),
+			);
+		if loudness::available() {
+			builder = builder.on_loudness(Arc::new(move |loudness: &Loudness| {
+				let _ = loudness_sink.submit_command(cmd::AUDIO_LOUDNESS, *loudness, Target::Auto);
+			}));
 		}
-		raw_video_caps
-			.set_property("caps", &raw_video_capsfilter);
-
-		encoder_video_caps
-			.set_property("caps", &encoded_video_capsfilter);
-		raw_audio_caps
-			.set_property("caps", &raw_audio_capsfilter);
-
-		encoder_video
-			.set_properties(&[
-				(&"intra-refresh", &true),
-				(&"vbv-buf-capacity", &(0 as u32)),
-				(&"qp-min", &(30 as u32)),
-				(&"key-int-max", &(36 as u32)),
-				// (&"pass", &"pass1"),
-				// (&"speed-preset", &"fast"),
-				// (&"tune", &"stillimage"),
-			]);
-		queue_video
-			.set_properties(&[
-				(&"max-size-bytes", &(0 as u32)),
-				(&"max-size-buffers", &(0 as u32)),
-				// (&"max-size-time", &(0 as u32)),
-			]);
-		queue_video.set_property("max-size-time", 0 as u64);
-
-		// encoder_audio.set_property("bitrate-type", "constrained-vbr").unwrap();
-		queue_audio
-			.set_properties(&[
-				(&"max-size-bytes", &(0 as u32)),
-				(&"max-size-buffers", &(0 as u32)),
-				// (&"max-size-time", &(0 as u32)),
-			]);
-		queue_audio.set_property("max-size-time", 0 as u64);
-
-
-		video_queue1
-			.set_properties(&[
-				(&"max-size-bytes", &(512000000 as u32)),
-				(&"max-size-buffers", &(0 as u32)),
-				// (&"max-size-time", &(0 as u32)),
-			]);
-		video_queue1.set_property("max-size-time", 0 as u64);
-		video_queue0
-			.set_properties(&[
-				(&"max-size-bytes", &(512000000 as u32)),
-				(&"max-size-buffers", &(0 as u32)),
-				// (&"max-size-time", &(0 as u32)),
-			])
-		;
-		video_queue0.set_property("max-size-time", 0 as u64);
-
-
-		sink.set_property("location", uri)
-		;
-
-		// Linking video elements
-		Element::link_many(&[
-			&src_video,
-			&video_tee,
-		])
-			.expect("unable to link video elements in recording pipeline");
-		// Linking video elements
-		Element::link_many(&[
-			&video_queue0,
-			&rate_video,
-			&convert_video,
-			&raw_video_caps,
-			&encoder_video,
-			&encoder_video_caps,
-			&queue_video,
-		])
-			.expect("unable to link video elements in recording pipeline");
-
-		Element::link_many(&[
-			&video_queue1,
-			&rate_video1,
-			&convert_video1,
-			&video_sink1,
-		])
-			.expect("unable to link video elements in recording pipeline");
-
-		let tee_video0_pad = video_tee.request_pad_simple("src_%u").unwrap();
-		println!(
-			"Obtained request pad {} for audio branch",
-			tee_video0_pad.name()
-		);
-		let queue_video0_pad = video_queue0.static_pad("sink").unwrap();
-		tee_video0_pad.link(&queue_video0_pad).unwrap();
-
-		let tee_video1_pad = video_tee.request_pad_simple("src_%u").unwrap();
-		println!(
-			"Obtained request pad {} for video branch",
-			tee_video1_pad.name()
-		);
-		let queue_video1_pad = video_queue1.static_pad("sink").unwrap();
-		tee_video1_pad.link(&queue_video1_pad).unwrap();
-
-		// Linking audio elements
-		Element::link_many(&[&src_audio, &raw_audio_caps, &queue_audio, &encoder_audio])
-			.expect("unable to link audio elements in recording pipeline");
-		// Linking tail elements
-		queue_video.link(&muxer).unwrap(); // Video to muxer // TODO (probably overcomplicating): use `link_pad` with sync handler
-		encoder_audio.link(&muxer).unwrap(); // Audio to muxer // TODO (probably overcomplicating): use `link_pad` with sync handler
-		Element::link_many(&[&muxer, &sink])
-			.expect("unable to link audio elements in recording pipeline");
-
-		let video_sink1 = video_sink1
-			.dynamic_cast::<gstreamer_app::AppSink>()
-			.expect("Sink element is expected to be an appsink!");
-		video_sink1.set_caps(Some(&gstreamer::Caps::new_simple(
-			"video/x-raw",
-			&[("format", &"RGBA"), ("pixel-aspect-ratio", &gstreamer::Fraction::from((1, 1)))],
-		)));
-		video_sink1.set_callbacks(
-			gstreamer_app::AppSinkCallbacks::builder()
-				.new_sample(move |sink| {
-					let sample = sink.pull_sample().map_err(|_| gstreamer::FlowError::Eos)?;
-					let buffer = sample.buffer().ok_or(gstreamer::FlowError::Error)?;
-					let map = buffer.map_readable().map_err(|_| gstreamer::FlowError::Error)?;
-
-					let pad = sink.static_pad("sink").ok_or(gstreamer::FlowError::Error)?;
-
-					let caps = pad.current_caps().ok_or(gstreamer::FlowError::Error)?;
-					let s = caps.structure(0).ok_or(gstreamer::FlowError::Error)?;
-					let width = s.get::<i32>("width").map_err(|_| gstreamer::FlowError::Error)?;
-					let height = s.get::<i32>("height").map_err(|_| gstreamer::FlowError::Error)?;
-					// Send original and processed image.
-					                    let image = ImageBuf::from_raw(
-                                            map.as_slice().to_owned(),
-                                            ImageFormat::RgbaSeparate,
-                                            width as _,
-                                            height as _,
-                                        );
-                                        event_sink
-                                            .submit_command(cmd::VIDEO_FRAME, image, Target::Auto)
-                                            .map_err(|e| gstreamer::FlowError::Error)?;
-					// let position = 		std::time::Duration::from_nanos(
-					// 	pipeline.query_position::<gst::ClockTime>().map_or(0, |pos| pos.nseconds()),
-					// ).as_secs();
-					// event_sink
-					// 	.submit_command(cmd::PLAYBACK_PROGRESS, position, Target::Auto)
-					// 	.map_err(|e| gstreamer::FlowError::Error)?;
+		let recorder = builder.build()?;
+		Ok(VideoPlayer { recorder, api: None, event_sink, paused: false, muted: false })
+	}
 
-					Ok(gstreamer::FlowSuccess::Ok)
-				})
-				.build(),
-		);
-		Ok(VideoPlayer {
-			bus: main_pipeline.bus().unwrap(),
-			pipeline: main_pipeline,
+	/// Start, restart or stop the audio monitor as described by `form`.
+	pub fn set_monitor(&mut self, form: &MonitorForm) -> Result<(), VideoError> {
+		match form.to_settings() {
+			Some(settings) => self.recorder.start_monitor(&settings),
+			None => {
+				self.recorder.stop_monitor();
+				Ok(())
+			}
+		}
+	}
 
-			paused: false,
-			muted: false,
+	/// Start the remote control API and return its URL.
+	pub fn start_api(&mut self, settings: &ApiSettings) -> Result<String, VideoError> {
+		self.api = None;
+		let status = self.recorder.status.status();
+		let directory = status.file.parent().map(Path::to_path_buf).unwrap_or_default();
+		let server = ApiServer::start(
+			settings,
+			self.recorder.status.clone(),
+			directory,
+			self.event_sink.clone(),
+		)?;
+		let url = server.url();
+		self.api = Some(server);
+		Ok(url)
+	}
 
+	/// Attach an SRT or RTP output to the encoded stream and return its id.
+	///
+	/// Health changes are sent to the UI as [`cmd::OUTPUT_HEALTH`].
+	pub fn add_output(&mut self, target: &StreamTarget) -> Result<u32, VideoError> {
+		let sink = self.event_sink.clone();
+		self.recorder.add_output(target, move |id, health| {
+			let _ = sink.submit_command(cmd::OUTPUT_HEALTH, (id, health), Target::Auto);
 		})
 	}
-
 }
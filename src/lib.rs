@@ -9,5 +9,8 @@
 	dead_code
 )]
 
+/// Druid user interface around the recorder.
+#[cfg(feature = "gui")]
 pub mod gui;
-mod media;
+/// GStreamer building blocks shared by the UI and the headless recorder.
+pub mod media;
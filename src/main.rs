@@ -16,7 +16,8 @@ fn main() -> Result<()> {
 	let launcher = AppLauncher::with_window(window);
 	let state = AppState {
 		video: VideoViewState {
-			camara_record: false
+			camara_record: false,
+			..Default::default()
 		},
 		theme: Theme::Light,
 	};
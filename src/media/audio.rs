@@ -0,0 +1,380 @@
+// Audio capture settings.
+
+// {audio source} - {audioconvert} - {audioresample} - {volume} - {processing} - [{audiopanorama}] - {audioconvert} - {caps} - ...
+
+// Mixed:
+// {input 0} -\
+// {input 1} - {audiomixer} - ...
+
+// Separate tracks:
+// {input 0} - {tee} - ... - {muxer}
+//                  \- {audiomixer} - ...
+
+// Devices deliver whatever rate, channel layout and sample format they
+// natively support. Converting and resampling right after the source lets any
+// of them negotiate, and the capsfilter then pins the stream to the settings,
+// so the recording always has the requested parameters. With several inputs
+// the meters and the monitor always get the mix, even when every input is
+// recorded to its own track.
+use std::{fmt, str::FromStr};
+
+use anyhow::anyhow;
+use gst::prelude::*;
+use gstreamer as gst;
+
+use crate::media::{error::VideoError, processing::AudioProcessing};
+
+/// Where the recorder takes its audio from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioSource {
+	/// Default ALSA capture device.
+	Device,
+	/// Default PulseAudio source.
+	Pulse,
+	/// Default PipeWire source.
+	PipeWire,
+	/// Generated `audiotestsrc` tone, for tests and demos.
+	Test,
+}
+
+impl Default for AudioSource {
+	fn default() -> Self {
+		AudioSource::Device
+	}
+}
+
+impl fmt::Display for AudioSource {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			AudioSource::Device => "alsa",
+			AudioSource::Pulse => "pulse",
+			AudioSource::PipeWire => "pipewire",
+			AudioSource::Test => "test",
+		})
+	}
+}
+
+impl FromStr for AudioSource {
+	type Err = anyhow::Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"alsa" | "device" => Ok(AudioSource::Device),
+			"pulse" => Ok(AudioSource::Pulse),
+			"pipewire" => Ok(AudioSource::PipeWire),
+			"test" => Ok(AudioSource::Test),
+			_ => Err(anyhow!("unknown audio source {}, expected alsa, pulse, pipewire or test", s)),
+		}
+	}
+}
+
+impl AudioSource {
+	/// Name of the element factory producing the audio.
+	pub fn factory(self) -> &'static str {
+		match self {
+			AudioSource::Device => "alsasrc",
+			AudioSource::Pulse => "pulsesrc",
+			AudioSource::PipeWire => "pipewiresrc",
+			AudioSource::Test => "audiotestsrc",
+		}
+	}
+
+	/// Build the source element called `name`, capturing from `device` or the
+	/// default device.
+	pub fn make_element(
+		self,
+		name: &str,
+		device: Option<&str>,
+	) -> Result<gst::Element, VideoError> {
+		let element = gst::ElementFactory::make(self.factory(), Some(name))
+			.map_err(|_| VideoError::MissingElement(self.factory().to_string()))?;
+		match (self, device) {
+			(AudioSource::Test, _) => element.set_property("is-live", true),
+			(AudioSource::PipeWire, Some(device)) => element.set_property("path", device),
+			(_, Some(device)) => element.set_property("device", device),
+			(_, None) => {}
+		}
+		Ok(element)
+	}
+}
+
+/// One of the inputs of the recording, with its place in the mix.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioInput {
+	/// Capture element.
+	pub source: AudioSource,
+	/// Device to capture from, the default device when `None`. PipeWire takes
+	/// a node id; ALSA a name like `hw:1,0`.
+	pub device: Option<String>,
+	/// Amplification in dB.
+	pub gain_db: f64,
+	/// Silence the input without removing it.
+	pub muted: bool,
+	/// Position in stereo recordings, from -1.0 (left) to 1.0 (right).
+	pub pan: f32,
+	/// Noise gate, compressor and limiter after the gain.
+	pub processing: AudioProcessing,
+}
+
+impl Default for AudioInput {
+	fn default() -> Self {
+		Self::new(AudioSource::default())
+	}
+}
+
+impl fmt::Display for AudioInput {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match &self.device {
+			Some(device) => write!(f, "{}:{}", self.source, device),
+			None => write!(f, "{}", self.source),
+		}
+	}
+}
+
+impl FromStr for AudioInput {
+	type Err = anyhow::Error;
+
+	/// Parse `source` or `source:device`, e.g. `alsa:hw:1,0`.
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let (source, device) = match s.split_once(':') {
+			Some((source, device)) => (source, Some(device.to_string())),
+			None => (s, None),
+		};
+		Ok(Self { device, ..Self::new(source.parse()?) })
+	}
+}
+
+impl AudioInput {
+	/// Default device of `source` at unity gain, centered.
+	pub fn new(source: AudioSource) -> Self {
+		Self {
+			source,
+			device: None,
+			gain_db: 0.0,
+			muted: false,
+			pan: 0.0,
+			processing: AudioProcessing::default(),
+		}
+	}
+
+	/// Linear factor of the `volume` element for `gain_db`.
+	pub fn volume(&self) -> f64 {
+		10f64.powf(self.gain_db / 20.0)
+	}
+
+	/// Reject gains and positions the elements cannot apply.
+	pub fn validate(&self) -> Result<(), VideoError> {
+		// `volume` goes up to 10, about 20 dB.
+		if !(-60.0..=20.0).contains(&self.gain_db) {
+			return Err(VideoError::Config(format!(
+				"gain of {} is {} dB, expected -60 to 20 dB",
+				self, self.gain_db
+			)));
+		}
+		if !(-1.0..=1.0).contains(&self.pan) {
+			return Err(VideoError::Config(format!(
+				"pan of {} is {}, expected -1.0 to 1.0",
+				self, self.pan
+			)));
+		}
+		self.processing.validate()
+	}
+}
+
+/// How several inputs end up in the recording.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioLayout {
+	/// One track with every input mixed in.
+	Mixed,
+	/// One track per input, for mixing after the fact.
+	Tracks,
+}
+
+impl Default for AudioLayout {
+	fn default() -> Self {
+		AudioLayout::Mixed
+	}
+}
+
+/// Raw sample format.
+#[allow(missing_docs)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleFormat {
+	S16LE,
+	S24LE,
+	S32LE,
+	F32LE,
+}
+
+impl Default for SampleFormat {
+	fn default() -> Self {
+		SampleFormat::S16LE
+	}
+}
+
+impl fmt::Display for SampleFormat {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(self.as_str())
+	}
+}
+
+impl FromStr for SampleFormat {
+	type Err = anyhow::Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s.to_ascii_uppercase().as_str() {
+			"S16LE" => Ok(SampleFormat::S16LE),
+			"S24LE" => Ok(SampleFormat::S24LE),
+			"S32LE" => Ok(SampleFormat::S32LE),
+			"F32LE" => Ok(SampleFormat::F32LE),
+			_ => Err(anyhow!("unknown sample format {}, expected S16LE, S24LE, S32LE or F32LE", s)),
+		}
+	}
+}
+
+impl SampleFormat {
+	/// Value of the `format` field in `audio/x-raw` caps.
+	pub fn as_str(self) -> &'static str {
+		match self {
+			SampleFormat::S16LE => "S16LE",
+			SampleFormat::S24LE => "S24LE",
+			SampleFormat::S32LE => "S32LE",
+			SampleFormat::F32LE => "F32LE",
+		}
+	}
+}
+
+/// Audio capture parameters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioSettings {
+	/// Inputs captured at once, at least one.
+	pub inputs: Vec<AudioInput>,
+	/// Whether the inputs are mixed or recorded to separate tracks.
+	pub layout: AudioLayout,
+	/// Sample rate in Hz.
+	pub rate: u32,
+	/// Number of channels.
+	pub channels: u32,
+	/// Sample format of the raw stream.
+	pub format: SampleFormat,
+}
+
+impl Default for AudioSettings {
+	fn default() -> Self {
+		Self {
+			inputs: vec![AudioInput::default()],
+			layout: AudioLayout::default(),
+			rate: 48000,
+			channels: 1,
+			format: SampleFormat::default(),
+		}
+	}
+}
+
+impl AudioSettings {
+	/// Default settings capturing from the default device of `source`.
+	pub fn new(source: AudioSource) -> Self {
+		Self { inputs: vec![AudioInput::new(source)], ..Self::default() }
+	}
+
+	/// Inputs joined for display, e.g. `alsa + pulse:monitor`.
+	pub fn sources(&self) -> String {
+		let inputs: Vec<String> = self.inputs.iter().map(AudioInput::to_string).collect();
+		inputs.join(" + ")
+	}
+
+	/// Tracks in the recording.
+	pub fn tracks(&self) -> usize {
+		match self.layout {
+			AudioLayout::Mixed => 1,
+			AudioLayout::Tracks => self.inputs.len(),
+		}
+	}
+
+	/// Caps the raw stream is converted to.
+	pub fn caps(&self) -> gst::Caps {
+		gst::Caps::builder("audio/x-raw")
+			.field("format", self.format.as_str())
+			.field("rate", self.rate as i32)
+			.field("channels", self.channels as i32)
+			.field("layout", "interleaved")
+			.build()
+	}
+
+	/// Reject parameters no capture device or encoder supports.
+	pub fn validate(&self) -> Result<(), VideoError> {
+		if self.inputs.is_empty() {
+			return Err(VideoError::Config("no audio input".to_string()));
+		}
+		for input in &self.inputs {
+			input.validate()?;
+		}
+		if !(8000..=192_000).contains(&self.rate) {
+			return Err(VideoError::Config(format!(
+				"audio sample rate {} Hz is outside 8000 to 192000 Hz",
+				self.rate
+			)));
+		}
+		if !(1..=8).contains(&self.channels) {
+			return Err(VideoError::Config(format!(
+				"{} audio channels, expected 1 to 8",
+				self.channels
+			)));
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+
+	use super::*;
+	use crate::media::{
+		builder::PreviewSink,
+		recorder::Recorder,
+		recording::{AudioFormat, RecordingOutput},
+		source::CaptureSource,
+		testing,
+	};
+
+	#[test]
+	fn converts_to_requested_format() {
+		let path = testing::temp_path("converted.wav");
+		let settings =
+			AudioSettings { rate: 22050, channels: 2, ..AudioSettings::new(AudioSource::Test) };
+		let builder = Recorder::builder()
+			.audio(settings)
+			.audio_only(AudioFormat::Wav)
+			.output(RecordingOutput::File(path.to_path_buf()));
+		let recorder = match testing::build(builder) {
+			Some(recorder) => recorder,
+			None => return,
+		};
+		testing::record(&recorder, Duration::from_secs(1));
+		assert_eq!(testing::audio_streams(&path), vec![(22050, 2)]);
+	}
+
+	#[test]
+	fn records_a_stream_per_track() {
+		let path = testing::temp_path("tracks.mkv");
+		let settings = AudioSettings {
+			inputs: vec![AudioInput::new(AudioSource::Test), AudioInput::new(AudioSource::Test)],
+			layout: AudioLayout::Tracks,
+			rate: 44100,
+			..AudioSettings::default()
+		};
+		let builder = Recorder::builder()
+			.source(CaptureSource::Test)
+			.resolution(320, 240)
+			.audio(settings)
+			.output(RecordingOutput::File(path.to_path_buf()))
+			.preview_sink(PreviewSink::Fake);
+		let recorder = match testing::build(builder) {
+			Some(recorder) => recorder,
+			None => return,
+		};
+		testing::record(&recorder, Duration::from_secs(1));
+		assert_eq!(testing::audio_streams(&path), vec![(44100, 1), (44100, 1)]);
+	}
+}
@@ -0,0 +1,125 @@
+// Software image adjustments of the camera.
+
+// {source} - {videoconvert} - {videobalance} - [{frame processors}] - {video tee} - ...
+
+// Many cameras expose poor or no hardware controls, so brightness, contrast,
+// saturation and hue are corrected by a `videobalance` ahead of the tee, where
+// preview and recording both see it. At the neutral settings the element runs
+// in passthrough and costs nothing. Every camera keeps its own adjustments,
+// since each one is off in its own way.
+use std::{
+	collections::BTreeMap,
+	path::{Path, PathBuf},
+};
+
+use gst::prelude::*;
+use gstreamer as gst;
+use serde::{Deserialize, Serialize};
+
+use crate::media::{error::VideoError, source::CaptureSource, store};
+
+/// Name of the `videobalance` element of the recorder.
+pub const BALANCE: &str = "desktop-video-balance";
+
+/// Color correction applied to every frame.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ImageAdjustments {
+	/// From -1.0 (black) to 1.0 (white), 0.0 for unchanged.
+	pub brightness: f64,
+	/// From 0.0 (flat grey) to 2.0, 1.0 for unchanged.
+	pub contrast: f64,
+	/// From 0.0 (greyscale) to 2.0, 1.0 for unchanged.
+	pub saturation: f64,
+	/// Rotation of the hues from -1.0 to 1.0, a full turn either way.
+	pub hue: f64,
+}
+
+impl Default for ImageAdjustments {
+	fn default() -> Self {
+		Self { brightness: 0.0, contrast: 1.0, saturation: 1.0, hue: 0.0 }
+	}
+}
+
+impl ImageAdjustments {
+	/// Named starting points for common camera problems.
+	pub const PRESETS: [(&'static str, ImageAdjustments); 4] = [
+		("Vivid", ImageAdjustments { brightness: 0.0, contrast: 1.15, saturation: 1.3, hue: 0.0 }),
+		(
+			"Low light",
+			ImageAdjustments { brightness: 0.12, contrast: 1.2, saturation: 0.9, hue: 0.0 },
+		),
+		(
+			"Washed out",
+			ImageAdjustments { brightness: -0.05, contrast: 1.3, saturation: 1.2, hue: 0.0 },
+		),
+		(
+			"Greyscale",
+			ImageAdjustments { brightness: 0.0, contrast: 1.0, saturation: 0.0, hue: 0.0 },
+		),
+	];
+
+	/// Reject values outside the ranges of `videobalance`.
+	pub fn validate(&self) -> Result<(), VideoError> {
+		let ranges = [
+			("brightness", self.brightness, -1.0, 1.0),
+			("contrast", self.contrast, 0.0, 2.0),
+			("saturation", self.saturation, 0.0, 2.0),
+			("hue", self.hue, -1.0, 1.0),
+		];
+		for (what, value, min, max) in ranges {
+			if !(min..=max).contains(&value) {
+				return Err(VideoError::Config(format!(
+					"{} of {} is not between {} and {}",
+					what, value, min, max
+				)));
+			}
+		}
+		Ok(())
+	}
+
+	/// Set the properties of `balance`, a `videobalance` element.
+	pub fn apply(&self, balance: &gst::Element) -> Result<(), VideoError> {
+		self.validate()?;
+		balance.set_property("brightness", self.brightness);
+		balance.set_property("contrast", self.contrast);
+		balance.set_property("saturation", self.saturation);
+		balance.set_property("hue", self.hue);
+		Ok(())
+	}
+}
+
+/// Adjustments by camera, stored as JSON.
+///
+/// Cameras are told apart by their source, as in `rtsp://door.local/stream`;
+/// local devices share one entry.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CameraAdjustments {
+	cameras: BTreeMap<String, ImageAdjustments>,
+}
+
+impl CameraAdjustments {
+	/// `camera-adjustments.json` in the user's configuration directory.
+	pub fn default_path() -> Option<PathBuf> {
+		store::config_path("camera-adjustments.json")
+	}
+
+	/// Adjustments saved at `path`, none if the file does not exist yet.
+	pub fn load(path: &Path) -> Result<Self, VideoError> {
+		store::load(path)
+	}
+
+	/// Write the adjustments to `path`, creating its directory.
+	pub fn save(&self, path: &Path) -> Result<(), VideoError> {
+		store::save(path, self)
+	}
+
+	/// Adjustments of `camera`, neutral if none were saved.
+	pub fn get(&self, camera: &CaptureSource) -> ImageAdjustments {
+		self.cameras.get(&camera.to_string()).copied().unwrap_or_default()
+	}
+
+	/// Remember `adjustments` for `camera`.
+	pub fn set(&mut self, camera: &CaptureSource, adjustments: ImageAdjustments) {
+		self.cameras.insert(camera.to_string(), adjustments);
+	}
+}
@@ -0,0 +1,1014 @@
+// Typed construction of recorder pipelines.
+
+// {source} - {videobalance} - [{frame processors}] - {video tee} - {queue} - {videorate} - {transform} - {videoconvert} - {videoscale} - {caps} - {x264enc} - {encoded tee} - {queue} - {muxer}
+//                                                               \- {queue} - {videorate} - {transform} - {videoconvert} - {preview sink}
+// {audio inputs} - {level} - [{spectrum}] - {audio tee} - {queue} - {audioconvert} - {voaacenc} -------------------------------------------------------------/
+//                                                             \- [monitor]
+
+// Audio only:
+// {audio inputs} - {level} - [{spectrum}] - {audio tee} - {queue} - {audioconvert} - {audioresample} - {encoder} - {muxer} - {filesink}
+
+// Audio inputs are mixed or recorded to separate tracks as described in
+// `audio.rs`; with separate tracks, every track gets its own encoder. Every
+// input runs through the processing chain of `processing.rs` first, and an
+// `ebur128level` ahead of `level` measures loudness when asked to. An A/V
+// offset delays the audio or the video pads of the muxer, see `sync.rs`. The
+// preview and the encoder mirror, flip, rotate and crop on their own, see
+// `transform.rs`.
+
+// Every setting is checked before the first element is created, so a bad
+// combination fails with a descriptive error instead of a half-built
+// pipeline. Test sources and fake sinks make the whole graph runnable without
+// any hardware.
+use std::{fmt, path::Path, sync::Arc, time::Duration};
+
+use gst::prelude::*;
+use gstreamer as gst;
+use gstreamer::{Caps, Element, Pipeline};
+use gstreamer_app as gst_app;
+
+use crate::media::{
+	audio::{AudioInput, AudioLayout, AudioSettings, AudioSource, SampleFormat},
+	balance::{ImageAdjustments, BALANCE},
+	error::VideoError,
+	frame::{Frame, FrameCallback, FrameSlot},
+	level::{make_level, AudioMeters, LevelCallback},
+	loudness::{make_loudness, LoudnessCallback},
+	mode::{FrameRate, VideoMode},
+	monitor::MonitorSettings,
+	output::EncodedTee,
+	processing::AudioProcessing,
+	processor::ProcessorChain,
+	recorder::Recorder,
+	recording::{segment_pattern, AudioFormat, Profile, RecordingOutput, RecordingSettings},
+	source::{CaptureSource, LiveSource},
+	spectrum::{SpectrumAnalyzer, SpectrumCallback, SpectrumSettings},
+	status::StatusTracker,
+	sync::AvOffset,
+	transform::{Branch, Transform, Transforms},
+};
+
+/// Where preview frames go.
+#[derive(Clone)]
+pub enum PreviewSink {
+	/// RGBA frames published to the recorder's frame slot.
+	Frames,
+	/// Like [`PreviewSink::Frames`], also passing every frame to the callback.
+	Callback(FrameCallback),
+	/// Throw the preview away in a `fakesink`.
+	Fake,
+}
+
+impl Default for PreviewSink {
+	fn default() -> Self {
+		PreviewSink::Frames
+	}
+}
+
+impl fmt::Debug for PreviewSink {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			PreviewSink::Frames => "Frames",
+			PreviewSink::Callback(_) => "Callback",
+			PreviewSink::Fake => "Fake",
+		})
+	}
+}
+
+/// Builder of a [`Recorder`].
+///
+/// ```no_run
+/// use druid_camera::media::{
+/// 	audio::AudioSource,
+/// 	builder::PreviewSink,
+/// 	recorder::Recorder,
+/// 	recording::RecordingOutput,
+/// 	source::CaptureSource,
+/// };
+///
+/// let recorder = Recorder::builder()
+/// 	.source(CaptureSource::Test)
+/// 	.audio_source(AudioSource::Test)
+/// 	.resolution(640, 480)
+/// 	.output(RecordingOutput::Discard)
+/// 	.preview_sink(PreviewSink::Fake)
+/// 	.build()?;
+/// recorder.record(true);
+/// # Ok::<(), druid_camera::media::error::VideoError>(())
+/// ```
+#[derive(Clone)]
+pub struct RecorderBuilder {
+	source: CaptureSource,
+	source_mode: Option<VideoMode>,
+	audio: Option<AudioSettings>,
+	framerate: FrameRate,
+	resolution: Option<(u32, u32)>,
+	profile: Profile,
+	output: RecordingOutput,
+	audio_only: Option<AudioFormat>,
+	preview_sink: PreviewSink,
+	processors: Option<ProcessorChain>,
+	adjustments: ImageAdjustments,
+	transforms: Transforms,
+	monitor: Option<MonitorSettings>,
+	av_offset: AvOffset,
+	on_level: Option<LevelCallback>,
+	on_spectrum: Option<(SpectrumSettings, SpectrumCallback)>,
+	on_loudness: Option<LoudnessCallback>,
+}
+
+impl fmt::Debug for RecorderBuilder {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("RecorderBuilder")
+			.field("source", &self.source)
+			.field("source_mode", &self.source_mode)
+			.field("audio", &self.audio)
+			.field("framerate", &self.framerate)
+			.field("resolution", &self.resolution)
+			.field("profile", &self.profile)
+			.field("output", &self.output)
+			.field("audio_only", &self.audio_only)
+			.field("preview_sink", &self.preview_sink)
+			.field("processors", &self.processors)
+			.field("adjustments", &self.adjustments)
+			.field("transforms", &self.transforms)
+			.field("monitor", &self.monitor)
+			.field("av_offset", &self.av_offset)
+			.field("on_level", &self.on_level.is_some())
+			.field("spectrum", &self.on_spectrum.as_ref().map(|(settings, _)| settings))
+			.field("on_loudness", &self.on_loudness.is_some())
+			.finish()
+	}
+}
+
+impl Default for RecorderBuilder {
+	fn default() -> Self {
+		let recording = RecordingSettings::default();
+		Self {
+			source: CaptureSource::default(),
+			source_mode: None,
+			audio: Some(AudioSettings::default()),
+			framerate: FrameRate::default(),
+			resolution: None,
+			profile: recording.profile,
+			output: recording.output(),
+			audio_only: recording.audio_only,
+			preview_sink: PreviewSink::default(),
+			processors: None,
+			adjustments: ImageAdjustments::default(),
+			transforms: Transforms::default(),
+			monitor: None,
+			av_offset: AvOffset::default(),
+			on_level: None,
+			on_spectrum: None,
+			on_loudness: None,
+		}
+	}
+}
+
+impl RecorderBuilder {
+	/// Capture video from `source`, the local camera by default.
+	pub fn source(mut self, source: CaptureSource) -> Self {
+		self.source = source;
+		self
+	}
+
+	/// Ask the source for `mode` instead of letting caps negotiation pick one.
+	///
+	/// If the source fails to start in that mode the recorder falls back to
+	/// automatic negotiation.
+	pub fn source_mode(mut self, mode: impl Into<Option<VideoMode>>) -> Self {
+		self.source_mode = mode.into();
+		self
+	}
+
+	/// Capture audio with `settings`; `None` records video only.
+	pub fn audio(mut self, settings: impl Into<Option<AudioSettings>>) -> Self {
+		self.audio = settings.into();
+		self
+	}
+
+	/// Capture audio from the default device of `source` alone, keeping the
+	/// other audio settings; `None` records video only.
+	pub fn audio_source(mut self, source: impl Into<Option<AudioSource>>) -> Self {
+		self.audio = source.into().map(|source| AudioSettings {
+			inputs: vec![AudioInput::new(source)],
+			..self.audio.take().unwrap_or_default()
+		});
+		self
+	}
+
+	/// Record at `framerate`, dropping or duplicating frames as needed.
+	pub fn framerate(mut self, framerate: FrameRate) -> Self {
+		self.framerate = framerate;
+		self
+	}
+
+	/// Scale the recording to `width`x`height`; both must be even.
+	pub fn resolution(mut self, width: u32, height: u32) -> Self {
+		self.resolution = Some((width, height));
+		self
+	}
+
+	/// Encode with the `profile` preset.
+	pub fn profile(mut self, profile: Profile) -> Self {
+		self.profile = profile;
+		self
+	}
+
+	/// Write the recording to `output`.
+	pub fn output(mut self, output: RecordingOutput) -> Self {
+		self.output = output;
+		self
+	}
+
+	/// Record only audio in `format`, leaving out the video source and every
+	/// video branch; `None` records video.
+	pub fn audio_only(mut self, format: impl Into<Option<AudioFormat>>) -> Self {
+		self.audio_only = format.into();
+		self
+	}
+
+	/// Take profile, output and audio-only format from `settings`.
+	pub fn recording(self, settings: &RecordingSettings) -> Self {
+		self.profile(settings.profile).output(settings.output()).audio_only(settings.audio_only)
+	}
+
+	/// Send preview frames to `sink`.
+	pub fn preview_sink(mut self, sink: PreviewSink) -> Self {
+		self.preview_sink = sink;
+		self
+	}
+
+	/// Correct the colors of the camera, see
+	/// [`Recorder::set_image_adjustments`] for changing them while running.
+	pub fn image_adjustments(mut self, adjustments: ImageAdjustments) -> Self {
+		self.adjustments = adjustments;
+		self
+	}
+
+	/// Mirror, flip, rotate and crop the preview and the recording, see
+	/// [`Recorder::set_transform`] for changing them while running.
+	pub fn transforms(mut self, transforms: Transforms) -> Self {
+		self.transforms = transforms;
+		self
+	}
+
+	/// Run every camera frame through `chain` before it reaches the preview
+	/// and the encoder. Processors can be added to the chain at any time, also
+	/// through [`Recorder::processors`].
+	pub fn frame_processors(mut self, chain: ProcessorChain) -> Self {
+		self.processors = Some(chain);
+		self
+	}
+
+	/// Play the captured audio while recording, see
+	/// [`Recorder::start_monitor`].
+	pub fn monitor(mut self, settings: impl Into<Option<MonitorSettings>>) -> Self {
+		self.monitor = settings.into();
+		self
+	}
+
+	/// Record the audio `offset` later than the video, or earlier when
+	/// negative, to make up for a camera out of sync with the microphone.
+	pub fn av_offset(mut self, offset: AvOffset) -> Self {
+		self.av_offset = offset;
+		self
+	}
+
+	/// Pass the captured audio levels to `callback`, several times a second.
+	pub fn on_level(mut self, callback: LevelCallback) -> Self {
+		self.on_level = Some(callback);
+		self
+	}
+
+	/// Analyze the captured audio with a `spectrum` element and pass the band
+	/// magnitudes described by `settings` to `callback`.
+	///
+	/// The settings can be changed while recording through
+	/// [`Recorder::spectrum`].
+	pub fn on_spectrum(mut self, settings: SpectrumSettings, callback: SpectrumCallback) -> Self {
+		self.on_spectrum = Some((settings, callback));
+		self
+	}
+
+	/// Measure the loudness of the captured audio after EBU R128 with an
+	/// `ebur128level` element and pass it to `callback`, several times a
+	/// second.
+	pub fn on_loudness(mut self, callback: LoudnessCallback) -> Self {
+		self.on_loudness = Some(callback);
+		self
+	}
+
+	/// Check the settings and build the pipeline, ready to be started with
+	/// [`Recorder::record`].
+	pub fn build(self) -> Result<Recorder, VideoError> {
+		gst::init()?;
+		self.validate()?;
+		let meters = self.meters()?;
+		if let Some(format) = self.audio_only {
+			return self.build_audio_only(format, meters);
+		}
+		let pipeline = Pipeline::new(Some("recorder"));
+
+		// Capture
+		let video_tee = make("tee", "video_tee")?;
+		pipeline.add(&video_tee)?;
+		let camera_sink = match &self.processors {
+			Some(chain) => chain.insert(&pipeline, &video_tee)?,
+			None => video_tee.clone(),
+		};
+		let convert_balance = make("videoconvert", "desktop-video-balance-converter")?;
+		let balance = make("videobalance", BALANCE)?;
+		self.adjustments.apply(&balance)?;
+		pipeline.add_many(&[&convert_balance, &balance])?;
+		Element::link_many(&[&convert_balance, &balance, &camera_sink])?;
+		let camera_sink = convert_balance;
+		let source =
+			LiveSource::new(&pipeline, &self.source, self.source_mode.clone(), &camera_sink)?;
+
+		// Encoding
+		let queue_encoder = make("queue2", "video_queue0")?;
+		let rate_video = make("videorate", "desktop-video-framerate")?;
+		let [crop_video, flip_video] = Transform::make_elements(Branch::Recording)?;
+		let convert_video = make("videoconvert", "desktop-video-converter")?;
+		let scale_video = make("videoscale", "desktop-video-scaler")?;
+		let raw_video_caps = make("capsfilter", "desktop-video-raw-caps")?;
+		let encoder_video = make("x264enc", "desktop-video-encoder")?;
+		let encoder_video_caps = make("capsfilter", "desktop-video-encoder-caps")?;
+		let encoded_tee = make("tee", "video-encoded-tee")?;
+		// Network outputs come and go, the recording must not stall meanwhile.
+		encoded_tee.set_property("allow-not-linked", true);
+		unlimit_queue(&queue_encoder, 512_000_000);
+		raw_video_caps.set_property("caps", &self.raw_video_caps());
+		encoder_video_caps.set_property(
+			"caps",
+			&Caps::builder("video/x-h264").field("profile", self.profile.h264_profile()).build(),
+		);
+		self.profile.configure_encoder(&encoder_video);
+		let encoding = [
+			&queue_encoder,
+			&rate_video,
+			&crop_video,
+			&flip_video,
+			&convert_video,
+			&scale_video,
+			&raw_video_caps,
+			&encoder_video,
+			&encoder_video_caps,
+			&encoded_tee,
+		];
+		pipeline.add_many(&encoding)?;
+		Element::link_many(&encoding)?;
+		link_from_request(&video_tee, &queue_encoder)?;
+		self.transforms.get(Branch::Recording).apply(pipeline.upcast_ref(), Branch::Recording)?;
+
+		// Preview
+		let frames = FrameSlot::default();
+		let status = StatusTracker::new(self.output.location(), Some(&rate_video));
+		status.set_source(source.source());
+		let queue_preview = make("queue2", "video_queue1")?;
+		let rate_preview = make("videorate", "desktop-video-framerate1")?;
+		let [crop_preview, flip_preview] = Transform::make_elements(Branch::Preview)?;
+		let convert_preview = make("videoconvert", "desktop-video-converter1")?;
+		let preview_sink = self.make_preview_sink(&frames, &status)?;
+		unlimit_queue(&queue_preview, 512_000_000);
+		let preview = [
+			&queue_preview,
+			&rate_preview,
+			&crop_preview,
+			&flip_preview,
+			&convert_preview,
+			&preview_sink,
+		];
+		pipeline.add_many(&preview)?;
+		Element::link_many(&preview)?;
+		link_from_request(&video_tee, &queue_preview)?;
+		self.transforms.get(Branch::Preview).apply(pipeline.upcast_ref(), Branch::Preview)?;
+
+		// Recording
+		let queue_video = make("queue2", "desktop-video-queue-1")?;
+		unlimit_queue(&queue_video, 0);
+		pipeline.add(&queue_video)?;
+		link_from_request(&encoded_tee, &queue_video)?;
+		let (muxer, video_template) = self.add_muxer(&pipeline)?;
+		link_to_request(&queue_video, &muxer, video_template)?;
+
+		let mut raw_audio = None;
+		if let Some(settings) = &self.audio {
+			let capture = add_audio_capture(&pipeline, settings, &meters)?;
+			for (i, track) in capture.track_sources().into_iter().enumerate() {
+				let encoder_audio = add_audio_encoder(&pipeline, track, i, "voaacenc", false)?;
+				link_to_request(&encoder_audio, &muxer, "audio_%u")?;
+			}
+			raw_audio = Some(EncodedTee::new(&pipeline, &capture.mix));
+			self.av_offset.apply(&pipeline)?;
+		}
+
+		let encoded = EncodedTee::new(&pipeline, &encoded_tee);
+		let mut recorder = Recorder::start(
+			pipeline,
+			Some(Arc::new(source)),
+			Some(encoded),
+			raw_audio,
+			frames,
+			status,
+			meters,
+		);
+		recorder.processors = self.processors.clone();
+		if let Some(monitor) = &self.monitor {
+			recorder.start_monitor(monitor)?;
+		}
+		Ok(recorder)
+	}
+
+	/// Microphone only pipeline writing `format`.
+	fn build_audio_only(
+		self,
+		format: AudioFormat,
+		meters: AudioMeters,
+	) -> Result<Recorder, VideoError> {
+		let settings = self.audio.as_ref().ok_or_else(|| {
+			VideoError::Config("audio-only recording needs an audio source".to_string())
+		})?;
+		let pipeline = Pipeline::new(Some("recorder"));
+		let capture = add_audio_capture(&pipeline, settings, &meters)?;
+		let encoders = capture
+			.track_sources()
+			.into_iter()
+			.enumerate()
+			.map(|(i, track)| add_audio_encoder(&pipeline, track, i, format.encoder(), true))
+			.collect::<Result<Vec<_>, _>>()?;
+		self.add_audio_sink(&pipeline, format, &encoders)?;
+
+		let status = StatusTracker::new(self.output.location(), None);
+		status.set_source(settings.sources());
+		let raw_audio = EncodedTee::new(&pipeline, &capture.mix);
+		let frames = FrameSlot::default();
+		let mut recorder =
+			Recorder::start(pipeline, None, None, Some(raw_audio), frames, status, meters);
+		if let Some(monitor) = &self.monitor {
+			recorder.start_monitor(monitor)?;
+		}
+		Ok(recorder)
+	}
+
+	/// Reject combinations that cannot work before touching GStreamer state.
+	fn validate(&self) -> Result<(), VideoError> {
+		if let Some(format) = self.audio_only {
+			if self.audio.is_none() {
+				return Err(VideoError::Config(
+					"audio-only recording needs an audio source".to_string(),
+				));
+			}
+			if self.processors.is_some() {
+				return Err(VideoError::Config(
+					"frame processors need a recording with video".to_string(),
+				));
+			}
+			let segmented = matches!(self.output, RecordingOutput::Segments { .. });
+			if segmented && format.muxer().is_none() {
+				return Err(VideoError::Config(format!("{} recordings cannot be segmented", format)));
+			}
+			let tracks = self.audio.as_ref().map_or(1, AudioSettings::tracks);
+			if tracks > 1 && format.muxer().is_none() {
+				return Err(VideoError::Config(format!(
+					"{} recordings hold a single track",
+					format
+				)));
+			}
+		}
+		if let Some((width, height)) = self.resolution {
+			if width == 0 || height == 0 || width % 2 != 0 || height % 2 != 0 {
+				return Err(VideoError::Config(format!(
+					"resolution {}x{} must be non-zero and even",
+					width, height
+				)));
+			}
+		}
+		match &self.output {
+			RecordingOutput::Segments { duration, .. } if duration.is_zero() => {
+				return Err(VideoError::Config("segment duration must not be zero".to_string()));
+			}
+			RecordingOutput::File(location) | RecordingOutput::Segments { location, .. } => {
+				check_location(location)?;
+			}
+			RecordingOutput::Discard => {}
+		}
+		if let Some(audio) = &self.audio {
+			audio.validate()?;
+			if audio.channels != 2 && audio.inputs.iter().any(|input| input.pan != 0.0) {
+				return Err(VideoError::Config("panning needs a stereo recording".to_string()));
+			}
+		}
+		if let Some((settings, _)) = &self.on_spectrum {
+			settings.validate()?;
+		}
+		self.adjustments.validate()?;
+		self.transforms.validate()?;
+		self.av_offset.validate()?;
+		if self.av_offset != AvOffset::default()
+			&& (self.audio.is_none() || self.audio_only.is_some())
+		{
+			return Err(VideoError::Config(
+				"an A/V offset needs a recording with audio and video".to_string(),
+			));
+		}
+		if let Some(monitor) = &self.monitor {
+			if self.audio.is_none() {
+				return Err(VideoError::Config("monitoring needs an audio source".to_string()));
+			}
+			monitor.validate()?;
+		}
+		match (&self.audio, self.audio_only) {
+			// voaacenc supports mono and stereo only.
+			(Some(audio), None | Some(AudioFormat::M4a)) if audio.channels > 2 => {
+				return Err(VideoError::Config(format!(
+					"AAC recording supports 1 or 2 audio channels, not {}",
+					audio.channels
+				)));
+			}
+			// Only the audio-only encoders are preceded by a resampler.
+			(Some(audio), None) if !AAC_RATES.contains(&audio.rate) => {
+				return Err(VideoError::Config(format!(
+					"AAC recording does not support {} Hz audio",
+					audio.rate
+				)));
+			}
+			_ => {}
+		}
+		for factory in self.required_elements() {
+			if gst::ElementFactory::find(factory).is_none() {
+				return Err(VideoError::MissingElement(factory.to_string()));
+			}
+		}
+		Ok(())
+	}
+
+	/// Factories of the fixed part of the pipeline; source bins report their
+	/// own missing elements when parsed.
+	fn required_elements(&self) -> Vec<&'static str> {
+		if let Some(format) = self.audio_only {
+			let mut factories = vec!["tee", "queue2", "capsfilter", "level", format.encoder()];
+			factories.extend(format.muxer());
+			factories.push(match self.output {
+				RecordingOutput::File(_) => "filesink",
+				RecordingOutput::Segments { .. } => "splitmuxsink",
+				RecordingOutput::Discard => "fakesink",
+			});
+			if let Some(audio) = &self.audio {
+				factories.extend(["audioconvert", "audioresample"]);
+				factories.extend(audio_input_elements(audio));
+				factories.extend(self.on_spectrum.as_ref().map(|_| "spectrum"));
+				factories.extend(self.on_loudness.as_ref().map(|_| "ebur128level"));
+			}
+			return factories;
+		}
+		let mut factories = vec![
+			"tee",
+			"queue2",
+			"videorate",
+			"videoconvert",
+			"videoscale",
+			"videobalance",
+			"videocrop",
+			"videoflip",
+			"capsfilter",
+			"x264enc",
+			"matroskamux",
+		];
+		factories.push(match self.preview_sink {
+			PreviewSink::Frames | PreviewSink::Callback(_) => "appsink",
+			PreviewSink::Fake => "fakesink",
+		});
+		if self.processors.is_some() {
+			factories.extend(["appsink", "appsrc"]);
+		}
+		factories.push(match self.output {
+			RecordingOutput::File(_) => "filesink",
+			RecordingOutput::Segments { .. } => "splitmuxsink",
+			RecordingOutput::Discard => "fakesink",
+		});
+		if let Some(audio) = &self.audio {
+			factories.extend(["audioconvert", "audioresample", "level", "voaacenc"]);
+			factories.extend(audio_input_elements(audio));
+			factories.extend(self.on_spectrum.as_ref().map(|_| "spectrum"));
+			factories.extend(self.on_loudness.as_ref().map(|_| "ebur128level"));
+		}
+		factories
+	}
+
+	/// Callbacks for the measurements of the audio branch.
+	fn meters(&self) -> Result<AudioMeters, VideoError> {
+		let spectrum = match &self.on_spectrum {
+			Some((settings, callback)) => {
+				Some((SpectrumAnalyzer::new(*settings)?, callback.clone()))
+			}
+			None => None,
+		};
+		let loudness = self.on_loudness.clone();
+		Ok(AudioMeters { level: self.on_level.clone(), spectrum, loudness })
+	}
+
+	fn raw_video_caps(&self) -> Caps {
+		let mut caps =
+			Caps::builder("video/x-raw").field("framerate", self.framerate.to_fraction());
+		if let Some((width, height)) = self.resolution {
+			caps = caps.field("width", width as i32).field("height", height as i32);
+		}
+		caps.build()
+	}
+
+	/// Appsink publishing RGBA frames, or a fakesink.
+	fn make_preview_sink(
+		&self,
+		frames: &FrameSlot,
+		status: &StatusTracker,
+	) -> Result<Element, VideoError> {
+		let on_frame = match &self.preview_sink {
+			PreviewSink::Fake => return make("fakesink", "video_sink"),
+			PreviewSink::Frames => None,
+			PreviewSink::Callback(callback) => Some(callback.clone()),
+		};
+		let sink = make("appsink", "video_sink")?;
+		let appsink = sink.clone().dynamic_cast::<gst_app::AppSink>().map_err(|_| VideoError::Cast)?;
+		appsink.set_caps(Some(&Caps::new_simple(
+			"video/x-raw",
+			&[("format", &"RGBA"), ("pixel-aspect-ratio", &gst::Fraction::from((1, 1)))],
+		)));
+		let frames = frames.clone();
+		let status = status.clone();
+		appsink.set_callbacks(
+			gst_app::AppSinkCallbacks::builder()
+				.new_sample(move |sink| {
+					let sample = sink.pull_sample().map_err(|_| gst::FlowError::Eos)?;
+					let buffer = sample.buffer().ok_or(gst::FlowError::Error)?;
+					let map = buffer.map_readable().map_err(|_| gst::FlowError::Error)?;
+
+					let pad = sink.static_pad("sink").ok_or(gst::FlowError::Error)?;
+
+					let caps = pad.current_caps().ok_or(gst::FlowError::Error)?;
+					let s = caps.structure(0).ok_or(gst::FlowError::Error)?;
+					let width = s.get::<i32>("width").map_err(|_| gst::FlowError::Error)?;
+					let height = s.get::<i32>("height").map_err(|_| gst::FlowError::Error)?;
+					let frame =
+						Frame { data: map.as_slice().into(), width: width as _, height: height as _ };
+					status.frame();
+					if let Some(on_frame) = &on_frame {
+						on_frame(&frame);
+					}
+					frames.publish(frame);
+
+					Ok(gst::FlowSuccess::Ok)
+				})
+				.build(),
+		);
+		Ok(sink)
+	}
+
+	/// Add the muxer and its sink, returning the element streams are linked to
+	/// and the name of its video pad template.
+	fn add_muxer(&self, pipeline: &Pipeline) -> Result<(Element, &'static str), VideoError> {
+		let muxer = make("matroskamux", "mkv-muxer")?;
+		match &self.output {
+			RecordingOutput::File(location) => {
+				let sink = make("filesink", "mkv-filesink")?;
+				sink.set_property("location", location.display().to_string());
+				pipeline.add_many(&[&muxer, &sink])?;
+				muxer.link(&sink)?;
+				Ok((muxer, "video_%u"))
+			}
+			RecordingOutput::Segments { location, duration } => {
+				let splitmux = make("splitmuxsink", "mkv-splitmuxsink")?;
+				splitmux.set_property("muxer", &muxer);
+				splitmux.set_property("location", segment_pattern(location));
+				splitmux.set_property("max-size-time", duration.as_nanos() as u64);
+				// Ask the encoder for a keyframe so segments end on time.
+				splitmux.set_property("send-keyframe-requests", true);
+				pipeline.add(&splitmux)?;
+				Ok((splitmux, "video"))
+			}
+			RecordingOutput::Discard => {
+				let sink = make("fakesink", "mkv-fakesink")?;
+				pipeline.add_many(&[&muxer, &sink])?;
+				muxer.link(&sink)?;
+				Ok((muxer, "video_%u"))
+			}
+		}
+	}
+
+	/// Link `encoders`, one per track, to the muxer and sink of an audio-only
+	/// recording. Formats without a muxer take a single track.
+	fn add_audio_sink(
+		&self,
+		pipeline: &Pipeline,
+		format: AudioFormat,
+		encoders: &[Element],
+	) -> Result<(), VideoError> {
+		let muxer = format.muxer().map(|factory| make(factory, "audio-muxer")).transpose()?;
+		let sink = match &self.output {
+			RecordingOutput::File(location) => {
+				let sink = make("filesink", "audio-filesink")?;
+				sink.set_property("location", location.display().to_string());
+				sink
+			}
+			RecordingOutput::Segments { location, duration } => {
+				let muxer = muxer.ok_or_else(|| {
+					VideoError::Config(format!("{} recordings cannot be segmented", format))
+				})?;
+				let splitmux = make("splitmuxsink", "audio-splitmuxsink")?;
+				splitmux.set_property("muxer", &muxer);
+				splitmux.set_property("location", segment_pattern(location));
+				splitmux.set_property("max-size-time", duration.as_nanos() as u64);
+				pipeline.add(&splitmux)?;
+				for encoder in encoders {
+					link_to_request(encoder, &splitmux, "audio_%u")?;
+				}
+				return Ok(());
+			}
+			RecordingOutput::Discard => make("fakesink", "audio-fakesink")?,
+		};
+		pipeline.add(&sink)?;
+		match (muxer, encoders) {
+			(Some(muxer), _) => {
+				pipeline.add(&muxer)?;
+				for encoder in encoders {
+					link_to_request(encoder, &muxer, "audio_%u")?;
+				}
+				muxer.link(&sink)?;
+			}
+			(None, [encoder]) => encoder.link(&sink)?,
+			(None, _) => {
+				return Err(VideoError::Config(format!("{} recordings hold a single track", format)))
+			}
+		}
+		Ok(())
+	}
+}
+
+/// Sample rates `voaacenc` accepts.
+const AAC_RATES: [u32; 12] =
+	[8000, 11025, 12000, 16000, 22050, 24000, 32000, 44100, 48000, 64000, 88200, 96000];
+
+/// Raw audio of the capture.
+struct AudioCapture {
+	/// Tee after the meters carrying the mix of every input, feeding the
+	/// monitor and, unless recording separate tracks, the recording.
+	mix: Element,
+	/// Tee of every input when recording separate tracks.
+	tracks: Vec<Element>,
+}
+
+impl AudioCapture {
+	/// Elements the recorded tracks are taken from, in track order.
+	fn track_sources(&self) -> Vec<&Element> {
+		if self.tracks.is_empty() {
+			vec![&self.mix]
+		} else {
+			self.tracks.iter().collect()
+		}
+	}
+}
+
+/// Add the audio inputs, converted to `settings`, mixed if there are several
+/// and measured by a `level` element and, if `meters` asks for them, an
+/// `ebur128level` and a `spectrum` element.
+fn add_audio_capture(
+	pipeline: &Pipeline,
+	settings: &AudioSettings,
+	meters: &AudioMeters,
+) -> Result<AudioCapture, VideoError> {
+	let mut tracks = Vec::new();
+	let mixed = match settings.inputs.as_slice() {
+		[input] => add_audio_input(pipeline, settings, 0, input, &settings.caps())?,
+		inputs => {
+			// `audiomixer` does not take packed 24 bit samples.
+			let mix_caps = AudioSettings { format: SampleFormat::F32LE, ..settings.clone() }.caps();
+			let mixer = make("audiomixer", "desktop-audio-mixer")?;
+			let convert_mix = make("audioconvert", "desktop-audio-mix-converter")?;
+			let mix_caps_filter = make("capsfilter", "desktop-audio-mix-caps")?;
+			mix_caps_filter.set_property("caps", &settings.caps());
+			pipeline.add_many(&[&mixer, &convert_mix, &mix_caps_filter])?;
+			Element::link_many(&[&mixer, &convert_mix, &mix_caps_filter])?;
+			for (i, input) in inputs.iter().enumerate() {
+				let mut upstream = add_audio_input(pipeline, settings, i, input, &mix_caps)?;
+				if settings.layout == AudioLayout::Tracks {
+					let tee = make("tee", &indexed("desktop-audio-track-tee", i))?;
+					let queue = make("queue", &indexed("desktop-audio-mix-queue", i))?;
+					pipeline.add_many(&[&tee, &queue])?;
+					upstream.link(&tee)?;
+					link_from_request(&tee, &queue)?;
+					tracks.push(tee);
+					upstream = queue;
+				}
+				link_to_request(&upstream, &mixer, "sink_%u")?;
+			}
+			mix_caps_filter
+		}
+	};
+	let mut capture = Vec::new();
+	if meters.loudness.is_some() {
+		capture.push(make_loudness("desktop-audio-loudness", Duration::from_millis(100))?);
+	}
+	capture.push(make_level("desktop-audio-level", Duration::from_millis(50))?);
+	if let Some((analyzer, _)) = &meters.spectrum {
+		capture.push(analyzer.make_element("desktop-audio-spectrum", Duration::from_millis(50))?);
+	}
+	let audio_tee = make("tee", "desktop-audio-tee")?;
+	// The monitor comes and goes, the recording must not stall meanwhile.
+	audio_tee.set_property("allow-not-linked", true);
+	capture.push(audio_tee.clone());
+	let capture: Vec<&Element> = capture.iter().collect();
+	pipeline.add_many(&capture)?;
+	Element::link_many(&capture)?;
+	mixed.link(capture[0])?;
+	Ok(AudioCapture { mix: audio_tee, tracks })
+}
+
+/// Add input `i`, with its gain, mute, processing and pan applied, converted to
+/// `caps`. Returns the last element of the chain.
+fn add_audio_input(
+	pipeline: &Pipeline,
+	settings: &AudioSettings,
+	i: usize,
+	input: &AudioInput,
+	caps: &Caps,
+) -> Result<Element, VideoError> {
+	let name = |base: &str| indexed(base, i);
+	let src_audio =
+		input.source.make_element(&name("desktop-audio-source"), input.device.as_deref())?;
+	let convert_audio = make("audioconvert", &name("desktop-audio-converter"))?;
+	let resample_audio = make("audioresample", &name("desktop-audio-resampler"))?;
+	let volume = make("volume", &name("desktop-audio-volume"))?;
+	volume.set_property("volume", input.volume());
+	volume.set_property("mute", input.muted);
+	let mut chain = vec![src_audio, convert_audio, resample_audio, volume];
+	chain.extend(AudioProcessing::make_elements(i)?);
+	// Panning only means something in stereo.
+	if settings.channels == 2 {
+		let panorama = make("audiopanorama", &name("desktop-audio-panorama"))?;
+		panorama.set_property("panorama", input.pan);
+		chain.push(panorama);
+	}
+	let convert_caps = make("audioconvert", &name("desktop-audio-caps-converter"))?;
+	let raw_audio_caps = make("capsfilter", &name("desktop-raw-audio-caps"))?;
+	raw_audio_caps.set_property("caps", caps);
+	chain.extend([convert_caps, raw_audio_caps.clone()]);
+	let chain: Vec<&Element> = chain.iter().collect();
+	pipeline.add_many(&chain)?;
+	Element::link_many(&chain)?;
+	input.processing.apply(pipeline.upcast_ref(), i)?;
+	Ok(raw_audio_caps)
+}
+
+/// Queue the raw audio of track `i` from `tee` and encode it with `factory`,
+/// resampling first if the encoder is picky. Returns the encoder.
+fn add_audio_encoder(
+	pipeline: &Pipeline,
+	tee: &Element,
+	i: usize,
+	factory: &str,
+	resample: bool,
+) -> Result<Element, VideoError> {
+	let queue_audio = make("queue2", &indexed("desktop-audio-queue", i))?;
+	// Encoders support fewer formats and rates than capture devices.
+	let convert_encoder = make("audioconvert", &indexed("desktop-audio-encoder-converter", i))?;
+	let encoder_audio = make(factory, &indexed("desktop-audio-encoder", i))?;
+	unlimit_queue(&queue_audio, 0);
+	let mut audio = vec![queue_audio.clone(), convert_encoder];
+	if resample {
+		audio.push(make("audioresample", &indexed("desktop-audio-encoder-resampler", i))?);
+	}
+	audio.push(encoder_audio.clone());
+	let audio: Vec<&Element> = audio.iter().collect();
+	pipeline.add_many(&audio)?;
+	Element::link_many(&audio)?;
+	link_from_request(tee, &queue_audio)?;
+	Ok(encoder_audio)
+}
+
+/// Factories the audio inputs of `settings` need.
+fn audio_input_elements(settings: &AudioSettings) -> Vec<&'static str> {
+	let mut factories: Vec<&'static str> =
+		settings.inputs.iter().map(|input| input.source.factory()).collect();
+	factories.extend(["volume", "audiodynamic"]);
+	if settings.channels == 2 {
+		factories.push("audiopanorama");
+	}
+	if settings.inputs.len() > 1 {
+		factories.push("audiomixer");
+	}
+	factories
+}
+
+/// `base` for the first of several elements, `base-i` for the others, so a
+/// single input keeps the plain names.
+pub(crate) fn indexed(base: &str, i: usize) -> String {
+	match i {
+		0 => base.to_string(),
+		_ => format!("{}-{}", base, i),
+	}
+}
+
+/// Make an element, naming the factory if its plugin is missing.
+fn make(factory: &str, name: &str) -> Result<Element, VideoError> {
+	gst::ElementFactory::make(factory, Some(name))
+		.map_err(|_| VideoError::MissingElement(factory.to_string()))
+}
+
+/// Let `queue` grow up to `max_bytes`, without buffer or time limits.
+fn unlimit_queue(queue: &Element, max_bytes: u32) {
+	queue.set_property("max-size-bytes", max_bytes);
+	queue.set_property("max-size-buffers", 0u32);
+	queue.set_property("max-size-time", 0u64);
+}
+
+/// Link a new `src_%u` pad of `tee` to the `sink` pad of `downstream`.
+fn link_from_request(tee: &Element, downstream: &Element) -> Result<(), VideoError> {
+	let tee_pad = tee
+		.request_pad_simple("src_%u")
+		.ok_or_else(|| VideoError::RequestPad(tee.name().to_string()))?;
+	let sink_pad = downstream.static_pad("sink").ok_or(VideoError::Caps)?;
+	tee_pad.link(&sink_pad)?;
+	Ok(())
+}
+
+/// Link the `src` pad of `upstream` to a new `template` pad of `downstream`.
+fn link_to_request(
+	upstream: &Element,
+	downstream: &Element,
+	template: &str,
+) -> Result<(), VideoError> {
+	let sink_pad = downstream
+		.request_pad_simple(template)
+		.ok_or_else(|| VideoError::RequestPad(downstream.name().to_string()))?;
+	let src_pad = upstream.static_pad("src").ok_or(VideoError::Caps)?;
+	src_pad.link(&sink_pad)?;
+	Ok(())
+}
+
+/// The recording can only be written into an existing directory.
+fn check_location(location: &Path) -> Result<(), VideoError> {
+	if location.file_name().is_none() {
+		return Err(VideoError::Config(format!("{} is not a file name", location.display())));
+	}
+	match location.parent() {
+		Some(directory) if !directory.as_os_str().is_empty() && !directory.is_dir() => {
+			Err(VideoError::Config(format!("directory {} does not exist", directory.display())))
+		}
+		_ => Ok(()),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::media::{audio::AudioSource, testing};
+
+	fn rejects(builder: RecorderBuilder) -> String {
+		match builder.validate() {
+			Err(VideoError::Config(message)) => message,
+			result => panic!("expected a configuration error, got {:?}", result),
+		}
+	}
+
+	#[test]
+	fn rejects_odd_resolution() {
+		let message = rejects(Recorder::builder().resolution(641, 480));
+		assert!(message.contains("641x480"), "{}", message);
+		rejects(Recorder::builder().resolution(0, 480));
+	}
+
+	#[test]
+	fn rejects_audio_only_without_audio() {
+		let message = rejects(Recorder::builder().audio(None).audio_only(AudioFormat::Wav));
+		assert!(message.contains("audio source"), "{}", message);
+	}
+
+	#[test]
+	fn rejects_out_of_range_av_offset() {
+		let offset = AvOffset(crate::media::sync::MAX_OFFSET.as_millis() as i64 + 1);
+		rejects(Recorder::builder().audio_source(AudioSource::Test).av_offset(offset));
+		rejects(Recorder::builder().audio_source(AudioSource::Test).av_offset(AvOffset(-offset.0)));
+	}
+
+	#[test]
+	fn runs_to_end_of_stream() {
+		let builder = Recorder::builder()
+			.source(CaptureSource::Test)
+			.audio_source(AudioSource::Test)
+			.resolution(320, 240)
+			.output(RecordingOutput::Discard)
+			.preview_sink(PreviewSink::Fake);
+		let recorder = match testing::build(builder) {
+			Some(recorder) => recorder,
+			None => return,
+		};
+		recorder.record(true);
+		assert!(recorder.status.status().recording);
+		std::thread::sleep(Duration::from_secs(1));
+		recorder.finish(Duration::from_secs(5)).expect("end-of-stream did not reach the muxer");
+		assert_eq!(recorder.pipeline.current_state(), gst::State::Null);
+		assert!(!recorder.status.status().recording);
+	}
+}
@@ -0,0 +1,54 @@
+// Errors of the media pipelines.
+use gstreamer as gst;
+use thiserror::Error;
+
+/// Error building or driving a pipeline.
+#[allow(missing_docs)]
+#[derive(Debug, Error)]
+pub enum VideoError {
+	#[error("{0}")]
+	Glib(#[from] glib::Error),
+	#[error("{0}")]
+	Bool(#[from] glib::BoolError),
+	#[error("failed to get the gstreamer bus")]
+	Bus,
+	#[error("{0}")]
+	StateChange(#[from] gst::StateChangeError),
+	#[error("failed to cast gstreamer element")]
+	Cast,
+	#[error("{0}")]
+	Io(#[from] std::io::Error),
+	#[error("invalid URI")]
+	Uri,
+	#[error("failed to get media capabilities")]
+	Caps,
+	#[error("failed to query media duration or position")]
+	Duration,
+	#[error("failed to sync with playback")]
+	Sync,
+	#[error("failed to request a pad from {0}")]
+	RequestPad(String),
+	#[error("missing GStreamer element {0}, is its plugin installed?")]
+	MissingElement(String),
+	#[error("invalid recorder configuration: {0}")]
+	Config(String),
+	#[error("the recorder was built without video")]
+	NoVideo,
+	#[error("the recorder was built without audio")]
+	NoAudio,
+	#[error("failed to parse {description:?}: {message}")]
+	Parse { description: String, message: String },
+	#[error("{element} has no unlinked {pad} pad")]
+	MissingPad { element: String, pad: String },
+	#[error("{upstream} produces {caps}, which {downstream} does not accept")]
+	IncompatibleCaps { upstream: String, downstream: String, caps: String },
+	#[error("failed to link {upstream} to {downstream}: {error}")]
+	Link { upstream: String, downstream: String, error: gst::PadLinkError },
+
+	#[error("{0}")]
+	PadLinkError(#[from] gst::PadLinkError),
+	#[error("{0}")]
+	FlowError(#[from] gstreamer::FlowError),
+	#[error("{0}")]
+	Other(#[from] anyhow::Error),
+}
@@ -0,0 +1,62 @@
+// Raw video frames shared between the pipeline and its consumers.
+use std::{
+	sync::{Arc, Condvar, Mutex},
+	time::Duration,
+};
+
+/// Tightly packed RGBA frame.
+#[derive(Debug, Clone)]
+pub struct Frame {
+	/// Pixel data, `width * height * 4` bytes.
+	pub data: Arc<[u8]>,
+	/// Width in pixels.
+	pub width: u32,
+	/// Height in pixels.
+	pub height: u32,
+}
+
+impl Frame {
+	/// Pixel data without the alpha channel.
+	pub fn to_rgb(&self) -> Vec<u8> {
+		self.data.chunks_exact(4).flat_map(|pixel| [pixel[0], pixel[1], pixel[2]]).collect()
+	}
+}
+
+/// Called with every new frame, on the streaming thread.
+pub type FrameCallback = Arc<dyn Fn(&Frame) + Send + Sync>;
+
+/// Latest frame of a stream, which consumers on other threads can wait for.
+#[derive(Debug, Clone, Default)]
+pub struct FrameSlot {
+	inner: Arc<(Mutex<(u64, Option<Frame>)>, Condvar)>,
+}
+
+impl FrameSlot {
+	/// Replace the latest frame and wake up waiting consumers.
+	pub fn publish(&self, frame: Frame) {
+		let (lock, condvar) = &*self.inner;
+		let mut latest = lock.lock().unwrap();
+		latest.0 += 1;
+		latest.1 = Some(frame);
+		condvar.notify_all();
+	}
+
+	/// Latest frame, if any was published yet.
+	pub fn latest(&self) -> Option<Frame> {
+		self.inner.0.lock().unwrap().1.clone()
+	}
+
+	/// Wait up to `timeout` for a frame newer than the one numbered `seen`.
+	///
+	/// Returns the frame and its number, pass `0` to get any frame.
+	pub fn wait_newer(&self, seen: u64, timeout: Duration) -> Option<(u64, Frame)> {
+		let (lock, condvar) = &*self.inner;
+		let latest = lock.lock().unwrap();
+		let (latest, _) =
+			condvar.wait_timeout_while(latest, timeout, |latest| latest.0 == seen).unwrap();
+		match &latest.1 {
+			Some(frame) if latest.0 != seen => Some((latest.0, frame.clone())),
+			_ => None,
+		}
+	}
+}
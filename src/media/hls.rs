@@ -0,0 +1,125 @@
+// HLS output for browser viewing.
+
+// {encoded tee} - {queue} - {h264parse} - {hlssink2}
+
+// The encoded camera stream is cut into MPEG-TS segments next to a live
+// playlist, which a small HTTP server hands out together with a viewer page.
+// The page relies on native HLS support, which only Safari and mobile browsers
+// have; desktop Chrome and Firefox get a link to the playlist for an external
+// player such as VLC instead. Use the WebRTC output for those.
+//
+// The directory goes into element properties rather than a launch string, so
+// quotes or spaces in its path cannot change the pipeline.
+use std::{
+	fs, io,
+	net::TcpStream,
+	path::{Path, PathBuf},
+};
+
+use gst::prelude::*;
+use gstreamer as gst;
+
+use crate::media::{
+	error::VideoError,
+	http::{self, HttpServer, Request},
+};
+
+const PLAYLIST: &str = "playlist.m3u8";
+const VIEWER: &str = include_str!("hls.html");
+
+/// Settings of the HLS output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HlsSettings {
+	/// Directory the playlist and segments are written to.
+	pub directory: PathBuf,
+	/// Target duration of a segment in seconds.
+	pub segment_duration: u32,
+	/// Number of segments listed in the playlist.
+	pub playlist_length: u32,
+	/// Port of the HTTP server serving the directory.
+	pub http_port: u16,
+}
+
+impl Default for HlsSettings {
+	fn default() -> Self {
+		Self {
+			directory: PathBuf::from(".media/hls"),
+			segment_duration: 2,
+			playlist_length: 5,
+			http_port: 8080,
+		}
+	}
+}
+
+/// Running HLS output and the HTTP server publishing it.
+#[derive(Debug)]
+pub struct HlsOutput {
+	branch: gst::Element,
+	server: HttpServer,
+}
+
+impl HlsOutput {
+	/// Prepare the output directory and start serving it.
+	///
+	/// Nothing is written until [`branch`](Self::branch) is linked to an encoded
+	/// H.264 stream.
+	pub fn start(settings: &HlsSettings) -> Result<Self, VideoError> {
+		gst::init()?;
+		fs::create_dir_all(&settings.directory)?;
+		let directory = settings.directory.canonicalize()?;
+
+		let make = |factory: &str, name: &str| {
+			gst::ElementFactory::make(factory, Some(name))
+				.map_err(|_| VideoError::MissingElement(factory.to_string()))
+		};
+		let queue = make("queue", "hls-queue")?;
+		let parse = make("h264parse", "hls-parse")?;
+		let sink = make("hlssink2", "sink")?;
+		sink.set_property("location", directory.join("segment%05d.ts").display().to_string());
+		sink.set_property("playlist-location", directory.join(PLAYLIST).display().to_string());
+		sink.set_property("target-duration", settings.segment_duration);
+		sink.set_property("playlist-length", settings.playlist_length);
+		// Keep segments around a bit longer than listed for slow clients.
+		sink.set_property("max-files", settings.playlist_length * 2);
+
+		let branch = gst::Bin::new(Some("hls-branch"));
+		branch.add_many(&[&queue, &parse, &sink])?;
+		gst::Element::link_many(&[&queue, &parse, &sink])?;
+		let queue_sink = queue.static_pad("sink").ok_or(VideoError::Caps)?;
+		branch.add_pad(&gst::GhostPad::with_target(Some("sink"), &queue_sink)?)?;
+
+		let server = HttpServer::start(settings.http_port, move |request, mut stream| {
+			if let Err(err) = serve(&directory, &request, &mut stream) {
+				log::debug!("failed to serve {}: {}", request.path, err);
+			}
+		})?;
+
+		Ok(HlsOutput { branch: branch.upcast(), server })
+	}
+
+	/// Branch to link to the encoded video stream.
+	pub fn branch(&self) -> &gst::Element {
+		&self.branch
+	}
+
+	/// URL of the viewer page.
+	pub fn url(&self) -> String {
+		self.server.url()
+	}
+}
+
+fn serve(directory: &Path, request: &Request, stream: &mut TcpStream) -> io::Result<()> {
+	let name = request.path.trim_start_matches('/');
+	if name.is_empty() || name == "index.html" {
+		let content_type = http::content_type("index.html");
+		return http::respond(stream, "200 OK", content_type, VIEWER.as_bytes(), true);
+	}
+	// Only plain file names are served, never anything outside the directory.
+	if name.contains('/') || name.contains("..") {
+		return http::not_found(stream);
+	}
+	match fs::read(directory.join(name)) {
+		Ok(body) => http::respond(stream, "200 OK", http::content_type(name), &body, true),
+		Err(_) => http::not_found(stream),
+	}
+}
@@ -0,0 +1,288 @@
+// Minimal HTTP/1.1 server for local viewers and scripts.
+
+// Each connection is handled on its own thread and handed to a callback
+// together with the parsed request, so handlers can either answer once or keep
+// the stream open (e.g. for `multipart/x-mixed-replace`).
+use std::{
+	io::{self, BufRead, BufReader, Read, Write},
+	net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener, TcpStream, UdpSocket},
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		Arc,
+	},
+	thread,
+	time::Duration,
+};
+
+use crate::media::error::VideoError;
+
+/// Largest request head (request line and headers) that is read.
+pub const MAX_HEAD_SIZE: u64 = 16 * 1024;
+/// Largest request body that is accepted, larger ones get `413`.
+pub const MAX_BODY_SIZE: usize = 64 * 1024;
+/// How long reading a request may stall before the connection is dropped.
+pub const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Parsed HTTP request head and body.
+#[derive(Debug, Clone)]
+pub struct Request {
+	/// Request method, e.g. `GET`.
+	pub method: String,
+	/// Path without the query string.
+	pub path: String,
+	/// Raw query string without the leading `?`.
+	pub query: String,
+	/// Header names are lowercased.
+	pub headers: Vec<(String, String)>,
+	/// Request body, read according to `Content-Length`.
+	pub body: Vec<u8>,
+}
+
+impl Request {
+	/// Read a request from `stream`.
+	///
+	/// Fails with [`io::ErrorKind::InvalidData`] if the body is larger than
+	/// [`MAX_BODY_SIZE`], and with [`io::ErrorKind::UnexpectedEof`] if the head
+	/// is cut off or larger than [`MAX_HEAD_SIZE`].
+	pub fn read(stream: &TcpStream) -> io::Result<Self> {
+		let mut reader = BufReader::new(stream.take(MAX_HEAD_SIZE));
+		let mut line = String::new();
+		reader.read_line(&mut line)?;
+		let mut parts = line.split_whitespace();
+		let method = parts.next().unwrap_or_default().to_string();
+		let target = parts.next().unwrap_or("/");
+		let (path, query) = match target.split_once('?') {
+			Some((path, query)) => (path.to_string(), query.to_string()),
+			None => (target.to_string(), String::new()),
+		};
+
+		let mut headers = Vec::new();
+		loop {
+			line.clear();
+			if reader.read_line(&mut line)? == 0 {
+				return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "incomplete request head"));
+			}
+			let header = line.trim_end();
+			if header.is_empty() {
+				break;
+			}
+			if let Some((name, value)) = header.split_once(':') {
+				headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+			}
+		}
+
+		let length = headers
+			.iter()
+			.find(|(name, _)| name == "content-length")
+			.and_then(|(_, value)| value.parse::<usize>().ok())
+			.unwrap_or(0);
+		if length > MAX_BODY_SIZE {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidData,
+				format!("request body of {} bytes exceeds {} bytes", length, MAX_BODY_SIZE),
+			));
+		}
+		reader.get_mut().set_limit(length as u64);
+		let mut body = vec![0; length];
+		reader.read_exact(&mut body)?;
+		Ok(Request { method, path, query, headers, body })
+	}
+
+	/// Value of the header `name` (lowercase).
+	pub fn header(&self, name: &str) -> Option<&str> {
+		self.headers.iter().find(|(n, _)| n == name).map(|(_, value)| value.as_str())
+	}
+
+	/// Value of the query parameter `name`.
+	pub fn param(&self, name: &str) -> Option<&str> {
+		self.query.split('&').filter_map(|pair| pair.split_once('=')).find_map(
+			|(key, value)| if key == name { Some(value) } else { None },
+		)
+	}
+}
+
+/// Write a complete response with `body` and close the connection.
+///
+/// With `cors` the response may be read by scripts of any origin, which suits
+/// the viewers but nothing that controls the application.
+pub fn respond(
+	stream: &mut TcpStream,
+	status: &str,
+	content_type: &str,
+	body: &[u8],
+	cors: bool,
+) -> io::Result<()> {
+	let cors = if cors { "Access-Control-Allow-Origin: *\r\n" } else { "" };
+	write!(
+		stream,
+		"HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-cache\r\n\
+		 {}Connection: close\r\n\r\n",
+		status,
+		content_type,
+		body.len(),
+		cors
+	)?;
+	stream.write_all(body)?;
+	stream.flush()
+}
+
+/// Respond with `404 Not Found`.
+pub fn not_found(stream: &mut TcpStream) -> io::Result<()> {
+	respond(stream, "404 Not Found", "text/plain", b"not found", false)
+}
+
+/// Content type guessed from the file extension of `path`.
+pub fn content_type(path: &str) -> &'static str {
+	match path.rsplit('.').next().unwrap_or_default() {
+		"html" => "text/html; charset=utf-8",
+		"js" => "application/javascript",
+		"json" => "application/json",
+		"m3u8" => "application/vnd.apple.mpegurl",
+		"ts" => "video/mp2t",
+		"jpg" | "jpeg" => "image/jpeg",
+		_ => "application/octet-stream",
+	}
+}
+
+/// Address other machines on the LAN can reach this one at.
+///
+/// Connecting a UDP socket does not send anything, it only picks the interface
+/// the default route goes through.
+pub fn lan_address() -> IpAddr {
+	UdpSocket::bind("0.0.0.0:0")
+		.and_then(|socket| socket.connect("8.8.8.8:80").map(|_| socket))
+		.and_then(|socket| socket.local_addr())
+		.map(|addr| addr.ip())
+		.unwrap_or(IpAddr::V4(Ipv4Addr::LOCALHOST))
+}
+
+/// HTTP server running on a background thread until dropped.
+#[derive(Debug)]
+pub struct HttpServer {
+	addr: SocketAddr,
+	running: Arc<AtomicBool>,
+}
+
+impl HttpServer {
+	/// Listen on all interfaces at `port` and call `handler` for every request.
+	pub fn start<F>(port: u16, handler: F) -> Result<Self, VideoError>
+	where
+		F: Fn(Request, TcpStream) + Send + Sync + 'static,
+	{
+		Self::start_on(IpAddr::V4(Ipv4Addr::UNSPECIFIED), port, handler)
+	}
+
+	/// Listen on `ip` at `port` and call `handler` for every request.
+	///
+	/// Bind to [`Ipv4Addr::LOCALHOST`] for servers that must not be reachable
+	/// from other machines.
+	pub fn start_on<F>(ip: IpAddr, port: u16, handler: F) -> Result<Self, VideoError>
+	where
+		F: Fn(Request, TcpStream) + Send + Sync + 'static,
+	{
+		let listener = TcpListener::bind((ip, port))?;
+		let addr = listener.local_addr()?;
+		let running = Arc::new(AtomicBool::new(true));
+		let handler = Arc::new(handler);
+
+		let accepting = running.clone();
+		thread::spawn(move || {
+			for stream in listener.incoming() {
+				if !accepting.load(Ordering::Relaxed) {
+					break;
+				}
+				let stream = match stream {
+					Ok(stream) => stream,
+					Err(err) => {
+						log::warn!("failed to accept HTTP connection: {}", err);
+						continue;
+					}
+				};
+				let handler = handler.clone();
+				thread::spawn(move || {
+					let mut stream = stream;
+					let request = stream
+						.set_read_timeout(Some(READ_TIMEOUT))
+						.and_then(|_| Request::read(&stream))
+						// Handlers like the WebSocket signalling keep reading.
+						.and_then(|request| stream.set_read_timeout(None).map(|_| request));
+					match request {
+						Ok(request) => handler(request, stream),
+						Err(err) if err.kind() == io::ErrorKind::InvalidData => {
+							log::debug!("rejected HTTP request: {}", err);
+							let body = err.to_string();
+							let status = "413 Payload Too Large";
+							let body = body.as_bytes();
+							let _ = respond(&mut stream, status, "text/plain", body, false);
+						}
+						Err(err) => log::debug!("failed to read HTTP request: {}", err),
+					}
+				});
+			}
+			log::debug!("HTTP server on {} stopped!", addr);
+		});
+		log::info!("HTTP server listening on {}", addr);
+		Ok(HttpServer { addr, running })
+	}
+
+	/// Port the server listens on.
+	pub fn port(&self) -> u16 {
+		self.addr.port()
+	}
+
+	/// Base URL, reachable from the LAN unless bound to the loopback interface.
+	pub fn url(&self) -> String {
+		let ip = if self.addr.ip().is_loopback() { self.addr.ip() } else { lan_address() };
+		format!("http://{}:{}/", ip, self.port())
+	}
+}
+
+impl Drop for HttpServer {
+	fn drop(&mut self) {
+		self.running.store(false, Ordering::Relaxed);
+		// Wake up the accept loop so it notices the flag.
+		let _ = TcpStream::connect(("127.0.0.1", self.addr.port()));
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn serve() -> HttpServer {
+		HttpServer::start_on(IpAddr::V4(Ipv4Addr::LOCALHOST), 0, |request, mut stream| {
+			let _ = respond(&mut stream, "200 OK", "text/plain", &request.body, false);
+		})
+		.unwrap()
+	}
+
+	fn exchange(server: &HttpServer, request: &[u8]) -> String {
+		let mut stream = TcpStream::connect(("127.0.0.1", server.port())).unwrap();
+		stream.write_all(request).unwrap();
+		let mut response = String::new();
+		stream.read_to_string(&mut response).unwrap();
+		response
+	}
+
+	#[test]
+	fn echoes_body() {
+		let server = serve();
+		let response = exchange(&server, b"POST / HTTP/1.1\r\nContent-Length: 5\r\n\r\nhello");
+		assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+		assert!(response.ends_with("hello"), "{}", response);
+	}
+
+	#[test]
+	fn rejects_large_body() {
+		let server = serve();
+		let request = format!("POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n", MAX_BODY_SIZE + 1);
+		let response = exchange(&server, request.as_bytes());
+		assert!(response.starts_with("HTTP/1.1 413"), "{}", response);
+	}
+
+	#[test]
+	fn binds_localhost() {
+		let server = serve();
+		assert!(server.url().starts_with("http://127.0.0.1:"));
+	}
+}
@@ -0,0 +1,44 @@
+// User supplied gst-launch fragments.
+
+// Sources and outputs are described with the gst-launch syntax and parsed into
+// bins whose unlinked pads are ghosted, the same way the built-in sources and
+// outputs are. Linking checks the caps first so a fragment producing or
+// expecting the wrong media names the element at fault instead of failing with
+// a bare "not negotiated".
+use gst::prelude::*;
+use gstreamer as gst;
+
+use crate::media::error::VideoError;
+
+/// Parse `description` into a bin called `name` exposing every pad in `pads`.
+pub fn parse_bin(description: &str, name: &str, pads: &[&str]) -> Result<gst::Bin, VideoError> {
+	let bin = gst::parse_bin_from_description(description, true).map_err(|err| {
+		VideoError::Parse { description: description.to_string(), message: err.to_string() }
+	})?;
+	bin.set_property("name", name);
+	for pad in pads {
+		if bin.static_pad(pad).is_none() {
+			return Err(VideoError::MissingPad { element: name.to_string(), pad: pad.to_string() });
+		}
+	}
+	Ok(bin)
+}
+
+/// Link `src` to `sink` after checking they can agree on caps.
+pub fn link_checked(src: &gst::Pad, sink: &gst::Pad) -> Result<(), VideoError> {
+	let upstream = owner_name(src);
+	let downstream = owner_name(sink);
+	let caps = src.query_caps(None);
+	if !caps.can_intersect(&sink.query_caps(None)) {
+		return Err(VideoError::IncompatibleCaps { upstream, downstream, caps: caps.to_string() });
+	}
+	src.link(sink).map_err(|error| VideoError::Link { upstream, downstream, error })?;
+	Ok(())
+}
+
+/// Name of the element behind `pad`, looking through ghost pads.
+fn owner_name(pad: &gst::Pad) -> String {
+	let target = pad.downcast_ref::<gst::GhostPad>().and_then(|ghost| ghost.target());
+	let pad = target.as_ref().unwrap_or(pad);
+	pad.parent_element().map_or_else(|| pad.name().to_string(), |element| element.name().to_string())
+}
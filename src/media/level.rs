@@ -0,0 +1,108 @@
+// Audio levels measured on the capture branch.
+
+// ... - {caps} - {level} - ...
+
+// The `level` element passes audio through untouched and posts a message with
+// the loudness of every channel at a fixed interval. The bus watch turns those
+// messages into `AudioLevel`s and hands them to a callback.
+use std::{fmt, sync::Arc, time::Duration};
+
+use gst::prelude::*;
+use gstreamer as gst;
+
+use crate::media::{
+	error::VideoError,
+	loudness::{Loudness, LoudnessCallback},
+	spectrum::{SpectrumAnalyzer, SpectrumCallback},
+};
+
+/// Loudness of every channel over the last interval, in dBFS.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AudioLevel {
+	/// Root mean square per channel.
+	pub rms: Vec<f64>,
+	/// Highest sample per channel.
+	pub peak: Vec<f64>,
+}
+
+/// Called with every level measurement.
+pub type LevelCallback = Arc<dyn Fn(&AudioLevel) + Send + Sync>;
+
+impl AudioLevel {
+	/// Levels in a message posted by the `level` element.
+	pub fn from_message(msg: &gst::Message) -> Option<Self> {
+		if !matches!(msg.view(), gst::MessageView::Element(..)) {
+			return None;
+		}
+		let s = msg.structure().filter(|s| s.name() == "level")?;
+		Some(AudioLevel { rms: channels(s, "rms"), peak: channels(s, "peak") })
+	}
+}
+
+/// Callbacks for the audio measurements posted on a bus.
+#[derive(Clone, Default)]
+pub struct AudioMeters {
+	/// Called with the measurements of the `level` element.
+	pub level: Option<LevelCallback>,
+	/// Analyzer of the `spectrum` element and the callback for its spectra.
+	pub spectrum: Option<(SpectrumAnalyzer, SpectrumCallback)>,
+	/// Called with the measurements of the `ebur128level` element.
+	pub loudness: Option<LoudnessCallback>,
+}
+
+impl fmt::Debug for AudioMeters {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("AudioMeters")
+			.field("level", &self.level.is_some())
+			.field("spectrum", &self.spectrum.as_ref().map(|(analyzer, _)| analyzer))
+			.field("loudness", &self.loudness.is_some())
+			.finish()
+	}
+}
+
+impl AudioMeters {
+	/// Pass a measurement in `msg` to its callback; `false` for other messages.
+	pub fn dispatch(&self, msg: &gst::Message) -> bool {
+		if let Some(on_level) = &self.level {
+			if let Some(level) = AudioLevel::from_message(msg) {
+				on_level(&level);
+				return true;
+			}
+		}
+		if let Some((analyzer, on_spectrum)) = &self.spectrum {
+			if let Some(spectrum) = analyzer.read(msg) {
+				on_spectrum(&spectrum);
+				return true;
+			}
+		}
+		if let Some(on_loudness) = &self.loudness {
+			if let Some(loudness) = Loudness::from_message(msg) {
+				on_loudness(&loudness);
+				return true;
+			}
+		}
+		false
+	}
+}
+
+/// `db` mapped from `-range_db..=0` to `0.0..=1.0`.
+pub fn normalize(db: f64, range_db: f64) -> f64 {
+	((db + range_db) / range_db).clamp(0.0, 1.0)
+}
+
+/// Build a `level` element posting a message every `interval`.
+pub fn make_level(name: &str, interval: Duration) -> Result<gst::Element, VideoError> {
+	let level = gst::ElementFactory::make("level", Some(name))
+		.map_err(|_| VideoError::MissingElement("level".to_string()))?;
+	level.set_property("post-messages", true);
+	level.set_property("interval", interval.as_nanos() as u64);
+	Ok(level)
+}
+
+/// Per channel values of the `field` array.
+fn channels(s: &gst::StructureRef, field: &str) -> Vec<f64> {
+	match s.get::<glib::ValueArray>(field) {
+		Ok(values) => values.iter().filter_map(|value| value.get::<f64>().ok()).collect(),
+		Err(_) => Vec::new(),
+	}
+}
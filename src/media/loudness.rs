@@ -0,0 +1,77 @@
+// Loudness of the captured audio after EBU R128.
+
+// ... - {audiomixer} - {ebur128level} - {level} - ...
+
+// `ebur128level` measures the mix the way broadcast loudness is specified, in
+// LUFS, and posts a message at a fixed interval. The integrated loudness
+// covers everything since the pipeline started, so it is the figure to compare
+// with a target like -16 LUFS for podcasts; momentary and short-term loudness
+// follow the last 400 ms and 3 s.
+use std::{sync::Arc, time::Duration};
+
+use gst::prelude::*;
+use gstreamer as gst;
+
+use crate::media::error::VideoError;
+
+/// Loudness measured by `ebur128level`, in LUFS.
+///
+/// Silence measures as negative infinity.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Loudness {
+	/// Loudness over the last 400 ms.
+	pub momentary: f64,
+	/// Loudness over the last 3 s.
+	pub short_term: f64,
+	/// Loudness of everything measured so far.
+	pub integrated: f64,
+	/// Spread between quiet and loud passages so far, in LU.
+	pub range: f64,
+}
+
+impl Default for Loudness {
+	fn default() -> Self {
+		Self {
+			momentary: f64::NEG_INFINITY,
+			short_term: f64::NEG_INFINITY,
+			integrated: f64::NEG_INFINITY,
+			range: 0.0,
+		}
+	}
+}
+
+/// Called with every loudness measurement.
+pub type LoudnessCallback = Arc<dyn Fn(&Loudness) + Send + Sync>;
+
+impl Loudness {
+	/// Loudness in a message posted by the `ebur128level` element.
+	pub fn from_message(msg: &gst::Message) -> Option<Self> {
+		if !matches!(msg.view(), gst::MessageView::Element(..)) {
+			return None;
+		}
+		let s = msg.structure().filter(|s| s.name() == "ebur128-level")?;
+		let field = |name: &str| s.get::<f64>(name).ok();
+		let default = Self::default();
+		Some(Self {
+			momentary: field("momentary-loudness").unwrap_or(default.momentary),
+			short_term: field("shortterm-loudness").unwrap_or(default.short_term),
+			integrated: field("global-loudness").unwrap_or(default.integrated),
+			range: field("loudness-range").unwrap_or(default.range),
+		})
+	}
+}
+
+/// Build an `ebur128level` element posting a message every `interval`.
+pub fn make_loudness(name: &str, interval: Duration) -> Result<gst::Element, VideoError> {
+	let loudness = gst::ElementFactory::make("ebur128level", Some(name))
+		.map_err(|_| VideoError::MissingElement("ebur128level".to_string()))?;
+	loudness.set_property("post-messages", true);
+	loudness.set_property("interval", interval.as_nanos() as u64);
+	Ok(loudness)
+}
+
+/// Whether `ebur128level` is installed. It comes with the Rust plugins, which
+/// not every distribution ships.
+pub fn available() -> bool {
+	gst::init().is_ok() && gst::ElementFactory::find("ebur128level").is_some()
+}
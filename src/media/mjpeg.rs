@@ -0,0 +1,193 @@
+// MJPEG-over-HTTP preview server.
+
+// {preview appsink} ~~> {frame slot} ~~> {jpeg encoder thread} ~~> viewers
+
+// The preview frames are already converted to RGBA for the UI, so they are
+// picked up from the frame slot and compressed on a dedicated thread instead of
+// adding a jpegenc branch to the pipeline. Frames are only encoded while a
+// viewer is connected; snapshots encode the latest frame on demand.
+use std::{
+	io::{self, Write},
+	net::TcpStream,
+	sync::{
+		atomic::{AtomicBool, AtomicUsize, Ordering},
+		Arc, Condvar, Mutex,
+	},
+	thread,
+	time::Duration,
+};
+
+use image::{codecs::jpeg::JpegEncoder, ColorType};
+
+use crate::media::{
+	error::VideoError,
+	frame::{Frame, FrameSlot},
+	http::{self, HttpServer},
+};
+
+const BOUNDARY: &str = "frame";
+
+/// Settings of the MJPEG server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MjpegSettings {
+	/// Port the server listens on.
+	pub port: u16,
+	/// JPEG quality from 1 to 100.
+	pub quality: u8,
+}
+
+impl Default for MjpegSettings {
+	fn default() -> Self {
+		Self { port: 8081, quality: 80 }
+	}
+}
+
+/// Encoded JPEG frames, numbered like the raw frames they come from.
+type JpegSlot = Arc<(Mutex<(u64, Option<Arc<Vec<u8>>>)>, Condvar)>;
+
+/// Running MJPEG server.
+#[derive(Debug)]
+pub struct MjpegServer {
+	server: HttpServer,
+	running: Arc<AtomicBool>,
+}
+
+impl MjpegServer {
+	/// Serve `/stream.mjpg` and `/snapshot.jpg` from the frames published to
+	/// `frames`.
+	pub fn start(settings: &MjpegSettings, frames: FrameSlot) -> Result<Self, VideoError> {
+		let running = Arc::new(AtomicBool::new(true));
+		let viewers = Arc::new(AtomicUsize::new(0));
+		let jpegs: JpegSlot = Default::default();
+		let quality = settings.quality.clamp(1, 100);
+
+		let encoder_running = running.clone();
+		let encoder_viewers = viewers.clone();
+		let encoder_frames = frames.clone();
+		let encoder_jpegs = jpegs.clone();
+		thread::spawn(move || {
+			let mut seen = 0;
+			while encoder_running.load(Ordering::Relaxed) {
+				let (number, frame) =
+					match encoder_frames.wait_newer(seen, Duration::from_millis(200)) {
+						Some(newer) => newer,
+						None => continue,
+					};
+				seen = number;
+				if encoder_viewers.load(Ordering::Relaxed) == 0 {
+					continue;
+				}
+				match encode(&frame, quality) {
+					Ok(jpeg) => {
+						let (lock, condvar) = &*encoder_jpegs;
+						*lock.lock().unwrap() = (number, Some(Arc::new(jpeg)));
+						condvar.notify_all();
+					}
+					Err(err) => log::warn!("failed to encode preview frame: {}", err),
+				}
+			}
+			log::debug!("MJPEG encoder stopped!");
+		});
+
+		let server_running = running.clone();
+		let server = HttpServer::start(settings.port, move |request, mut stream| {
+			let result = match request.path.as_str() {
+				"/" | "/stream.mjpg" => {
+					viewers.fetch_add(1, Ordering::Relaxed);
+					let result = stream_frames(&mut stream, &jpegs, &server_running);
+					viewers.fetch_sub(1, Ordering::Relaxed);
+					result
+				}
+				"/snapshot.jpg" => snapshot(&mut stream, &frames, quality),
+				_ => http::not_found(&mut stream),
+			};
+			if let Err(err) = result {
+				log::debug!("MJPEG viewer {} left: {}", request.path, err);
+			}
+		})?;
+
+		Ok(MjpegServer { server, running })
+	}
+
+	/// URL of the MJPEG stream.
+	pub fn url(&self) -> String {
+		format!("{}stream.mjpg", self.server.url())
+	}
+}
+
+impl Drop for MjpegServer {
+	fn drop(&mut self) {
+		self.running.store(false, Ordering::Relaxed);
+	}
+}
+
+/// Compress `frame` to JPEG.
+pub fn encode(frame: &Frame, quality: u8) -> image::ImageResult<Vec<u8>> {
+	let mut jpeg = Vec::new();
+	JpegEncoder::new_with_quality(&mut jpeg, quality).encode(
+		&frame.to_rgb(),
+		frame.width,
+		frame.height,
+		ColorType::Rgb8,
+	)?;
+	Ok(jpeg)
+}
+
+fn stream_frames(stream: &mut TcpStream, jpegs: &JpegSlot, running: &AtomicBool) -> io::Result<()> {
+	write!(
+		stream,
+		"HTTP/1.1 200 OK\r\nContent-Type: multipart/x-mixed-replace; boundary={}\r\n\
+		 Cache-Control: no-cache\r\nAccess-Control-Allow-Origin: *\r\nConnection: close\r\n\r\n",
+		BOUNDARY
+	)?;
+	let (lock, condvar) = &**jpegs;
+	let mut seen = 0;
+	while running.load(Ordering::Relaxed) {
+		let jpeg = {
+			let latest = lock.lock().unwrap();
+			let (latest, _) = condvar
+				.wait_timeout_while(latest, Duration::from_millis(200), |latest| latest.0 == seen)
+				.unwrap();
+			match &latest.1 {
+				Some(jpeg) if latest.0 != seen => {
+					seen = latest.0;
+					jpeg.clone()
+				}
+				_ => continue,
+			}
+		};
+		write!(
+			stream,
+			"--{}\r\nContent-Type: image/jpeg\r\nContent-Length: {}\r\n\r\n",
+			BOUNDARY,
+			jpeg.len()
+		)?;
+		stream.write_all(&jpeg)?;
+		stream.write_all(b"\r\n")?;
+		stream.flush()?;
+	}
+	Ok(())
+}
+
+fn snapshot(stream: &mut TcpStream, frames: &FrameSlot, quality: u8) -> io::Result<()> {
+	let frame = match frames.latest() {
+		Some(frame) => frame,
+		None => {
+			let status = "503 Service Unavailable";
+			return http::respond(stream, status, "text/plain", b"no frame yet", true);
+		}
+	};
+	match encode(&frame, quality) {
+		Ok(jpeg) => {
+			let content_type = http::content_type("snapshot.jpg");
+			http::respond(stream, "200 OK", content_type, &jpeg, true)
+		}
+		Err(err) => http::respond(
+			stream,
+			"500 Internal Server Error",
+			"text/plain",
+			err.to_string().as_bytes(),
+			true,
+		),
+	}
+}
@@ -1 +1,58 @@
+/// Audio capture settings.
+pub mod audio;
+/// Software image adjustments of the camera.
+pub mod balance;
+/// Typed construction of recorder pipelines.
+pub mod builder;
+/// Errors of the media pipelines.
+pub mod error;
+/// Raw video frames shared between the pipeline and its consumers.
+pub mod frame;
+/// HLS output for browser viewing.
+pub mod hls;
+/// Minimal HTTP/1.1 server for local viewers and scripts.
+pub mod http;
+/// User supplied gst-launch fragments.
+pub mod launch;
+/// Audio levels measured on the capture branch.
+pub mod level;
+/// Loudness of the captured audio after EBU R128.
+pub mod loudness;
+/// MJPEG-over-HTTP preview server.
+pub mod mjpeg;
+/// Video modes offered by capture sources.
+pub mod mode;
+/// Listening to the captured audio while recording.
+pub mod monitor;
+/// Network outputs attached to the encoded camera stream.
+pub mod output;
+/// Playback of recordings.
+pub mod playback;
+/// Dynamics processing of every audio input.
+pub mod processing;
+/// Rust code modifying every camera frame.
+pub mod processor;
+/// Recording pipeline, independent of any user interface.
+pub mod recorder;
+/// Recording file and encoder settings.
+pub mod recording;
+/// Embedded RTSP server publishing the encoded camera stream.
+pub mod rtsp;
+/// Capture sources feeding the recorder pipeline.
+pub mod source;
+/// Audio spectrum measured on the capture and playback branches.
+pub mod spectrum;
+/// Live recorder counters shared with reporting threads.
+pub mod status;
+/// Settings kept between runs.
+pub mod store;
+/// Audio/video sync offset at the muxer.
+pub mod sync;
+#[cfg(test)]
+mod testing;
+/// Single frames decoded from recordings.
 pub mod thumbnail;
+/// Mirror, flip, rotation and crop of the camera image.
+pub mod transform;
+/// WebRTC preview publishing with a built-in signalling endpoint.
+pub mod webrtc;
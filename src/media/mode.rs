@@ -0,0 +1,224 @@
+// Video modes offered by capture sources.
+
+// {source} - {capsfilter name=source-mode} - ...
+// {source} - {capsfilter name=source-mode} - {jpegdec} - {videoconvert} - ...
+
+// The caps a source can produce are expanded into one mode per format, size
+// and framerate. Cameras list NTSC rates such as 30000/1001, so framerates are
+// kept as fractions. The chosen mode is applied with a capsfilter inside the
+// source bin; compressed MJPEG modes are decoded right after it.
+use std::{fmt, str::FromStr};
+
+use anyhow::{anyhow, Context};
+use gstreamer as gst;
+use num_rational::Ratio;
+use num_traits::ToPrimitive;
+
+/// Frames per second.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FrameRate(pub Ratio<i32>);
+
+impl FrameRate {
+	/// 24 frames per second.
+	pub const F24: FrameRate = FrameRate(Ratio::new_raw(24, 1));
+	/// 30 frames per second.
+	pub const F30: FrameRate = FrameRate(Ratio::new_raw(30, 1));
+
+	/// `numer / denom` frames per second.
+	pub fn new(numer: i32, denom: i32) -> Self {
+		FrameRate(Ratio::new(numer, denom))
+	}
+
+	/// Rate as a float, e.g. `29.97`.
+	pub fn fps(self) -> f64 {
+		self.0.to_f64().unwrap_or_default()
+	}
+
+	/// Rate as a caps field value.
+	pub fn to_fraction(self) -> gst::Fraction {
+		gst::Fraction(self.0)
+	}
+}
+
+impl Default for FrameRate {
+	fn default() -> Self {
+		FrameRate::F24
+	}
+}
+
+impl From<gst::Fraction> for FrameRate {
+	fn from(fraction: gst::Fraction) -> Self {
+		FrameRate(fraction.0)
+	}
+}
+
+impl fmt::Display for FrameRate {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		if self.0.is_integer() {
+			write!(f, "{}", self.0.numer())
+		} else {
+			write!(f, "{:.2}", self.fps())
+		}
+	}
+}
+
+impl FromStr for FrameRate {
+	type Err = anyhow::Error;
+
+	/// Parse `30`, `30000/1001` or `29.97`.
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let rate = if let Some((numer, denom)) = s.split_once('/') {
+			let numer = numer.trim().parse().with_context(|| format!("invalid framerate {}", s))?;
+			let denom = denom.trim().parse().with_context(|| format!("invalid framerate {}", s))?;
+			if denom == 0 {
+				return Err(anyhow!("framerate {} has a zero denominator", s));
+			}
+			Ratio::new(numer, denom)
+		} else if s.contains('.') {
+			let fps: f64 = s.parse().with_context(|| format!("invalid framerate {}", s))?;
+			// Recover NTSC rates, 29.97 is 30000/1001 rather than 2997/100.
+			let ntsc = (fps * 1.001).round();
+			if (ntsc / 1.001 - fps).abs() < 0.01 {
+				let numer = (ntsc as i32)
+					.checked_mul(1000)
+					.ok_or_else(|| anyhow!("framerate {} is too large", s))?;
+				Ratio::new(numer, 1001)
+			} else {
+				Ratio::approximate_float(fps).ok_or_else(|| anyhow!("invalid framerate {}", s))?
+			}
+		} else {
+			Ratio::from_integer(s.parse().with_context(|| format!("invalid framerate {}", s))?)
+		};
+		if *rate.numer() <= 0 {
+			return Err(anyhow!("framerate {} must be positive", s));
+		}
+		Ok(FrameRate(rate))
+	}
+}
+
+/// Format, size and framerate a source can produce.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct VideoMode {
+	/// Raw video format like `YUY2`, or `MJPG` for compressed frames.
+	pub format: String,
+	/// Width in pixels.
+	pub width: u32,
+	/// Height in pixels.
+	pub height: u32,
+	/// Frames per second.
+	pub framerate: FrameRate,
+}
+
+impl fmt::Display for VideoMode {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}x{} @ {} fps ({})", self.width, self.height, self.framerate, self.format)
+	}
+}
+
+impl VideoMode {
+	const JPEG: &'static str = "MJPG";
+
+	/// Caps selecting this mode.
+	pub fn caps(&self) -> String {
+		let media = if self.format == Self::JPEG {
+			"image/jpeg".to_string()
+		} else {
+			format!("video/x-raw, format={}", self.format)
+		};
+		format!(
+			"{}, width={}, height={}, framerate={}/{}",
+			media,
+			self.width,
+			self.height,
+			self.framerate.0.numer(),
+			self.framerate.0.denom()
+		)
+	}
+
+	/// Fragment appended to a source description to select this mode.
+	pub fn description(&self) -> String {
+		let filter = format!("capsfilter name=source-mode caps=\"{}\"", self.caps());
+		if self.format == Self::JPEG {
+			format!("{} ! jpegdec ! videoconvert", filter)
+		} else {
+			filter
+		}
+	}
+}
+
+/// Every fixed mode in `caps`, largest and fastest first.
+///
+/// Sizes given as ranges cannot be listed and are skipped; framerate ranges
+/// contribute their bounds.
+pub fn modes_from_caps(caps: &gst::CapsRef) -> Vec<VideoMode> {
+	let mut modes = Vec::new();
+	for s in caps.iter() {
+		let formats = match s.name() {
+			"video/x-raw" => values::<String>(s, "format"),
+			"image/jpeg" => vec![VideoMode::JPEG.to_string()],
+			_ => continue,
+		};
+		let widths = values::<i32>(s, "width");
+		let heights = values::<i32>(s, "height");
+		let mut framerates: Vec<FrameRate> =
+			values::<gst::Fraction>(s, "framerate").into_iter().map(FrameRate::from).collect();
+		if let Ok(range) = s.get::<gst::FractionRange>("framerate") {
+			framerates.push(range.min().into());
+			framerates.push(range.max().into());
+		}
+		for format in &formats {
+			for &width in &widths {
+				for &height in &heights {
+					for &framerate in framerates.iter().filter(|rate| *rate.0.numer() > 0) {
+						modes.push(VideoMode {
+							format: format.clone(),
+							width: width as u32,
+							height: height as u32,
+							framerate,
+						});
+					}
+				}
+			}
+		}
+	}
+	modes.sort_by(|a, b| {
+		let key = |mode: &VideoMode| (mode.width * mode.height, mode.framerate);
+		key(b).cmp(&key(a)).then_with(|| a.format.cmp(&b.format))
+	});
+	modes.dedup();
+	modes
+}
+
+/// Fixed value or list of values of `field`.
+fn values<T>(s: &gst::StructureRef, field: &str) -> Vec<T>
+where
+	T: for<'a> glib::value::FromValue<'a> + 'static,
+{
+	if let Ok(value) = s.get::<T>(field) {
+		return vec![value];
+	}
+	match s.get::<gst::List>(field) {
+		Ok(list) => list.as_slice().iter().filter_map(|value| value.get::<T>().ok()).collect(),
+		Err(_) => Vec::new(),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parses_framerates() {
+		assert_eq!("30".parse::<FrameRate>().unwrap(), FrameRate::F30);
+		assert_eq!("30000/1001".parse::<FrameRate>().unwrap(), FrameRate::new(30000, 1001));
+		assert_eq!("29.97".parse::<FrameRate>().unwrap(), FrameRate::new(30000, 1001));
+		assert_eq!("12.5".parse::<FrameRate>().unwrap(), FrameRate::new(25, 2));
+	}
+
+	#[test]
+	fn rejects_invalid_framerates() {
+		for s in ["30/0", "0", "-30", "30/-1", "2999999.997", "fast", "30/x"] {
+			assert!(s.parse::<FrameRate>().is_err(), "{} was accepted", s);
+		}
+	}
+}
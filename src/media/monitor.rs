@@ -0,0 +1,209 @@
+// Listening to the captured audio while recording.
+
+// ... - {level} - [{spectrum}] - {audio tee} - {queue} - ... recording
+//                                           \- {queue} - {volume} - {audioconvert} - {audioresample} - {audio sink}
+
+// The monitor branch hangs off the raw audio tee behind a leaky queue, so a
+// slow or missing sound card drops monitored audio instead of stalling the
+// recording, and its volume only changes what is heard. The latency setting
+// caps both that queue and the buffer of the audio sink.
+use std::{fmt, str::FromStr, time::Duration};
+
+use anyhow::anyhow;
+use gst::prelude::*;
+use gstreamer as gst;
+
+use crate::media::{error::VideoError, launch};
+
+/// Where monitored audio is played.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MonitorSink {
+	/// The default sound output, picked by `autoaudiosink`.
+	Auto,
+	/// Throw the audio away in a `fakesink`, for machines without sound.
+	Fake,
+}
+
+impl Default for MonitorSink {
+	fn default() -> Self {
+		MonitorSink::Auto
+	}
+}
+
+impl fmt::Display for MonitorSink {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			MonitorSink::Auto => "auto",
+			MonitorSink::Fake => "fake",
+		})
+	}
+}
+
+impl FromStr for MonitorSink {
+	type Err = anyhow::Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"auto" => Ok(MonitorSink::Auto),
+			"fake" => Ok(MonitorSink::Fake),
+			_ => Err(anyhow!("unknown monitor sink {}, expected auto or fake", s)),
+		}
+	}
+}
+
+/// Settings of the audio monitor.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MonitorSettings {
+	/// Loudness of what is heard, 1.0 for unchanged.
+	pub volume: f64,
+	/// Audio buffered before the sound card; lower reacts faster but may
+	/// crackle on a busy machine.
+	pub latency: Duration,
+	/// Where the audio is played.
+	pub sink: MonitorSink,
+}
+
+impl Default for MonitorSettings {
+	fn default() -> Self {
+		Self { volume: 1.0, latency: Duration::from_millis(40), sink: MonitorSink::Auto }
+	}
+}
+
+impl MonitorSettings {
+	/// Reject volumes and latencies the elements cannot use.
+	pub fn validate(&self) -> Result<(), VideoError> {
+		check_volume(self.volume)?;
+		if !(Duration::from_millis(5)..=Duration::from_secs(1)).contains(&self.latency) {
+			return Err(VideoError::Config(format!(
+				"monitor latency of {:?} is not between 5 ms and 1 s",
+				self.latency
+			)));
+		}
+		Ok(())
+	}
+
+	fn description(&self) -> String {
+		let sink = match self.sink {
+			MonitorSink::Auto => "autoaudiosink name=sink",
+			MonitorSink::Fake => "fakesink name=sink sync=false async=false",
+		};
+		format!(
+			"queue leaky=downstream max-size-buffers=0 max-size-bytes=0 max-size-time={} ! \
+			 volume name=volume volume={} ! audioconvert ! audioresample ! {}",
+			self.latency.as_nanos(),
+			self.volume,
+			sink
+		)
+	}
+}
+
+/// Monitor branch playing the captured audio, ready to be linked to the raw
+/// audio tee.
+#[derive(Debug)]
+pub struct AudioMonitor {
+	/// Settings the monitor was started with, volume kept up to date.
+	pub settings: MonitorSettings,
+	branch: gst::Element,
+	volume: gst::Element,
+}
+
+impl AudioMonitor {
+	/// Build the monitor branch for `settings`.
+	pub fn start(settings: &MonitorSettings) -> Result<Self, VideoError> {
+		gst::init()?;
+		settings.validate()?;
+		let bin = launch::parse_bin(&settings.description(), "audio-monitor", &["sink"])?;
+		let volume = bin.by_name("volume").ok_or(VideoError::Cast)?;
+		if let Some(sink) = bin.by_name("sink").and_then(|sink| sink.downcast::<gst::Bin>().ok()) {
+			// The actual sink is only created once autoaudiosink starts.
+			let latency = settings.latency;
+			sink.connect_element_added(move |_, element| {
+				if element.find_property("buffer-time").is_some() {
+					let buffer = latency.as_micros() as i64;
+					element.set_property("buffer-time", buffer);
+					element.set_property("latency-time", (buffer / 2).min(10_000));
+				}
+			});
+		}
+		Ok(Self { settings: settings.clone(), branch: bin.upcast(), volume })
+	}
+
+	/// Bin to attach to the raw audio tee.
+	pub fn branch(&self) -> &gst::Element {
+		&self.branch
+	}
+
+	/// Change the loudness of what is heard.
+	pub fn set_volume(&mut self, volume: f64) -> Result<(), VideoError> {
+		check_volume(volume)?;
+		self.volume.set_property("volume", volume);
+		self.settings.volume = volume;
+		Ok(())
+	}
+}
+
+/// `volume` accepts up to 10, anything above 4 is deafening.
+fn check_volume(volume: f64) -> Result<(), VideoError> {
+	if !(0.0..=4.0).contains(&volume) {
+		return Err(VideoError::Config(format!("monitor volume {} is not between 0 and 4", volume)));
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{
+		sync::{
+			atomic::{AtomicUsize, Ordering},
+			Arc,
+		},
+		thread,
+	};
+
+	use super::*;
+	use crate::media::{
+		audio::{AudioSettings, AudioSource},
+		recorder::Recorder,
+		recording::{AudioFormat, RecordingOutput},
+		testing,
+	};
+
+	#[test]
+	fn parses_sink() {
+		assert_eq!("fake".parse::<MonitorSink>().unwrap(), MonitorSink::Fake);
+		let auto = MonitorSink::Auto.to_string();
+		assert_eq!(auto.parse::<MonitorSink>().unwrap(), MonitorSink::Auto);
+		assert!("speakers".parse::<MonitorSink>().is_err());
+	}
+
+	#[test]
+	fn attaches_and_detaches_while_recording() {
+		let builder = Recorder::builder()
+			.audio(AudioSettings::new(AudioSource::Test))
+			.audio_only(AudioFormat::Wav)
+			.output(RecordingOutput::Discard);
+		let mut recorder = match testing::build(builder) {
+			Some(recorder) => recorder,
+			None => return,
+		};
+		recorder.record(true);
+
+		let settings = MonitorSettings { sink: MonitorSink::Fake, ..MonitorSettings::default() };
+		recorder.start_monitor(&settings).unwrap();
+		let branch = recorder.monitor.as_ref().unwrap().0.branch().clone();
+		let buffers = Arc::new(AtomicUsize::new(0));
+		let counted = buffers.clone();
+		branch.static_pad("sink").unwrap().add_probe(gst::PadProbeType::BUFFER, move |_, _| {
+			counted.fetch_add(1, Ordering::SeqCst);
+			gst::PadProbeReturn::Ok
+		});
+		thread::sleep(Duration::from_millis(500));
+		assert!(buffers.load(Ordering::SeqCst) > 0, "no audio reached the monitor");
+
+		recorder.stop_monitor();
+		thread::sleep(Duration::from_millis(500));
+		assert!(recorder.monitor.is_none());
+		assert!(branch.parent().is_none(), "monitor branch is still in the pipeline");
+		recorder.finish(Duration::from_secs(5)).expect("recording stalled after detaching");
+	}
+}
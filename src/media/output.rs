@@ -0,0 +1,413 @@
+// Network outputs attached to the encoded camera stream.
+
+// {encoded tee} - {queue} - {h264parse} - {mpegtsmux} - {srtsink}
+// {encoded tee} - {queue} - {h264parse} - {rtph264pay} - {udpsink}
+// {encoded tee} - {custom fragment}
+
+// Outputs can be attached and detached while recording. Each one reports its
+// health through a callback: it is streaming once buffers reach the network
+// sink (or, for SRT listeners, once a caller connected) and failed when the bus
+// reports an error from inside its bin.
+use std::{
+	fmt,
+	net::{Ipv4Addr, Ipv6Addr},
+	sync::{
+		atomic::{AtomicBool, AtomicUsize, Ordering},
+		Arc,
+	},
+};
+
+use gst::prelude::*;
+use gstreamer as gst;
+use url::Url;
+
+use crate::media::{error::VideoError, launch};
+
+/// Tee after the video encoder, shared by the recording and the network
+/// outputs.
+///
+/// The raw audio tee is driven through the same handle to attach the audio
+/// monitor.
+#[derive(Debug, Clone)]
+pub struct EncodedTee {
+	pipeline: gst::Pipeline,
+	tee: gst::Element,
+}
+
+/// Output branch linked to an [`EncodedTee`].
+#[derive(Debug)]
+pub struct EncodedBranch {
+	/// Bin of the output.
+	pub bin: gst::Element,
+	/// Tee pad feeding the bin.
+	pub tee_pad: gst::Pad,
+}
+
+impl EncodedTee {
+	/// Handle on `tee`, which must be a `tee` element inside `pipeline`.
+	pub fn new(pipeline: &gst::Pipeline, tee: &gst::Element) -> Self {
+		Self { pipeline: pipeline.clone(), tee: tee.clone() }
+	}
+
+	/// Link `bin` to the encoded video stream.
+	///
+	/// The bin must expose a `sink` pad accepting H.264. It shares the camera and
+	/// the encoder with the preview and the recording instead of opening the
+	/// device again.
+	pub fn attach(&self, bin: &gst::Element) -> Result<EncodedBranch, VideoError> {
+		self.pipeline.add(bin)?;
+		let tee_pad = match self.tee.request_pad_simple("src_%u") {
+			Some(pad) => pad,
+			None => {
+				let _ = self.pipeline.remove(bin);
+				return Err(VideoError::RequestPad(self.tee.name().to_string()));
+			}
+		};
+		// Leave the pipeline as it was on any failure below.
+		let rollback = || {
+			if let Some(peer) = tee_pad.peer() {
+				let _ = tee_pad.unlink(&peer);
+			}
+			self.tee.release_request_pad(&tee_pad);
+			let _ = bin.set_state(gst::State::Null);
+			let _ = self.pipeline.remove(bin);
+		};
+		let sink_pad = match bin.static_pad("sink") {
+			Some(pad) => pad,
+			None => {
+				rollback();
+				return Err(VideoError::Caps);
+			}
+		};
+		if let Err(err) = launch::link_checked(&tee_pad, &sink_pad) {
+			rollback();
+			return Err(err);
+		}
+		if let Err(err) = bin.sync_state_with_parent() {
+			rollback();
+			return Err(err.into());
+		}
+		Ok(EncodedBranch { bin: bin.clone(), tee_pad })
+	}
+
+	/// Unlink and dispose a branch attached with [`attach`](Self::attach).
+	///
+	/// The tee pad is only released once no buffer is in flight, so this is safe
+	/// to call while recording.
+	pub fn detach(&self, branch: EncodedBranch) {
+		let EncodedBranch { bin, tee_pad } = branch;
+		let pipeline = self.pipeline.clone();
+		let tee = self.tee.clone();
+		tee_pad.add_probe(gst::PadProbeType::IDLE, move |pad, _| {
+			if let Some(peer) = pad.peer() {
+				let _ = pad.unlink(&peer);
+			}
+			tee.release_request_pad(pad);
+			let _ = bin.set_state(gst::State::Null);
+			let _ = pipeline.remove(&bin);
+			gst::PadProbeReturn::Remove
+		});
+	}
+}
+
+/// Connection mode of an SRT output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SrtMode {
+	/// Connect to a remote listener.
+	Caller,
+	/// Wait for remote callers on a local port.
+	Listener,
+}
+
+/// Destination of a network output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StreamTarget {
+	/// MPEG-TS over SRT.
+	Srt {
+		/// Caller or listener.
+		mode: SrtMode,
+		/// Remote host in caller mode, ignored by listeners.
+		host: String,
+		/// Remote port in caller mode, local port in listener mode.
+		port: u16,
+		/// Encryption passphrase, encryption is disabled when empty.
+		passphrase: String,
+		/// SRT latency in milliseconds.
+		latency: u32,
+	},
+	/// H.264 RTP over UDP, to a unicast host or a multicast group.
+	Rtp {
+		/// Destination address.
+		host: String,
+		/// Destination port.
+		port: u16,
+		/// Time to live of multicast packets.
+		ttl: u32,
+	},
+	/// gst-launch fragment with a single unlinked sink pad accepting H.264,
+	/// e.g. `h264parse ! flvmux ! rtmpsink location=...`.
+	Custom {
+		/// Pipeline description.
+		description: String,
+	},
+}
+
+impl fmt::Display for StreamTarget {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			StreamTarget::Srt { mode: SrtMode::Caller, host, port, .. } => {
+				write!(f, "srt://{}:{}", host, port)
+			}
+			StreamTarget::Srt { mode: SrtMode::Listener, port, .. } => {
+				write!(f, "srt://:{} (listener)", port)
+			}
+			StreamTarget::Rtp { host, port, .. } => write!(f, "rtp://{}:{}", host, port),
+			StreamTarget::Custom { description } => write!(f, "gst:{}", description),
+		}
+	}
+}
+
+impl StreamTarget {
+	/// Check settings GStreamer would only reject once streaming.
+	pub fn validate(&self) -> Result<(), VideoError> {
+		match self {
+			StreamTarget::Srt { passphrase, .. }
+				if !passphrase.is_empty() && !(10..=79).contains(&passphrase.len()) =>
+			{
+				Err(VideoError::Config(
+					"SRT passphrase must be 10 to 79 characters long".to_string(),
+				))
+			}
+			StreamTarget::Srt { mode: SrtMode::Caller, host, .. } | StreamTarget::Rtp { host, .. }
+				if host.is_empty() =>
+			{
+				Err(VideoError::Config("missing destination host".to_string()))
+			}
+			StreamTarget::Custom { description } if description.trim().is_empty() => {
+				Err(VideoError::Config("missing pipeline description".to_string()))
+			}
+			_ => Ok(()),
+		}
+	}
+
+	/// Pipeline description of the output; user supplied values are set by
+	/// [`configure`](Self::configure) instead of being pasted in.
+	fn description(&self) -> String {
+		match self {
+			StreamTarget::Srt { .. } => {
+				"queue leaky=downstream ! h264parse config-interval=-1 ! mpegtsmux alignment=7 ! \
+				 srtsink name=sink wait-for-connection=false sync=false async=false"
+					.to_string()
+			}
+			StreamTarget::Rtp { .. } => {
+				"queue leaky=downstream ! h264parse ! rtph264pay config-interval=-1 pt=96 ! \
+				 udpsink name=sink auto-multicast=true sync=false async=false"
+					.to_string()
+			}
+			StreamTarget::Custom { description } => description.clone(),
+		}
+	}
+
+	/// Set the user supplied values on the sink of `bin`.
+	fn configure(&self, bin: &gst::Bin) -> Result<(), VideoError> {
+		let sink = || bin.by_name("sink").ok_or(VideoError::Cast);
+		match self {
+			StreamTarget::Srt { mode, host, port, passphrase, latency } => {
+				let sink = sink()?;
+				sink.set_property("uri", srt_uri(*mode, host, *port)?);
+				sink.set_property("latency", i32::try_from(*latency).unwrap_or(i32::MAX));
+				if !passphrase.is_empty() {
+					sink.set_property("passphrase", passphrase);
+				}
+			}
+			StreamTarget::Rtp { host, port, ttl } => {
+				let sink = sink()?;
+				sink.set_property("host", host);
+				sink.set_property("port", i32::from(*port));
+				sink.set_property("ttl-mc", i32::try_from(*ttl).unwrap_or(i32::MAX));
+			}
+			StreamTarget::Custom { .. } => {}
+		}
+		Ok(())
+	}
+
+	fn is_multicast(&self) -> bool {
+		match self {
+			StreamTarget::Rtp { host, .. } => {
+				host.parse::<Ipv4Addr>().map_or(false, |addr| addr.is_multicast())
+			}
+			_ => false,
+		}
+	}
+}
+
+/// URI of an SRT sink; the host goes through [`Url`] so it cannot add query
+/// parameters like a passphrase.
+fn srt_uri(mode: SrtMode, host: &str, port: u16) -> Result<String, VideoError> {
+	match mode {
+		SrtMode::Caller => {
+			let invalid = || VideoError::Config(format!("invalid destination host {}", host));
+			let mut uri = Url::parse("srt://localhost").map_err(|_| VideoError::Uri)?;
+			let host = match host.parse::<Ipv6Addr>() {
+				Ok(_) => format!("[{}]", host),
+				Err(_) => host.to_string(),
+			};
+			uri.set_host(Some(&host)).map_err(|_| invalid())?;
+			uri.set_port(Some(port)).map_err(|_| invalid())?;
+			uri.query_pairs_mut().append_pair("mode", "caller");
+			Ok(uri.to_string())
+		}
+		SrtMode::Listener => Ok(format!("srt://:{}?mode=listener", port)),
+	}
+}
+
+/// Health of a network output as shown to the user.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OutputHealth {
+	/// Attached, no data went out yet.
+	Starting,
+	/// SRT listener without a connected caller.
+	Waiting,
+	/// Data is being sent.
+	Streaming,
+	/// The output failed and stopped sending.
+	Failed(String),
+}
+
+impl fmt::Display for OutputHealth {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			OutputHealth::Starting => write!(f, "starting"),
+			OutputHealth::Waiting => write!(f, "waiting for caller"),
+			OutputHealth::Streaming => write!(f, "streaming"),
+			OutputHealth::Failed(err) => write!(f, "failed: {}", err),
+		}
+	}
+}
+
+/// Callback receiving health changes of an output.
+pub type HealthCallback = Arc<dyn Fn(OutputHealth) + Send + Sync>;
+
+/// Output bin for a [`StreamTarget`], ready to be linked to the encoded stream.
+pub struct StreamOutput {
+	/// Identifier the UI refers to the output by.
+	pub id: u32,
+	/// Where the output sends to.
+	pub target: StreamTarget,
+	branch: gst::Element,
+	on_health: HealthCallback,
+}
+
+impl fmt::Debug for StreamOutput {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("StreamOutput")
+			.field("id", &self.id)
+			.field("target", &self.target)
+			.field("branch", &self.branch)
+			.finish()
+	}
+}
+
+impl StreamOutput {
+	/// Build the output bin for `target`, reporting health to `on_health`.
+	pub fn new(id: u32, target: &StreamTarget, on_health: HealthCallback) -> Result<Self, VideoError> {
+		gst::init()?;
+		target.validate()?;
+		let branch = launch::parse_bin(&target.description(), &format!("output-{}", id), &["sink"])?;
+		target.configure(&branch)?;
+		if target.is_multicast() {
+			log::info!("sending RTP to multicast group {}", target);
+		}
+
+		match target {
+			StreamTarget::Srt { mode: SrtMode::Listener, .. } => {
+				let sink = branch.by_name("sink").ok_or(VideoError::Cast)?;
+				// Several callers may be connected, wait again only once all left.
+				let callers = Arc::new(AtomicUsize::new(0));
+				let added = on_health.clone();
+				let added_callers = callers.clone();
+				sink.connect("caller-added", false, move |_| {
+					added_callers.fetch_add(1, Ordering::Relaxed);
+					added(OutputHealth::Streaming);
+					None
+				});
+				let removed = on_health.clone();
+				sink.connect("caller-removed", false, move |_| {
+					if callers.fetch_sub(1, Ordering::Relaxed) == 1 {
+						removed(OutputHealth::Waiting);
+					}
+					None
+				});
+				on_health(OutputHealth::Waiting);
+			}
+			_ => {
+				// Custom fragments need not name their sink, their input is watched
+				// instead.
+				let sink_pad = match branch.by_name("sink") {
+					Some(sink) => sink.static_pad("sink"),
+					None => branch.static_pad("sink"),
+				}
+				.ok_or(VideoError::Caps)?;
+				let streaming = on_health.clone();
+				let reported = AtomicBool::new(false);
+				sink_pad.add_probe(gst::PadProbeType::BUFFER, move |_, _| {
+					if !reported.swap(true, Ordering::Relaxed) {
+						streaming(OutputHealth::Streaming);
+					}
+					gst::PadProbeReturn::Remove
+				});
+				on_health(OutputHealth::Starting);
+			}
+		}
+
+		Ok(StreamOutput { id, target: target.clone(), branch: branch.upcast(), on_health })
+	}
+
+	/// Branch to link to the encoded video stream.
+	pub fn branch(&self) -> &gst::Element {
+		&self.branch
+	}
+
+	/// Whether `object` is, or is inside, this output.
+	pub fn owns(&self, object: &gst::Object) -> bool {
+		object == self.branch.upcast_ref::<gst::Object>() || object.has_as_ancestor(&self.branch)
+	}
+
+	/// Report a failure raised on the bus.
+	pub fn fail(&self, error: &glib::Error) {
+		(self.on_health)(OutputHealth::Failed(error.to_string()));
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::media::testing;
+
+	#[test]
+	fn builds_srt_uri() {
+		let uri = srt_uri(SrtMode::Caller, "example.com", 9000).unwrap();
+		assert_eq!(uri, "srt://example.com:9000?mode=caller");
+		let uri = srt_uri(SrtMode::Caller, "::1", 9000).unwrap();
+		assert_eq!(uri, "srt://[::1]:9000?mode=caller");
+		assert_eq!(srt_uri(SrtMode::Listener, "", 9000).unwrap(), "srt://:9000?mode=listener");
+		assert!(srt_uri(SrtMode::Caller, "host?passphrase=0123456789", 9000).is_err());
+		assert!(srt_uri(SrtMode::Caller, "host latency=0", 9000).is_err());
+	}
+
+	#[test]
+	fn sets_rtp_host_as_property() {
+		if !testing::has_elements(&["queue", "h264parse", "rtph264pay", "udpsink"]) {
+			return;
+		}
+		let host = "127.0.0.1 port=1 ! fakesink".to_string();
+		let target = StreamTarget::Rtp { host: host.clone(), port: 5004, ttl: 4 };
+		let bin = launch::parse_bin(&target.description(), "output", &["sink"]).unwrap();
+		target.configure(&bin).unwrap();
+		let sink = bin.by_name("sink").unwrap();
+		assert_eq!(sink.property::<String>("host"), host);
+		assert_eq!(sink.property::<i32>("port"), 5004);
+		// queue, h264parse, rtph264pay and udpsink, nothing injected.
+		assert_eq!(bin.children().len(), 4);
+	}
+}
@@ -0,0 +1,270 @@
+// Playback of recordings.
+
+// {playbin} - ... - {audio filter: {audioconvert} - [{ebur128level}] - [{level}] - [{spectrum}]} - {audio sink}
+
+// `playbin` does the demuxing and decoding; its audio filter measures the
+// decoded audio with the same elements and callbacks as the capture branch.
+// Its `av-offset` shifts audio against video for files recorded out of sync.
+use std::{path::Path, thread, time::Duration};
+
+use gst::prelude::*;
+use gstreamer as gst;
+
+use crate::media::{
+	error::VideoError,
+	level::{make_level, AudioMeters},
+	loudness::make_loudness,
+	sync::AvOffset,
+};
+
+/// Position in the media.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Position {
+	/// Position based on time.
+	///
+	/// Not the most accurate format for videos.
+	Time(std::time::Duration),
+	/// Position based on nth frame.
+	Frame(u64),
+}
+
+impl From<Position> for gst::GenericFormattedValue {
+	fn from(pos: Position) -> Self {
+		match pos {
+			Position::Time(t) => gst::ClockTime::from_nseconds(t.as_nanos() as _).into(),
+			Position::Frame(f) => gst::format::Default(f).into(),
+		}
+	}
+}
+
+impl From<std::time::Duration> for Position {
+	fn from(t: std::time::Duration) -> Self {
+		Position::Time(t)
+	}
+}
+
+impl From<u64> for Position {
+	fn from(f: u64) -> Self {
+		Position::Frame(f)
+	}
+}
+
+/// Player of a recorded file.
+#[derive(Debug)]
+pub struct Player {
+	/// The `playbin` element.
+	pub playbin: gst::Element,
+	/// Measurements of the decoded audio.
+	pub meters: AudioMeters,
+}
+
+impl Drop for Player {
+	fn drop(&mut self) {
+		if self.playbin.set_state(gst::State::Null).is_err() {
+			log::warn!("failed to stop playback");
+		}
+	}
+}
+
+impl Player {
+	/// Open the file at `path`, paused on its first frame, measuring its audio
+	/// with `meters`.
+	pub fn new(path: &Path, meters: AudioMeters) -> Result<Self, VideoError> {
+		gst::init()?;
+		let uri = glib::filename_to_uri(path.canonicalize()?, None)?;
+		let playbin = gst::ElementFactory::make("playbin", Some("player"))
+			.map_err(|_| VideoError::MissingElement("playbin".to_string()))?;
+		playbin.set_property("uri", uri.as_str());
+		playbin.set_property("audio-filter", &audio_filter(&meters)?);
+		playbin.set_state(gst::State::Paused)?;
+		let bus = playbin.bus().expect("Pipeline without bus. Shouldn't happen!");
+		watch_bus(bus, playbin.downgrade(), meters.clone());
+		Ok(Self { playbin, meters })
+	}
+
+	/// Start or continue playing.
+	pub fn play(&self) -> Result<(), VideoError> {
+		self.playbin.set_state(gst::State::Playing)?;
+		Ok(())
+	}
+
+	/// Pause on the current frame.
+	pub fn pause(&self) -> Result<(), VideoError> {
+		self.playbin.set_state(gst::State::Paused)?;
+		Ok(())
+	}
+
+	/// Jump to `position`.
+	pub fn seek(&self, position: impl Into<Position>) -> Result<(), VideoError> {
+		let position: gst::GenericFormattedValue = position.into().into();
+		self.playbin.seek_simple(gst::SeekFlags::FLUSH | gst::SeekFlags::ACCURATE, position)?;
+		Ok(())
+	}
+
+	/// Time played so far.
+	pub fn position(&self) -> Result<Duration, VideoError> {
+		let position = self.playbin.query_position::<gst::ClockTime>();
+		position.map(|t| Duration::from_nanos(t.nseconds())).ok_or(VideoError::Duration)
+	}
+
+	/// Play audio `offset` later than video, or earlier when negative.
+	pub fn set_av_offset(&self, offset: AvOffset) -> Result<(), VideoError> {
+		offset.validate()?;
+		self.playbin.set_property("av-offset", offset.nanos());
+		Ok(())
+	}
+
+	/// Length of the file.
+	pub fn duration(&self) -> Result<Duration, VideoError> {
+		let duration = self.playbin.query_duration::<gst::ClockTime>();
+		duration.map(|t| Duration::from_nanos(t.nseconds())).ok_or(VideoError::Duration)
+	}
+}
+
+/// Bin measuring the decoded audio with the elements `meters` needs.
+fn audio_filter(meters: &AudioMeters) -> Result<gst::Bin, VideoError> {
+	let interval = Duration::from_millis(50);
+	let convert = gst::ElementFactory::make("audioconvert", Some("player-audio-converter"))
+		.map_err(|_| VideoError::MissingElement("audioconvert".to_string()))?;
+	let mut chain = vec![convert];
+	if meters.loudness.is_some() {
+		chain.push(make_loudness("player-audio-loudness", Duration::from_millis(100))?);
+	}
+	if meters.level.is_some() {
+		chain.push(make_level("player-audio-level", interval)?);
+	}
+	if let Some((analyzer, _)) = &meters.spectrum {
+		chain.push(analyzer.make_element("player-audio-spectrum", interval)?);
+	}
+	let chain: Vec<&gst::Element> = chain.iter().collect();
+	let bin = gst::Bin::new(Some("player-audio-filter"));
+	bin.add_many(&chain)?;
+	gst::Element::link_many(&chain)?;
+	let first = chain[0].static_pad("sink").ok_or(VideoError::Caps)?;
+	let last = chain[chain.len() - 1].static_pad("src").ok_or(VideoError::Caps)?;
+	bin.add_pad(&gst::GhostPad::with_target(Some("sink"), &first)?)?;
+	bin.add_pad(&gst::GhostPad::with_target(Some("src"), &last)?)?;
+	Ok(bin)
+}
+
+/// Pass measurements to `meters` and log errors until the player is dropped.
+fn watch_bus(bus: gst::Bus, playbin: glib::WeakRef<gst::Element>, meters: AudioMeters) {
+	thread::spawn(move || {
+		while playbin.upgrade().is_some() {
+			let msg = match bus.timed_pop(gst::ClockTime::from_mseconds(100)) {
+				Some(msg) => msg,
+				None => continue,
+			};
+			if meters.dispatch(&msg) {
+				continue;
+			}
+			match msg.view() {
+				gst::MessageView::Error(err) => {
+					log::error!("playback failed: {} ({:?})", err.error(), err.debug());
+				}
+				gst::MessageView::Eos(..) => log::debug!("playback reached the end"),
+				_ => {}
+			}
+		}
+	});
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::{Arc, Mutex};
+
+	use super::*;
+	use crate::media::{
+		audio::AudioSource,
+		builder::PreviewSink,
+		level::AudioLevel,
+		recorder::Recorder,
+		recording::RecordingOutput,
+		source::CaptureSource,
+		spectrum::{Spectrum, SpectrumAnalyzer, SpectrumSettings},
+		testing::{self, TempPath},
+	};
+
+	/// Two seconds of test pattern and tone, `None` if a plugin is missing.
+	fn record(name: &str) -> Option<TempPath> {
+		let path = testing::temp_path(name);
+		let builder = Recorder::builder()
+			.source(CaptureSource::Test)
+			.resolution(320, 240)
+			.audio_source(AudioSource::Test)
+			.output(RecordingOutput::File(path.to_path_buf()))
+			.preview_sink(PreviewSink::Fake);
+		testing::record(&testing::build(builder)?, Duration::from_secs(2));
+		Some(path)
+	}
+
+	/// Play into fake sinks, so neither a screen nor a sound card is needed.
+	fn play_silently(player: &Player) {
+		player.playbin.set_state(gst::State::Null).unwrap();
+		for property in ["video-sink", "audio-sink"] {
+			let sink = gst::ElementFactory::make("fakesink", None).unwrap();
+			player.playbin.set_property(property, &sink);
+		}
+		player.play().unwrap();
+	}
+
+	#[test]
+	fn plays_and_measures_audio() {
+		if !testing::has_elements(&["playbin", "fakesink", "level", "spectrum"]) {
+			return;
+		}
+		let path = match record("played.mkv") {
+			Some(path) => path,
+			None => return,
+		};
+		let levels: Arc<Mutex<Vec<AudioLevel>>> = Default::default();
+		let spectra: Arc<Mutex<Vec<Spectrum>>> = Default::default();
+		let level_sink = levels.clone();
+		let on_level = move |level: &AudioLevel| level_sink.lock().unwrap().push(level.clone());
+		let spectrum_sink = spectra.clone();
+		let on_spectrum =
+			move |spectrum: &Spectrum| spectrum_sink.lock().unwrap().push(spectrum.clone());
+		let meters = AudioMeters {
+			level: Some(Arc::new(on_level)),
+			spectrum: Some((
+				SpectrumAnalyzer::new(SpectrumSettings::default()).unwrap(),
+				Arc::new(on_spectrum),
+			)),
+			loudness: None,
+		};
+		let player = Player::new(&path, meters).unwrap();
+		play_silently(&player);
+		thread::sleep(Duration::from_secs(1));
+
+		let duration = player.duration().unwrap();
+		assert!(duration > Duration::from_millis(1500), "duration {:?}", duration);
+		assert!(player.position().unwrap() > Duration::ZERO);
+		let levels = levels.lock().unwrap();
+		let peaks = levels.iter().flat_map(|level| level.peak.iter().copied());
+		let peak = peaks.fold(f64::MIN, f64::max);
+		// audiotestsrc plays a sine at 80 % of full scale.
+		assert!(peak > -6.0, "peak of {} dB", peak);
+		let spectra = spectra.lock().unwrap();
+		assert!(!spectra.is_empty(), "no spectrum of the played audio");
+		assert!(spectra.iter().any(|spectrum| spectrum
+			.magnitudes
+			.iter()
+			.any(|&magnitude| magnitude > spectrum.threshold as f32 + 10.0)));
+	}
+
+	#[test]
+	fn sets_av_offset() {
+		if !testing::has_elements(&["playbin"]) {
+			return;
+		}
+		let path = match record("offset.mkv") {
+			Some(path) => path,
+			None => return,
+		};
+		let player = Player::new(&path, AudioMeters::default()).unwrap();
+		player.set_av_offset(AvOffset(-150)).unwrap();
+		assert_eq!(player.playbin.property::<i64>("av-offset"), -150_000_000);
+		assert!(player.set_av_offset(AvOffset(10_000)).is_err());
+		assert_eq!(player.playbin.property::<i64>("av-offset"), -150_000_000);
+	}
+}
@@ -0,0 +1,217 @@
+// Dynamics processing of every audio input.
+
+// ... - {volume} - {noise gate} - {compressor} - {limiter} - [{audiopanorama}] - ...
+
+// Gate, compressor and limiter are `audiodynamic` elements, an expander and two
+// compressors. All three are always in the chain and a stage that is switched
+// off runs at a ratio of 1, which passes audio through untouched, so stages can
+// be switched while recording without relinking anything. Microphones differ a
+// lot in output level and noise floor, so the gain and processing of every
+// microphone can be saved as a preset and are picked up again whenever that
+// microphone is used.
+use std::{
+	collections::BTreeMap,
+	path::{Path, PathBuf},
+};
+
+use gst::prelude::*;
+use gstreamer as gst;
+use serde::{Deserialize, Serialize};
+
+use crate::media::{audio::AudioInput, builder::indexed, error::VideoError, store};
+
+/// Name of the noise gate of the first input.
+pub const GATE: &str = "desktop-audio-gate";
+/// Name of the compressor of the first input.
+pub const COMPRESSOR: &str = "desktop-audio-compressor";
+/// Name of the limiter of the first input.
+pub const LIMITER: &str = "desktop-audio-limiter";
+
+/// Expander silencing everything below the threshold, like breathing and fan
+/// noise between words.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct NoiseGate {
+	/// Whether the gate acts; off it lets everything through.
+	pub enabled: bool,
+	/// Level below which audio is attenuated, in dBFS.
+	pub threshold_db: f64,
+	/// How steeply audio below the threshold falls off, 1.0 for not at all.
+	pub ratio: f64,
+}
+
+impl Default for NoiseGate {
+	fn default() -> Self {
+		Self { enabled: false, threshold_db: -50.0, ratio: 4.0 }
+	}
+}
+
+/// Compressor evening out loud and quiet passages.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Compressor {
+	/// Whether the compressor acts.
+	pub enabled: bool,
+	/// Level above which audio is compressed, in dBFS.
+	pub threshold_db: f64,
+	/// Compression above the threshold, 4.0 for 4:1.
+	pub ratio: f64,
+}
+
+impl Default for Compressor {
+	fn default() -> Self {
+		Self { enabled: false, threshold_db: -20.0, ratio: 4.0 }
+	}
+}
+
+/// Limiter keeping peaks below a ceiling.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Limiter {
+	/// Whether the limiter acts.
+	pub enabled: bool,
+	/// Highest level let through, in dBFS.
+	pub ceiling_db: f64,
+}
+
+impl Default for Limiter {
+	fn default() -> Self {
+		Self { enabled: false, ceiling_db: -1.0 }
+	}
+}
+
+/// Dynamics processing of one input, every stage off by default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct AudioProcessing {
+	/// First stage, right after the input gain.
+	pub gate: NoiseGate,
+	/// Second stage.
+	pub compressor: Compressor,
+	/// Last stage, catching the peaks the compressor lets through.
+	pub limiter: Limiter,
+}
+
+impl AudioProcessing {
+	/// Reject thresholds and ratios `audiodynamic` cannot apply.
+	pub fn validate(&self) -> Result<(), VideoError> {
+		check_range("noise gate threshold", self.gate.threshold_db, -90.0, 0.0)?;
+		check_range("noise gate ratio", self.gate.ratio, 1.0, 20.0)?;
+		check_range("compressor threshold", self.compressor.threshold_db, -60.0, 0.0)?;
+		check_range("compressor ratio", self.compressor.ratio, 1.0, 20.0)?;
+		check_range("limiter ceiling", self.limiter.ceiling_db, -20.0, 0.0)
+	}
+
+	/// Gate, compressor and limiter of input `i`, in chain order.
+	pub(crate) fn make_elements(i: usize) -> Result<[gst::Element; 3], VideoError> {
+		let make = |base: &str| {
+			gst::ElementFactory::make("audiodynamic", Some(&indexed(base, i)))
+				.map_err(|_| VideoError::MissingElement("audiodynamic".to_string()))
+		};
+		Ok([make(GATE)?, make(COMPRESSOR)?, make(LIMITER)?])
+	}
+
+	/// Configure the elements of input `i` in `bin`, while recording or not.
+	pub fn apply(&self, bin: &gst::Bin, i: usize) -> Result<(), VideoError> {
+		let element = |base: &str| {
+			bin.by_name(&indexed(base, i))
+				.ok_or_else(|| VideoError::Config(format!("no audio processing for input {}", i)))
+		};
+		let gate = element(GATE)?;
+		gate.set_property_from_str("mode", "expander");
+		gate.set_property_from_str("characteristics", "soft-knee");
+		gate.set_property("threshold", amplitude(self.gate.threshold_db));
+		gate.set_property("ratio", if self.gate.enabled { self.gate.ratio as f32 } else { 1.0 });
+
+		let compressor = element(COMPRESSOR)?;
+		compressor.set_property_from_str("mode", "compressor");
+		compressor.set_property_from_str("characteristics", "soft-knee");
+		compressor.set_property("threshold", amplitude(self.compressor.threshold_db));
+		// `audiodynamic` takes the slope above the threshold, the inverse of the
+		// usual ratio.
+		let ratio = if self.compressor.enabled { 1.0 / self.compressor.ratio as f32 } else { 1.0 };
+		compressor.set_property("ratio", ratio);
+
+		let limiter = element(LIMITER)?;
+		limiter.set_property_from_str("mode", "compressor");
+		limiter.set_property_from_str("characteristics", "hard-knee");
+		limiter.set_property("threshold", amplitude(self.limiter.ceiling_db));
+		limiter.set_property("ratio", if self.limiter.enabled { 0.0f32 } else { 1.0 });
+		Ok(())
+	}
+}
+
+/// Gain and processing saved for a microphone.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MicPreset {
+	/// Amplification in dB, see [`AudioInput::gain_db`].
+	pub gain_db: f64,
+	/// Dynamics processing after the gain.
+	pub processing: AudioProcessing,
+}
+
+/// Presets by microphone, stored as JSON.
+///
+/// Microphones are told apart by source and device, as in `alsa:hw:1,0`, so
+/// the default device of a source shares one preset.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProcessingPresets {
+	microphones: BTreeMap<String, MicPreset>,
+}
+
+impl ProcessingPresets {
+	/// `audio-presets.json` in the user's configuration directory.
+	pub fn default_path() -> Option<PathBuf> {
+		store::config_path("audio-presets.json")
+	}
+
+	/// Presets saved at `path`, none if the file does not exist yet.
+	pub fn load(path: &Path) -> Result<Self, VideoError> {
+		store::load(path)
+	}
+
+	/// Write the presets to `path`, creating its directory.
+	pub fn save(&self, path: &Path) -> Result<(), VideoError> {
+		store::save(path, self)
+	}
+
+	/// Preset saved for the microphone of `input`.
+	pub fn get(&self, input: &AudioInput) -> Option<&MicPreset> {
+		self.microphones.get(&input.to_string())
+	}
+
+	/// Remember the gain and processing of `input` for its microphone.
+	pub fn store(&mut self, input: &AudioInput) {
+		let preset = MicPreset { gain_db: input.gain_db, processing: input.processing };
+		self.microphones.insert(input.to_string(), preset);
+	}
+
+	/// Forget the preset of the microphone of `input`.
+	pub fn remove(&mut self, input: &AudioInput) -> Option<MicPreset> {
+		self.microphones.remove(&input.to_string())
+	}
+
+	/// Give `input` the gain and processing saved for its microphone. Returns
+	/// whether there was a preset.
+	pub fn apply_to(&self, input: &mut AudioInput) -> bool {
+		match self.get(input) {
+			Some(preset) => {
+				input.gain_db = preset.gain_db;
+				input.processing = preset.processing;
+				true
+			}
+			None => false,
+		}
+	}
+}
+
+/// Linear amplitude of `db` dBFS, as `audiodynamic` takes its threshold.
+fn amplitude(db: f64) -> f32 {
+	10f64.powf(db / 20.0) as f32
+}
+
+fn check_range(what: &str, value: f64, min: f64, max: f64) -> Result<(), VideoError> {
+	if !(min..=max).contains(&value) {
+		return Err(VideoError::Config(format!(
+			"{} of {} is not between {} and {}",
+			what, value, min, max
+		)));
+	}
+	Ok(())
+}
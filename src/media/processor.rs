@@ -0,0 +1,162 @@
+// Rust code modifying every camera frame.
+
+// {source} - {videoconvert} - {caps} - {appsink} ~ processors ~ {appsrc} - {video tee} - ...
+
+// Code outside GStreamer can only touch frames between an appsink and an
+// appsrc. Frames leave the pipeline as RGBA, run through the processors in
+// order and come back with their timestamps untouched, ahead of the tee, so
+// the preview and the encoder both get the processed frames. Processors can
+// be added and removed while running; a recorder built without a chain links
+// the camera straight to the tee and copies nothing.
+use std::{
+	fmt,
+	sync::{Arc, Mutex},
+	time::Duration,
+};
+
+use gst::prelude::*;
+use gstreamer as gst;
+use gstreamer::{Caps, Element, Pipeline};
+use gstreamer_app as gst_app;
+
+use crate::media::error::VideoError;
+
+/// RGBA frame a processor may change in place.
+#[derive(Debug)]
+pub struct FrameMut<'a> {
+	/// Pixel data, `width * height * 4` bytes.
+	pub data: &'a mut [u8],
+	/// Width in pixels.
+	pub width: u32,
+	/// Height in pixels.
+	pub height: u32,
+	/// Presentation time since the start of the stream.
+	pub timestamp: Duration,
+}
+
+/// Step of a [`ProcessorChain`], called with every frame on the streaming
+/// thread; slow processors hold up the whole video branch.
+///
+/// Closures taking a [`FrameMut`] are processors too.
+pub trait FrameProcessor: Send {
+	/// Change, draw over or just look at `frame`.
+	fn process(&mut self, frame: &mut FrameMut<'_>);
+}
+
+impl<F: FnMut(&mut FrameMut<'_>) + Send> FrameProcessor for F {
+	fn process(&mut self, frame: &mut FrameMut<'_>) {
+		self(frame)
+	}
+}
+
+/// Processors run in order on every frame, shared between the pipeline and
+/// the code registering them.
+#[derive(Clone, Default)]
+pub struct ProcessorChain {
+	processors: Arc<Mutex<Vec<Box<dyn FrameProcessor>>>>,
+}
+
+impl fmt::Debug for ProcessorChain {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("ProcessorChain").field("len", &self.len()).finish()
+	}
+}
+
+impl ProcessorChain {
+	/// Chain without processors, passing frames through unchanged.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Run `processor` after the ones already registered.
+	pub fn push(&self, processor: impl FrameProcessor + 'static) {
+		self.processors.lock().unwrap().push(Box::new(processor));
+	}
+
+	/// Remove every processor.
+	pub fn clear(&self) {
+		self.processors.lock().unwrap().clear();
+	}
+
+	/// Number of registered processors.
+	pub fn len(&self) -> usize {
+		self.processors.lock().unwrap().len()
+	}
+
+	/// Whether no processor is registered.
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	/// Run every processor on `frame`.
+	pub fn process(&self, frame: &mut FrameMut<'_>) {
+		for processor in self.processors.lock().unwrap().iter_mut() {
+			processor.process(frame);
+		}
+	}
+
+	/// Add the round trip through the chain to `pipeline`, feeding
+	/// `downstream`. Returns the element to link the camera to.
+	pub(crate) fn insert(
+		&self,
+		pipeline: &Pipeline,
+		downstream: &Element,
+	) -> Result<Element, VideoError> {
+		let make = |factory: &str, name: &str| {
+			gst::ElementFactory::make(factory, Some(name))
+				.map_err(|_| VideoError::MissingElement(factory.to_string()))
+		};
+		let convert = make("videoconvert", "frame-processor-converter")?;
+		let caps = make("capsfilter", "frame-processor-caps")?;
+		caps.set_property("caps", &Caps::builder("video/x-raw").field("format", "RGBA").build());
+		let sink = make("appsink", "frame-processor-sink")?;
+		let src = make("appsrc", "frame-processor-src")?;
+		pipeline.add_many(&[&convert, &caps, &sink, &src])?;
+		Element::link_many(&[&convert, &caps, &sink])?;
+		src.link(downstream)?;
+
+		let appsrc = src.dynamic_cast::<gst_app::AppSrc>().map_err(|_| VideoError::Cast)?;
+		appsrc.set_format(gst::Format::Time);
+		appsrc.set_is_live(true);
+		appsrc.set_do_timestamp(false);
+		// The sinks after the appsrc sync, waiting here would only add latency.
+		sink.set_property("sync", false);
+		let appsink = sink.dynamic_cast::<gst_app::AppSink>().map_err(|_| VideoError::Cast)?;
+		let eos_src = appsrc.clone();
+		let chain = self.clone();
+		appsink.set_callbacks(
+			gst_app::AppSinkCallbacks::builder()
+				.new_sample(move |sink| {
+					let sample = sink.pull_sample().map_err(|_| gst::FlowError::Eos)?;
+					let caps = sample.caps_owned().ok_or(gst::FlowError::NotNegotiated)?;
+					let mut buffer = sample.buffer_owned().ok_or(gst::FlowError::Error)?;
+					drop(sample);
+					if appsrc.caps().as_ref() != Some(&caps) {
+						appsrc.set_caps(Some(&caps));
+					}
+					if !chain.is_empty() {
+						let s = caps.structure(0).ok_or(gst::FlowError::NotNegotiated)?;
+						let width = s.get::<i32>("width").map_err(|_| gst::FlowError::Error)?;
+						let height = s.get::<i32>("height").map_err(|_| gst::FlowError::Error)?;
+						let buffer = buffer.make_mut();
+						let timestamp = buffer.pts().map_or(Duration::ZERO, |pts| {
+							Duration::from_nanos(pts.nseconds())
+						});
+						let mut map = buffer.map_writable().map_err(|_| gst::FlowError::Error)?;
+						chain.process(&mut FrameMut {
+							data: map.as_mut_slice(),
+							width: width as u32,
+							height: height as u32,
+							timestamp,
+						});
+					}
+					appsrc.push_buffer(buffer)
+				})
+				.eos(move |_| {
+					let _ = eos_src.end_of_stream();
+				})
+				.build(),
+		);
+		Ok(convert)
+	}
+}
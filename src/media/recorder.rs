@@ -0,0 +1,548 @@
+// Recording pipeline, independent of any user interface.
+
+// The pipeline is put together by the builder; the recorder drives it. The
+// encoded tee is shared by the recording and all network outputs, which are
+// attached and detached while running. Audio-only recorders have neither a
+// video source nor an encoded tee, so everything built on them fails with
+// `VideoError::NoVideo`. The raw audio tee feeds the recording and, while
+// listening, the audio monitor.
+use std::{
+	fs,
+	path::Path,
+	sync::{Arc, Condvar, Mutex},
+	thread,
+	time::Duration,
+};
+
+use gst::prelude::*;
+use gstreamer as gst;
+use gstreamer::Pipeline;
+
+use crate::media::{
+	audio::AudioInput,
+	balance::{ImageAdjustments, BALANCE},
+	builder::{indexed, RecorderBuilder},
+	error::VideoError,
+	frame::FrameSlot,
+	hls::{HlsOutput, HlsSettings},
+	level::AudioMeters,
+	mjpeg::{self, MjpegServer, MjpegSettings},
+	mode::VideoMode,
+	monitor::{AudioMonitor, MonitorSettings},
+	output::{EncodedBranch, EncodedTee, OutputHealth, StreamOutput, StreamTarget},
+	processor::ProcessorChain,
+	rtsp::{RtspServer, RtspSettings},
+	source::{Backoff, CaptureSource, LiveSource},
+	spectrum::SpectrumAnalyzer,
+	status::StatusTracker,
+	sync::AvOffset,
+	transform::{Branch, Transform},
+	webrtc::{WebRtcServer, WebRtcSettings},
+};
+
+/// Capture, encoding and recording pipeline with its network outputs.
+#[derive(Debug)]
+pub struct Recorder {
+	/// Bus of the pipeline, watched on a background thread.
+	pub bus: gst::Bus,
+	/// The whole pipeline.
+	pub pipeline: gst::Pipeline,
+	/// Capture source, swapped when switching cameras or reconnecting; `None`
+	/// when recording audio only.
+	pub source: Option<Arc<LiveSource>>,
+	/// Tee after the video encoder, shared by the recording and the network
+	/// outputs; `None` when recording audio only.
+	pub encoded: Option<EncodedTee>,
+	/// Tee after the audio capture, shared by the recording and the monitor;
+	/// `None` when recording video only.
+	pub raw_audio: Option<EncodedTee>,
+	/// Audio monitor, while listening.
+	pub monitor: Option<(AudioMonitor, EncodedBranch)>,
+	/// Embedded RTSP server, while serving.
+	pub rtsp: Option<(RtspServer, EncodedBranch)>,
+	/// HLS output, while publishing.
+	pub hls: Option<(HlsOutput, EncodedBranch)>,
+	/// WebRTC signalling server, while publishing.
+	pub webrtc: Option<WebRtcServer>,
+	/// Latest preview frame, also read by the MJPEG server.
+	pub frames: FrameSlot,
+	/// Processors run on every camera frame before preview and encoding, if
+	/// the recorder was built with a chain.
+	pub processors: Option<ProcessorChain>,
+	/// MJPEG preview server, while serving.
+	pub mjpeg: Option<MjpegServer>,
+	/// Recording state and counters, also reported by the remote API.
+	pub status: StatusTracker,
+	/// Spectrum analyzer of the captured audio, if one was requested.
+	pub spectrum: Option<SpectrumAnalyzer>,
+	/// SRT and RTP outputs, shared with the bus watch to report failures.
+	pub outputs: Arc<Mutex<Vec<(StreamOutput, EncodedBranch)>>>,
+	next_output: u32,
+	/// Set by the bus watch once end-of-stream reached the recording sink.
+	eos: Arc<(Mutex<bool>, Condvar)>,
+}
+
+impl Drop for Recorder {
+	fn drop(&mut self) {
+		if self.pipeline.current_state() == gst::State::Playing {
+			if let Err(err) = self.finish(Duration::from_secs(3)) {
+				log::warn!("recording was not finalized: {}", err);
+			}
+		}
+		self.pipeline.set_state(gst::State::Null).expect("failed to set state");
+	}
+}
+
+impl Recorder {
+	/// Start describing a recorder, see [`RecorderBuilder`].
+	pub fn builder() -> RecorderBuilder {
+		RecorderBuilder::default()
+	}
+
+	/// Wrap a pipeline put together by [`RecorderBuilder::build`] and start
+	/// watching its bus.
+	pub(crate) fn start(
+		pipeline: Pipeline,
+		source: Option<Arc<LiveSource>>,
+		encoded: Option<EncodedTee>,
+		raw_audio: Option<EncodedTee>,
+		frames: FrameSlot,
+		status: StatusTracker,
+		meters: AudioMeters,
+	) -> Self {
+		let bus = pipeline.bus().expect("Pipeline without bus. Shouldn't happen!");
+		let outputs = Arc::new(Mutex::new(Vec::new()));
+		let eos = Arc::new((Mutex::new(false), Condvar::new()));
+		let spectrum = meters.spectrum.as_ref().map(|(analyzer, _)| analyzer.clone());
+		watch_bus(
+			bus.clone(),
+			pipeline.downgrade(),
+			source.clone(),
+			outputs.clone(),
+			eos.clone(),
+			meters,
+		);
+		Recorder {
+			bus,
+			pipeline,
+			source,
+			encoded,
+			raw_audio,
+			monitor: None,
+			rtsp: None,
+			hls: None,
+			webrtc: None,
+			frames,
+			processors: None,
+			mjpeg: None,
+			status,
+			spectrum,
+			outputs,
+			next_output: 0,
+			eos,
+		}
+	}
+
+	/// Start the embedded RTSP server and return the URL it serves.
+	///
+	/// A running server is restarted with the new settings.
+	pub fn start_rtsp(&mut self, settings: &RtspSettings) -> Result<String, VideoError> {
+		self.stop_rtsp();
+		let encoded = self.encoded.as_ref().ok_or(VideoError::NoVideo)?;
+		let server = RtspServer::start(settings)?;
+		let branch = encoded.attach(server.branch())?;
+		let url = server.url();
+		self.rtsp = Some((server, branch));
+		Ok(url)
+	}
+
+	/// Stop the embedded RTSP server, if running.
+	pub fn stop_rtsp(&mut self) {
+		if let Some((server, branch)) = self.rtsp.take() {
+			if let Some(encoded) = &self.encoded {
+				encoded.detach(branch);
+			}
+			drop(server);
+		}
+	}
+
+	/// Start writing the HLS playlist and return the URL of the viewer page.
+	///
+	/// A running output is restarted with the new settings.
+	pub fn start_hls(&mut self, settings: &HlsSettings) -> Result<String, VideoError> {
+		self.stop_hls();
+		let encoded = self.encoded.as_ref().ok_or(VideoError::NoVideo)?;
+		let output = HlsOutput::start(settings)?;
+		let branch = encoded.attach(output.branch())?;
+		let url = output.url();
+		self.hls = Some((output, branch));
+		Ok(url)
+	}
+
+	/// Start the WebRTC signalling server and return the URL of the viewer page.
+	///
+	/// Every viewer gets its own peer attached to the encoded stream; they are
+	/// detached when their WebSocket closes.
+	pub fn start_webrtc(&mut self, settings: &WebRtcSettings) -> Result<String, VideoError> {
+		self.webrtc = None;
+		let encoded = self.encoded.clone().ok_or(VideoError::NoVideo)?;
+		let server = WebRtcServer::start(settings, encoded)?;
+		let url = server.url();
+		self.webrtc = Some(server);
+		Ok(url)
+	}
+
+	/// Start playing the captured audio.
+	///
+	/// A running monitor is restarted with the new settings. The monitor only
+	/// reads from the raw audio; its volume and buffering leave the recording
+	/// alone.
+	pub fn start_monitor(&mut self, settings: &MonitorSettings) -> Result<(), VideoError> {
+		self.stop_monitor();
+		let raw_audio = self.raw_audio.as_ref().ok_or(VideoError::NoAudio)?;
+		let monitor = AudioMonitor::start(settings)?;
+		let branch = raw_audio.attach(monitor.branch())?;
+		self.monitor = Some((monitor, branch));
+		Ok(())
+	}
+
+	/// Stop playing the captured audio, if listening.
+	pub fn stop_monitor(&mut self) {
+		if let Some((monitor, branch)) = self.monitor.take() {
+			if let Some(raw_audio) = &self.raw_audio {
+				raw_audio.detach(branch);
+			}
+			drop(monitor);
+		}
+	}
+
+	/// Apply the gain, mute, pan and processing of `input` to audio input
+	/// `index` while running. Source and device stay as built; pan is ignored
+	/// in mono.
+	pub fn set_audio_input(&self, index: usize, input: &AudioInput) -> Result<(), VideoError> {
+		input.validate()?;
+		let volume = self
+			.pipeline
+			.by_name(&indexed("desktop-audio-volume", index))
+			.ok_or_else(|| VideoError::Config(format!("no audio input {}", index)))?;
+		volume.set_property("volume", input.volume());
+		volume.set_property("mute", input.muted);
+		if let Some(panorama) = self.pipeline.by_name(&indexed("desktop-audio-panorama", index)) {
+			panorama.set_property("panorama", input.pan);
+		}
+		input.processing.apply(self.pipeline.upcast_ref(), index)
+	}
+
+	/// Correct the colors of the camera while running.
+	pub fn set_image_adjustments(&self, adjustments: &ImageAdjustments) -> Result<(), VideoError> {
+		let balance = self.pipeline.by_name(BALANCE).ok_or(VideoError::NoVideo)?;
+		adjustments.apply(&balance)
+	}
+
+	/// Mirror, flip, rotate and crop the frames of `branch` while running.
+	///
+	/// Changing the size of recorded frames mid-file may fail the muxer, so
+	/// the recording is best transformed while not recording.
+	pub fn set_transform(&self, branch: Branch, transform: &Transform) -> Result<(), VideoError> {
+		transform.apply(self.pipeline.upcast_ref(), branch)
+	}
+
+	/// Scale recorded frames to `size`, so a zoom recorded as a crop keeps
+	/// the frame size; `None` leaves the size to the camera and the transform
+	/// again, also dropping a resolution set on the builder.
+	pub fn scale_to(&self, size: Option<(u32, u32)>) -> Result<(), VideoError> {
+		let filter = self.pipeline.by_name("desktop-video-raw-caps").ok_or(VideoError::NoVideo)?;
+		let mut caps = filter.property::<gst::Caps>("caps");
+		{
+			let structure = caps.make_mut().structure_mut(0).ok_or(VideoError::Caps)?;
+			match size {
+				Some((width, height)) => {
+					structure.set("width", width as i32);
+					structure.set("height", height as i32);
+				}
+				None => {
+					structure.remove_field("width");
+					structure.remove_field("height");
+				}
+			}
+		}
+		filter.set_property("caps", &caps);
+		Ok(())
+	}
+
+	/// Shift the recorded audio by `offset` against the video, see
+	/// [`AvOffset::apply`].
+	pub fn set_av_offset(&self, offset: AvOffset) -> Result<(), VideoError> {
+		offset.apply(&self.pipeline)
+	}
+
+	/// Start or stop recording.
+	pub fn record(&self, recording: bool) {
+		let state = if recording { gst::State::Playing } else { gst::State::Paused };
+		if let Err(err) = self.pipeline.set_state(state) {
+			log::error!("failed to set recorder to {:?}: {}", state, err);
+			return;
+		}
+		self.status.set_recording(recording);
+	}
+
+	/// Finish the recording so the file is complete and seekable.
+	///
+	/// End-of-stream is sent through the pipeline and awaited for up to
+	/// `timeout`, then the pipeline is stopped either way.
+	pub fn finish(&self, timeout: Duration) -> Result<(), VideoError> {
+		*self.eos.0.lock().unwrap() = false;
+		let sent = self.pipeline.send_event(gst::event::Eos::new());
+		let (lock, condvar) = &*self.eos;
+		let finished = sent
+			&& *condvar
+				.wait_timeout_while(lock.lock().unwrap(), timeout, |done| !*done)
+				.unwrap()
+				.0;
+		self.pipeline.set_state(gst::State::Null)?;
+		self.status.set_recording(false);
+		if finished {
+			Ok(())
+		} else {
+			Err(VideoError::Sync)
+		}
+	}
+
+	/// Video capture source, unless recording audio only.
+	pub fn video_source(&self) -> Result<&LiveSource, VideoError> {
+		self.source.as_deref().ok_or(VideoError::NoVideo)
+	}
+
+	/// Switch the capture source while running.
+	pub fn set_source(&self, source: &CaptureSource) -> Result<(), VideoError> {
+		self.video_source()?.replace(Some(source))?;
+		self.status.set_source(source);
+		Ok(())
+	}
+
+	/// Ask the capture source for `mode`, `None` for automatic negotiation.
+	pub fn set_mode(&self, mode: Option<VideoMode>) -> Result<(), VideoError> {
+		self.video_source()?.set_mode(mode)
+	}
+
+	/// Save the latest preview frame as JPEG to `path`.
+	///
+	/// Encoding and writing happen on a background thread.
+	pub fn snapshot(&self, path: &Path) {
+		let frame = match self.frames.latest() {
+			Some(frame) => frame,
+			None => {
+				log::warn!("no frame to snapshot yet");
+				return;
+			}
+		};
+		let path = path.to_path_buf();
+		thread::spawn(move || {
+			let result = mjpeg::encode(&frame, 90)
+				.map_err(anyhow::Error::from)
+				.and_then(|jpeg| fs::write(&path, jpeg).map_err(anyhow::Error::from));
+			match result {
+				Ok(()) => log::info!("snapshot saved to {}", path.display()),
+				Err(err) => log::error!("failed to save snapshot {}: {}", path.display(), err),
+			}
+		});
+	}
+
+	/// Start the MJPEG preview server and return the URL of the stream.
+	pub fn start_mjpeg(&mut self, settings: &MjpegSettings) -> Result<String, VideoError> {
+		self.mjpeg = None;
+		let server = MjpegServer::start(settings, self.frames.clone())?;
+		let url = server.url();
+		self.mjpeg = Some(server);
+		Ok(url)
+	}
+
+	/// Attach an SRT or RTP output to the encoded stream and return its id.
+	///
+	/// Health changes are reported to `on_health` along with the id.
+	pub fn add_output(
+		&mut self,
+		target: &StreamTarget,
+		on_health: impl Fn(u32, OutputHealth) + Send + Sync + 'static,
+	) -> Result<u32, VideoError> {
+		let encoded = self.encoded.as_ref().ok_or(VideoError::NoVideo)?;
+		let id = self.next_output;
+		let output = StreamOutput::new(id, target, Arc::new(move |health| on_health(id, health)))?;
+		let branch = encoded.attach(output.branch())?;
+		self.outputs.lock().unwrap().push((output, branch));
+		self.next_output += 1;
+		Ok(id)
+	}
+
+	/// Detach the output `id`, if attached.
+	pub fn remove_output(&mut self, id: u32) {
+		let removed = {
+			let mut outputs = self.outputs.lock().unwrap();
+			outputs.iter().position(|(output, _)| output.id == id).map(|i| outputs.remove(i))
+		};
+		if let (Some((_, branch)), Some(encoded)) = (removed, &self.encoded) {
+			encoded.detach(branch);
+		}
+	}
+
+	/// Stop the HLS output, if running.
+	pub fn stop_hls(&mut self) {
+		if let Some((output, branch)) = self.hls.take() {
+			if let Some(encoded) = &self.encoded {
+				encoded.detach(branch);
+			}
+			drop(output);
+		}
+	}
+}
+
+/// Handle messages of the recorder pipeline until it is dropped.
+///
+/// Errors raised inside the capture source rebuild it after a growing delay;
+/// the delay starts over once the source delivered buffers again. Errors of
+/// network outputs only mark the output as failed. End-of-stream is signalled
+/// through `eos` and audio measurements are passed to `meters`.
+fn watch_bus(
+	bus: gst::Bus,
+	pipeline: glib::WeakRef<Pipeline>,
+	source: Option<Arc<LiveSource>>,
+	outputs: Arc<Mutex<Vec<(StreamOutput, EncodedBranch)>>>,
+	eos: Arc<(Mutex<bool>, Condvar)>,
+	meters: AudioMeters,
+) {
+	thread::spawn(move || {
+		let mut backoff = Backoff::default();
+		while pipeline.upgrade().is_some() {
+			let msg = match bus.timed_pop(gst::ClockTime::from_mseconds(100)) {
+				Some(msg) => msg,
+				None => continue,
+			};
+			if let gst::MessageView::Eos(..) = msg.view() {
+				let (lock, condvar) = &*eos;
+				*lock.lock().unwrap() = true;
+				condvar.notify_all();
+				continue;
+			}
+			if meters.dispatch(&msg) {
+				continue;
+			}
+			if let gst::MessageView::Error(err) = msg.view() {
+				let src = msg.src().map(|s| String::from(s.path_string())).unwrap_or_default();
+				let outputs = outputs.lock().unwrap();
+				let failed = outputs
+					.iter()
+					.find(|(output, _)| msg.src().map_or(false, |s| output.owns(&s)));
+				if let Some((output, _)) = failed {
+					log::warn!("output {} failed: {}", output.target, err.error());
+					output.fail(&err.error());
+					continue;
+				}
+				drop(outputs);
+				let source = match &source {
+					Some(source) if msg.src().map_or(false, |s| source.owns(&s)) => source,
+					_ => {
+						log::error!("error from {}: {} ({:?})", src, err.error(), err.debug());
+						continue;
+					}
+				};
+				if source.retrying() {
+					// The bin that is about to be replaced is still failing.
+					continue;
+				}
+				let flowed = source.take_flowing();
+				if !flowed {
+					// A mode the source never produced a frame in is most likely
+					// refused by it, not a flaky connection.
+					if let Some(mode) = source.clear_mode() {
+						log::warn!(
+							"capture source {} failed in mode {}: {}, falling back to automatic \
+							 negotiation",
+							src,
+							mode,
+							err.error()
+						);
+						if let Err(err) = source.replace(None) {
+							log::error!("failed to restart capture source: {}", err);
+						}
+						continue;
+					}
+				} else {
+					backoff.reset();
+				}
+				if !source.source().is_network() {
+					log::error!("capture source {} failed: {}", src, err.error());
+					continue;
+				}
+				let delay = backoff.next_delay();
+				log::warn!("capture source {} failed: {}, retrying in {:?}", src, err.error(), delay);
+				// Sleeping here would hold up EOS and the meters meanwhile.
+				source.retry_after(delay);
+			}
+		}
+		log::debug!("Recorder bus watch stopped!");
+	});
+}
+
+#[cfg(test)]
+mod tests {
+	use std::net::TcpListener;
+
+	use gst_rtsp_server::prelude::*;
+	use gstreamer_rtsp_server as gst_rtsp_server;
+
+	use super::*;
+	use crate::media::{recording::RecordingOutput, source::RtspTransport, testing};
+
+	/// RTSP server streaming a JPEG test pattern at `/test` on `port`.
+	fn serve_test_pattern(port: u16) -> (gst_rtsp_server::RTSPServer, glib::MainLoop) {
+		let server = gst_rtsp_server::RTSPServer::new();
+		server.set_address("127.0.0.1");
+		server.set_service(&port.to_string());
+		let factory = gst_rtsp_server::RTSPMediaFactory::new();
+		factory.set_launch(
+			"( videotestsrc is-live=true ! video/x-raw, width=320, height=240 ! jpegenc ! \
+			 rtpjpegpay name=pay0 pt=26 )",
+		);
+		factory.set_shared(true);
+		server.mount_points().unwrap().add_factory("/test", &factory);
+		let context = glib::MainContext::new();
+		let main_loop = glib::MainLoop::new(Some(&context), false);
+		server.attach(Some(&context)).unwrap();
+		let thread_loop = main_loop.clone();
+		thread::spawn(move || thread_loop.run());
+		(server, main_loop)
+	}
+
+	#[test]
+	fn reconnects_to_rtsp_server() {
+		let network = ["rtspsrc", "decodebin", "rtpjpegpay", "rtpjpegdepay", "jpegenc", "jpegdec"];
+		if !testing::has_elements(testing::VIDEO_RECORDER)
+			|| !testing::has_elements(&network)
+			|| !testing::has_elements(&["videotestsrc", "appsink"])
+		{
+			return;
+		}
+		let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+		let recorder = Recorder::builder()
+			.source(CaptureSource::Rtsp {
+				url: format!("rtsp://127.0.0.1:{}/test", port),
+				latency: 0,
+				transport: RtspTransport::Tcp,
+			})
+			.audio(None)
+			.output(RecordingOutput::Discard)
+			.build()
+			.unwrap();
+		recorder.record(true);
+
+		// Nothing listens yet, so the source fails and is retried.
+		thread::sleep(Duration::from_secs(1));
+		assert!(recorder.frames.latest().is_none());
+
+		let (_server, main_loop) = serve_test_pattern(port);
+		let frame = recorder.frames.wait_newer(0, Duration::from_secs(20));
+		main_loop.quit();
+		let (_, frame) = frame.expect("no frame after the RTSP server came up");
+		assert_eq!((frame.width, frame.height), (320, 240));
+	}
+}
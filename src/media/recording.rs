@@ -0,0 +1,238 @@
+// Recording file and encoder settings.
+use std::{
+	fmt,
+	path::{Path, PathBuf},
+	str::FromStr,
+	time::Duration,
+};
+
+use anyhow::anyhow;
+use gst::prelude::*;
+use gstreamer as gst;
+
+/// H.264 encoder preset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Profile {
+	/// Constrained baseline tuned for low latency, playable everywhere.
+	Preview,
+	/// Main profile at a moderate speed preset.
+	Balanced,
+	/// High profile at a slow preset, for the smallest files.
+	Archive,
+}
+
+impl Default for Profile {
+	fn default() -> Self {
+		Profile::Preview
+	}
+}
+
+impl fmt::Display for Profile {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			Profile::Preview => "preview",
+			Profile::Balanced => "balanced",
+			Profile::Archive => "archive",
+		})
+	}
+}
+
+impl FromStr for Profile {
+	type Err = anyhow::Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"preview" => Ok(Profile::Preview),
+			"balanced" => Ok(Profile::Balanced),
+			"archive" => Ok(Profile::Archive),
+			_ => Err(anyhow!("unknown profile {}, expected preview, balanced or archive", s)),
+		}
+	}
+}
+
+impl Profile {
+	/// Value of the `profile` field in the `video/x-h264` caps.
+	pub fn h264_profile(self) -> &'static str {
+		match self {
+			Profile::Preview => "constrained-baseline",
+			Profile::Balanced => "main",
+			Profile::Archive => "high",
+		}
+	}
+
+	/// Apply the preset to an `x264enc` element.
+	pub fn configure_encoder(self, encoder: &gst::Element) {
+		match self {
+			Profile::Preview => {
+				encoder.set_property("intra-refresh", true);
+				encoder.set_property("vbv-buf-capacity", 0u32);
+				encoder.set_property("qp-min", 30u32);
+				encoder.set_property("key-int-max", 36u32);
+			}
+			Profile::Balanced => {
+				encoder.set_property_from_str("speed-preset", "fast");
+				encoder.set_property("key-int-max", 60u32);
+			}
+			Profile::Archive => {
+				encoder.set_property_from_str("speed-preset", "slow");
+				encoder.set_property("key-int-max", 120u32);
+			}
+		}
+	}
+}
+
+/// Codec and container of audio-only recordings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioFormat {
+	/// Opus in an Ogg container, small files for speech.
+	Opus,
+	/// Lossless FLAC.
+	Flac,
+	/// Uncompressed PCM in a WAV file.
+	Wav,
+	/// AAC in an MPEG-4 container, plays on phones.
+	M4a,
+}
+
+impl fmt::Display for AudioFormat {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			AudioFormat::Opus => "opus",
+			AudioFormat::Flac => "flac",
+			AudioFormat::Wav => "wav",
+			AudioFormat::M4a => "m4a",
+		})
+	}
+}
+
+impl FromStr for AudioFormat {
+	type Err = anyhow::Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"opus" | "ogg" => Ok(AudioFormat::Opus),
+			"flac" => Ok(AudioFormat::Flac),
+			"wav" => Ok(AudioFormat::Wav),
+			"m4a" | "aac" => Ok(AudioFormat::M4a),
+			_ => Err(anyhow!("unknown audio format {}, expected opus, flac, wav or m4a", s)),
+		}
+	}
+}
+
+impl AudioFormat {
+	/// File extension of the recording.
+	pub fn extension(self) -> &'static str {
+		match self {
+			AudioFormat::Opus => "ogg",
+			AudioFormat::Flac => "flac",
+			AudioFormat::Wav => "wav",
+			AudioFormat::M4a => "m4a",
+		}
+	}
+
+	/// Factory of the encoder; `wavenc` also writes the container.
+	pub fn encoder(self) -> &'static str {
+		match self {
+			AudioFormat::Opus => "opusenc",
+			AudioFormat::Flac => "flacenc",
+			AudioFormat::Wav => "wavenc",
+			AudioFormat::M4a => "voaacenc",
+		}
+	}
+
+	/// Factory of the muxer, `None` when the encoder output is written as is.
+	pub fn muxer(self) -> Option<&'static str> {
+		match self {
+			AudioFormat::Opus => Some("oggmux"),
+			AudioFormat::M4a => Some("mp4mux"),
+			AudioFormat::Flac | AudioFormat::Wav => None,
+		}
+	}
+}
+
+/// Where and how the recording is written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordingSettings {
+	/// File to write, Matroska unless recording audio only.
+	pub location: PathBuf,
+	/// Encoder preset.
+	pub profile: Profile,
+	/// Start a new file every `segment`, numbering them after `location`.
+	pub segment: Option<Duration>,
+	/// Record only the microphone in this format, without any video.
+	pub audio_only: Option<AudioFormat>,
+}
+
+impl Default for RecordingSettings {
+	fn default() -> Self {
+		Self {
+			location: PathBuf::from(".media/druid.mkv"),
+			profile: Profile::default(),
+			segment: None,
+			audio_only: None,
+		}
+	}
+}
+
+impl RecordingSettings {
+	/// Recording to `location` with the default preset and no segments.
+	pub fn new(location: impl AsRef<Path>) -> Self {
+		Self { location: location.as_ref().to_path_buf(), ..Default::default() }
+	}
+
+	/// Record audio only in `format`, renaming the file to its extension.
+	pub fn audio_only(mut self, format: AudioFormat) -> Self {
+		self.location.set_extension(format.extension());
+		self.audio_only = Some(format);
+		self
+	}
+
+	/// `splitmuxsink` location pattern, `video.mkv` becomes `video-%05d.mkv`.
+	pub fn segment_pattern(&self) -> String {
+		segment_pattern(&self.location)
+	}
+
+	/// Output the recorder writes to.
+	pub fn output(&self) -> RecordingOutput {
+		match self.segment {
+			Some(duration) => {
+				RecordingOutput::Segments { location: self.location.clone(), duration }
+			}
+			None => RecordingOutput::File(self.location.clone()),
+		}
+	}
+}
+
+/// Where the encoded recording goes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecordingOutput {
+	/// A single file.
+	File(PathBuf),
+	/// Numbered files, see [`segment_pattern`].
+	Segments {
+		/// Path the segment names are derived from.
+		location: PathBuf,
+		/// Length of every segment.
+		duration: Duration,
+	},
+	/// Mux into a `fakesink`, for tests and stream-only setups.
+	Discard,
+}
+
+impl RecordingOutput {
+	/// Path the output is named after, empty when discarding.
+	pub fn location(&self) -> &Path {
+		match self {
+			RecordingOutput::File(location) | RecordingOutput::Segments { location, .. } => location,
+			RecordingOutput::Discard => Path::new(""),
+		}
+	}
+}
+
+/// `splitmuxsink` location pattern for `location`, `video.mkv` becomes
+/// `video-%05d.mkv`.
+pub fn segment_pattern(location: &Path) -> String {
+	let stem = location.file_stem().unwrap_or_default().to_string_lossy();
+	let extension = location.extension().map_or("mkv".into(), |ext| ext.to_string_lossy());
+	location.with_file_name(format!("{}-%05d.{}", stem, extension)).display().to_string()
+}
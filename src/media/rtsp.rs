@@ -0,0 +1,180 @@
+// Embedded RTSP server publishing the encoded camera stream.
+
+// {encoded tee} - {queue} - {appsink} ~~> {appsrc} - {h264parse} - {rtph264pay}
+
+// The recorder already encodes the camera to H.264 for the recording, so the
+// server does not open the device a second time. Instead an appsink branch is
+// attached to the encoded tee and every sample is pushed into the appsrc of the
+// shared RTSP media.
+use std::{
+	sync::{Arc, Mutex},
+	thread,
+};
+
+use gst::prelude::*;
+use gst_rtsp_server::prelude::*;
+use gstreamer as gst;
+use gstreamer_app as gst_app;
+use gstreamer_rtsp_server as gst_rtsp_server;
+
+use crate::media::error::VideoError;
+
+/// Settings of the embedded RTSP server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RtspSettings {
+	/// Port the server listens on.
+	pub port: u16,
+	/// Mount point the camera is published at, e.g. `/camera`.
+	pub mount: String,
+	/// Basic auth user name. Authentication is disabled when empty.
+	pub username: String,
+	/// Basic auth password.
+	pub password: String,
+}
+
+impl Default for RtspSettings {
+	fn default() -> Self {
+		Self { port: 8554, mount: "/camera".into(), username: String::new(), password: String::new() }
+	}
+}
+
+impl RtspSettings {
+	/// Mount point with a leading slash, as required by the mount table.
+	pub fn mount_path(&self) -> String {
+		if self.mount.starts_with('/') {
+			self.mount.clone()
+		} else {
+			format!("/{}", self.mount)
+		}
+	}
+
+	/// URL clients on this machine can open.
+	pub fn url(&self) -> String {
+		format!("rtsp://127.0.0.1:{}{}", self.port, self.mount_path())
+	}
+}
+
+/// Running RTSP server fed from the encoded branch of a recorder pipeline.
+#[derive(Debug)]
+pub struct RtspServer {
+	server: gst_rtsp_server::RTSPServer,
+	source: Option<glib::SourceId>,
+	main_loop: glib::MainLoop,
+	branch: gst::Element,
+	settings: RtspSettings,
+}
+
+impl RtspServer {
+	/// Start serving on `settings.port`.
+	///
+	/// The server runs on its own glib main loop so it does not depend on the
+	/// UI event loop. Nothing is streamed until [`branch`](Self::branch) is
+	/// linked to an encoded H.264 stream.
+	pub fn start(settings: &RtspSettings) -> Result<Self, VideoError> {
+		gst::init()?;
+		let clients: Arc<Mutex<Vec<gst_app::AppSrc>>> = Arc::new(Mutex::new(Vec::new()));
+
+		let branch = gst::parse_bin_from_description(
+			"queue leaky=downstream max-size-buffers=200 max-size-bytes=0 max-size-time=0 ! \
+			 appsink name=sink sync=false",
+			true,
+		)?;
+		branch.set_property("name", "rtsp-branch");
+		let appsink = branch
+			.by_name("sink")
+			.ok_or(VideoError::Cast)?
+			.dynamic_cast::<gst_app::AppSink>()
+			.map_err(|_| VideoError::Cast)?;
+		let sink_clients = clients.clone();
+		appsink.set_callbacks(
+			gst_app::AppSinkCallbacks::builder()
+				.new_sample(move |sink| {
+					let sample = sink.pull_sample().map_err(|_| gst::FlowError::Eos)?;
+					// Clients whose media got torn down refuse buffers, forget them.
+					sink_clients.lock().unwrap().retain(|src| src.push_sample(&sample).is_ok());
+					Ok(gst::FlowSuccess::Ok)
+				})
+				.build(),
+		);
+
+		let server = gst_rtsp_server::RTSPServer::new();
+		server.set_service(&settings.port.to_string());
+		let mounts = server.mount_points().ok_or(VideoError::Cast)?;
+
+		let factory = gst_rtsp_server::RTSPMediaFactory::new();
+		factory.set_launch(
+			"( appsrc name=src is-live=true format=time do-timestamp=true ! h264parse ! \
+			 rtph264pay name=pay0 pt=96 config-interval=1 )",
+		);
+		// All viewers share a single media so the appsink only feeds one appsrc.
+		factory.set_shared(true);
+		factory.connect_media_configure(move |_, media| {
+			let appsrc = media
+				.element()
+				.dynamic_cast::<gst::Bin>()
+				.ok()
+				.and_then(|bin| bin.by_name_recurse_up("src"))
+				.and_then(|src| src.dynamic_cast::<gst_app::AppSrc>().ok());
+			match appsrc {
+				Some(appsrc) => clients.lock().unwrap().push(appsrc),
+				None => log::error!("RTSP media without appsrc"),
+			}
+		});
+
+		if !settings.username.is_empty() {
+			let auth = gst_rtsp_server::RTSPAuth::new();
+			let token = gst_rtsp_server::RTSPToken::new(&[(
+				*gst_rtsp_server::RTSP_TOKEN_MEDIA_FACTORY_ROLE,
+				&"user",
+			)]);
+			let basic = gst_rtsp_server::RTSPAuth::make_basic(&settings.username, &settings.password);
+			auth.add_basic(basic.as_str(), &token);
+			server.set_auth(Some(&auth));
+			factory.add_role_from_structure(
+				&gst::Structure::builder("user")
+					.field(*gst_rtsp_server::RTSP_PERM_MEDIA_FACTORY_ACCESS, true)
+					.field(*gst_rtsp_server::RTSP_PERM_MEDIA_FACTORY_CONSTRUCT, true)
+					.build(),
+			);
+		}
+		mounts.add_factory(&settings.mount_path(), &factory);
+
+		let context = glib::MainContext::new();
+		let main_loop = glib::MainLoop::new(Some(&context), false);
+		let source = server.attach(Some(&context))?;
+		let thread_loop = main_loop.clone();
+		thread::spawn(move || thread_loop.run());
+		log::info!("RTSP server listening on {}", settings.url());
+
+		Ok(RtspServer {
+			server,
+			source: Some(source),
+			main_loop,
+			branch: branch.upcast(),
+			settings: settings.clone(),
+		})
+	}
+
+	/// Appsink branch to link to the encoded video stream.
+	pub fn branch(&self) -> &gst::Element {
+		&self.branch
+	}
+
+	/// URL the stream is published at.
+	pub fn url(&self) -> String {
+		self.settings.url()
+	}
+}
+
+impl Drop for RtspServer {
+	fn drop(&mut self) {
+		if let Some(mounts) = self.server.mount_points() {
+			mounts.remove_factory(&self.settings.mount_path());
+		}
+		if let Some(source) = self.source.take() {
+			source.remove();
+		}
+		self.main_loop.quit();
+		log::debug!("RTSP server stopped!");
+	}
+}
@@ -0,0 +1,503 @@
+// Capture sources feeding the recorder pipeline.
+
+// {source bin: {source} - {mode caps}} - {video tee} - ...
+
+// Every source is wrapped in a bin exposing a single `src` pad with raw video,
+// so local devices and network cameras share the same preview and recording
+// branches. Network sources are rebuilt with an exponential backoff whenever
+// the bus reports an error from inside the bin. A mode picked from the source's
+// caps is enforced by a capsfilter inside the bin; if the source cannot start
+// in it, the bin is rebuilt without one.
+use std::{
+	fmt,
+	str::FromStr,
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		Arc, Mutex,
+	},
+	thread,
+	time::Duration,
+};
+
+use anyhow::{anyhow, Context};
+use gst::prelude::*;
+use gstreamer as gst;
+
+use crate::media::{
+	error::VideoError,
+	launch,
+	mode::{modes_from_caps, VideoMode},
+};
+
+/// Lower layer transport used by RTSP sources.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RtspTransport {
+	/// Interleave RTP in the RTSP connection, works through NAT and firewalls.
+	Tcp,
+	/// Plain RTP over UDP, lower latency on local networks.
+	Udp,
+}
+
+impl RtspTransport {
+	fn protocols(self) -> &'static str {
+		match self {
+			RtspTransport::Tcp => "tcp",
+			RtspTransport::Udp => "udp",
+		}
+	}
+}
+
+/// Where the recorder takes its video from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CaptureSource {
+	/// Local camera of this machine.
+	Device,
+	/// RTSP camera, e.g. `rtsp://192.168.1.10:554/stream`.
+	Rtsp {
+		/// Stream URL.
+		url: String,
+		/// Jitterbuffer latency in milliseconds.
+		latency: u32,
+		/// Transport the RTP packets are received over.
+		transport: RtspTransport,
+	},
+	/// MJPEG stream served over HTTP as `multipart/x-mixed-replace`.
+	HttpMjpeg {
+		/// Stream URL.
+		url: String,
+	},
+	/// H.264 RTP packets sent to a local UDP port.
+	UdpRtp {
+		/// Address to listen on, may be a multicast group.
+		address: String,
+		/// Port to listen on.
+		port: u16,
+		/// Jitterbuffer latency in milliseconds.
+		latency: u32,
+	},
+	/// Generated `videotestsrc` pattern, for tests and demos.
+	Test,
+	/// gst-launch fragment with a single unlinked source pad, e.g.
+	/// `videotestsrc pattern=ball ! videoconvert`.
+	Custom {
+		/// Pipeline description.
+		description: String,
+	},
+}
+
+impl Default for CaptureSource {
+	fn default() -> Self {
+		CaptureSource::Device
+	}
+}
+
+impl fmt::Display for CaptureSource {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			CaptureSource::Device => write!(f, "camera"),
+			CaptureSource::Rtsp { url, .. } | CaptureSource::HttpMjpeg { url } => write!(f, "{}", url),
+			CaptureSource::UdpRtp { address, port, .. } => write!(f, "udp://{}:{}", address, port),
+			CaptureSource::Test => write!(f, "test"),
+			CaptureSource::Custom { description } => write!(f, "gst:{}", description),
+		}
+	}
+}
+
+impl FromStr for CaptureSource {
+	type Err = anyhow::Error;
+
+	/// Parse `device`, `test`, an `rtsp://` or `http://` URL, `udp://address:port`
+	/// or a `gst:` prefixed pipeline description.
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		if let Some(description) = s.strip_prefix("gst:") {
+			return Ok(CaptureSource::Custom { description: description.trim().to_string() });
+		}
+		match s.split_once("://") {
+			None if s == "device" || s == "camera" => Ok(CaptureSource::Device),
+			None if s == "test" => Ok(CaptureSource::Test),
+			Some(("rtsp" | "rtsps", _)) => Ok(CaptureSource::Rtsp {
+				url: s.to_string(),
+				latency: 200,
+				transport: RtspTransport::Tcp,
+			}),
+			Some(("http" | "https", _)) => Ok(CaptureSource::HttpMjpeg { url: s.to_string() }),
+			Some(("udp", address)) => {
+				let (address, port) =
+					address.rsplit_once(':').ok_or_else(|| anyhow!("missing port in {}", s))?;
+				Ok(CaptureSource::UdpRtp {
+					address: address.to_string(),
+					port: port.parse().with_context(|| format!("invalid port in {}", s))?,
+					latency: 200,
+				})
+			}
+			_ => Err(anyhow!("unknown source {}, expected device, test, rtsp://, http://, udp:// or gst:", s)),
+		}
+	}
+}
+
+impl CaptureSource {
+	/// Whether the source is received over the network.
+	pub fn is_network(&self) -> bool {
+		!matches!(self, CaptureSource::Device | CaptureSource::Test)
+	}
+
+	/// Pipeline description producing raw video.
+	///
+	/// User supplied values are set by [`configure`](Self::configure) instead
+	/// of being pasted in, so a URL or address cannot add elements or
+	/// properties.
+	fn description(&self) -> String {
+		match self {
+			#[cfg(target_os = "linux")]
+			CaptureSource::Device => "v4l2src".to_string(),
+			#[cfg(not(target_os = "linux"))]
+			CaptureSource::Device => "autovideosrc".to_string(),
+			CaptureSource::Rtsp { .. } => {
+				"rtspsrc name=source ! application/x-rtp, media=video ! decodebin ! videoconvert"
+					.to_string()
+			}
+			CaptureSource::HttpMjpeg { .. } => {
+				"souphttpsrc name=source is-live=true do-timestamp=true ! multipartdemux ! \
+				 image/jpeg ! jpegdec ! videoconvert"
+					.to_string()
+			}
+			CaptureSource::UdpRtp { .. } => {
+				"udpsrc name=source caps=\"application/x-rtp, media=video, clock-rate=90000, \
+				 encoding-name=H264, payload=96\" ! rtpjitterbuffer name=jitterbuffer ! \
+				 rtph264depay ! decodebin ! videoconvert"
+					.to_string()
+			}
+			CaptureSource::Test => "videotestsrc is-live=true".to_string(),
+			CaptureSource::Custom { description } => description.clone(),
+		}
+	}
+
+	/// Set the user supplied values on the elements of `bin`.
+	fn configure(&self, bin: &gst::Bin) -> Result<(), VideoError> {
+		let element = |name: &str| bin.by_name(name).ok_or(VideoError::Cast);
+		match self {
+			CaptureSource::Rtsp { url, latency, transport } => {
+				let source = element("source")?;
+				source.set_property("location", url);
+				source.set_property("latency", *latency);
+				source.set_property_from_str("protocols", transport.protocols());
+			}
+			CaptureSource::HttpMjpeg { url } => element("source")?.set_property("location", url),
+			CaptureSource::UdpRtp { address, port, latency } => {
+				let source = element("source")?;
+				source.set_property("address", address);
+				source.set_property("port", i32::from(*port));
+				element("jitterbuffer")?.set_property("latency", *latency);
+			}
+			CaptureSource::Device | CaptureSource::Test | CaptureSource::Custom { .. } => {}
+		}
+		Ok(())
+	}
+
+	/// Build a bin with a `src` pad producing raw video, in `mode` if given.
+	pub fn make_bin(&self, mode: Option<&VideoMode>) -> Result<gst::Element, VideoError> {
+		let description = match mode {
+			Some(mode) => format!("{} ! {}", self.description(), mode.description()),
+			None => self.description(),
+		};
+		let bin = launch::parse_bin(&description, "capture-source", &["src"])?;
+		self.configure(&bin)?;
+		Ok(bin.upcast())
+	}
+}
+
+/// Exponential reconnection delay.
+#[derive(Debug, Clone)]
+pub struct Backoff {
+	initial: Duration,
+	max: Duration,
+	next: Duration,
+}
+
+impl Default for Backoff {
+	fn default() -> Self {
+		Self::new(Duration::from_millis(500), Duration::from_secs(30))
+	}
+}
+
+impl Backoff {
+	/// Backoff starting at `initial` and doubling up to `max`.
+	pub fn new(initial: Duration, max: Duration) -> Self {
+		Self { initial, max, next: initial }
+	}
+
+	/// Delay to wait before the next attempt.
+	pub fn next_delay(&mut self) -> Duration {
+		let delay = self.next;
+		self.next = (self.next * 2).min(self.max);
+		delay
+	}
+
+	/// Start over after a successful connection.
+	pub fn reset(&mut self) {
+		self.next = self.initial;
+	}
+}
+
+/// Source, its requested mode and the bin built from them.
+struct Current {
+	source: CaptureSource,
+	mode: Option<VideoMode>,
+	bin: gst::Element,
+}
+
+/// Capture source linked into a pipeline, which can be swapped while running.
+pub struct LiveSource {
+	pipeline: glib::WeakRef<gst::Pipeline>,
+	downstream: gst::Element,
+	current: Mutex<Current>,
+	flowing: Arc<AtomicBool>,
+	retrying: AtomicBool,
+}
+
+impl fmt::Debug for LiveSource {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let current = self.current.lock().unwrap();
+		f.debug_struct("LiveSource")
+			.field("source", &current.source)
+			.field("mode", &current.mode)
+			.field("bin", &current.bin)
+			.field("retrying", &self.retrying)
+			.finish()
+	}
+}
+
+impl LiveSource {
+	/// Add the bin of `source` to `pipeline` and link it to `downstream`.
+	///
+	/// A `mode` the source cannot be linked in is dropped in favour of
+	/// automatic negotiation.
+	pub fn new(
+		pipeline: &gst::Pipeline,
+		source: &CaptureSource,
+		mode: Option<VideoMode>,
+		downstream: &gst::Element,
+	) -> Result<Self, VideoError> {
+		let flowing = Arc::new(AtomicBool::new(false));
+		let (mode, bin) =
+			match Self::link_bin(pipeline, source, mode.as_ref(), downstream, &flowing) {
+				Ok(bin) => (mode, bin),
+				Err(err) if mode.is_some() => {
+					log::warn!("{}, falling back to automatic negotiation", err);
+					(None, Self::link_bin(pipeline, source, None, downstream, &flowing)?)
+				}
+				Err(err) => return Err(err),
+			};
+		Ok(LiveSource {
+			pipeline: pipeline.downgrade(),
+			downstream: downstream.clone(),
+			current: Mutex::new(Current { source: source.clone(), mode, bin }),
+			flowing,
+			retrying: AtomicBool::new(false),
+		})
+	}
+
+	fn link_bin(
+		pipeline: &gst::Pipeline,
+		source: &CaptureSource,
+		mode: Option<&VideoMode>,
+		downstream: &gst::Element,
+		flowing: &Arc<AtomicBool>,
+	) -> Result<gst::Element, VideoError> {
+		let bin = source.make_bin(mode)?;
+		pipeline.add(&bin)?;
+		let linked = || -> Result<gst::Pad, VideoError> {
+			let src_pad = bin.static_pad("src").ok_or(VideoError::Caps)?;
+			let sink_pad = downstream.static_pad("sink").ok_or(VideoError::Caps)?;
+			launch::link_checked(&src_pad, &sink_pad)?;
+			Ok(src_pad)
+		};
+		// A bin left behind would hold the device or keep a connection open.
+		let src_pad = match linked() {
+			Ok(pad) => pad,
+			Err(err) => {
+				let _ = pipeline.remove(&bin);
+				return Err(err);
+			}
+		};
+		let flowing = flowing.clone();
+		src_pad.add_probe(gst::PadProbeType::BUFFER, move |_, _| {
+			flowing.store(true, Ordering::Relaxed);
+			gst::PadProbeReturn::Remove
+		});
+		Ok(bin)
+	}
+
+	/// Source currently in use.
+	pub fn source(&self) -> CaptureSource {
+		self.current.lock().unwrap().source.clone()
+	}
+
+	/// Mode requested from the source, `None` when negotiated automatically.
+	pub fn mode(&self) -> Option<VideoMode> {
+		self.current.lock().unwrap().mode.clone()
+	}
+
+	/// Modes the current source offers.
+	///
+	/// The caps are queried from the source element inside the bin, so they
+	/// are not narrowed by the mode in use. Sources that only know their caps
+	/// once connected, like network streams, report no modes before that.
+	pub fn modes(&self) -> Vec<VideoMode> {
+		let current = self.current.lock().unwrap();
+		let element = current.bin.downcast_ref::<gst::Bin>().and_then(|bin| {
+			bin.iterate_sources().into_iter().filter_map(Result::ok).next()
+		});
+		let pad = element.as_ref().and_then(|element| element.static_pad("src"));
+		match pad {
+			Some(pad) => modes_from_caps(&pad.query_caps(None)),
+			None => Vec::new(),
+		}
+	}
+
+	/// Whether `object` is, or is inside, the current source bin.
+	pub fn owns(&self, object: &gst::Object) -> bool {
+		let current = self.current.lock().unwrap();
+		object == current.bin.upcast_ref::<gst::Object>() || object.has_as_ancestor(&current.bin)
+	}
+
+	/// Whether buffers came out of the source since the last call.
+	pub fn take_flowing(&self) -> bool {
+		self.flowing.swap(false, Ordering::Relaxed)
+	}
+
+	/// Tear down the current bin and build `source` in its place.
+	///
+	/// Passing `None` rebuilds the current source in its current mode, which is
+	/// how errors are recovered from. A new source starts with automatic
+	/// negotiation since modes differ between sources.
+	pub fn replace(&self, source: Option<&CaptureSource>) -> Result<(), VideoError> {
+		let mut current = self.current.lock().unwrap();
+		let (source, mode) = match source {
+			Some(source) => (source.clone(), None),
+			None => (current.source.clone(), current.mode.clone()),
+		};
+		self.rebuild(&mut current, source, mode)
+	}
+
+	/// Whether a rebuild scheduled by [`retry_after`](Self::retry_after) is
+	/// still waiting.
+	pub fn retrying(&self) -> bool {
+		self.retrying.load(Ordering::SeqCst)
+	}
+
+	/// Rebuild the current source after `delay` on a separate thread.
+	///
+	/// Does nothing if a retry is already pending. The retry is dropped if the
+	/// source was swapped or the pipeline went away meanwhile.
+	pub fn retry_after(self: &Arc<Self>, delay: Duration) {
+		if self.retrying.swap(true, Ordering::SeqCst) {
+			return;
+		}
+		let live = Arc::downgrade(self);
+		let expected = self.source();
+		thread::spawn(move || {
+			thread::sleep(delay);
+			let live = match live.upgrade() {
+				Some(live) => live,
+				None => return,
+			};
+			let result = {
+				let mut current = live.current.lock().unwrap();
+				if current.source == expected {
+					let mode = current.mode.clone();
+					live.rebuild(&mut current, expected, mode)
+				} else {
+					Ok(())
+				}
+			};
+			live.retrying.store(false, Ordering::SeqCst);
+			if let Err(err) = result {
+				log::error!("failed to restart capture source: {}", err);
+			}
+		});
+	}
+
+	/// Rebuild the current source in `mode`, `None` for automatic negotiation.
+	///
+	/// If the source cannot be linked in `mode` the previous bin is kept and
+	/// the error is returned.
+	pub fn set_mode(&self, mode: Option<VideoMode>) -> Result<(), VideoError> {
+		let mut current = self.current.lock().unwrap();
+		let source = current.source.clone();
+		self.rebuild(&mut current, source, mode)
+	}
+
+	/// Drop the requested mode so the next rebuild negotiates automatically,
+	/// returning the mode that was dropped.
+	pub fn clear_mode(&self) -> Option<VideoMode> {
+		self.current.lock().unwrap().mode.take()
+	}
+
+	fn rebuild(
+		&self,
+		current: &mut Current,
+		source: CaptureSource,
+		mode: Option<VideoMode>,
+	) -> Result<(), VideoError> {
+		let pipeline = self.pipeline.upgrade().ok_or(VideoError::Sync)?;
+		let old = &current.bin;
+		old.set_state(gst::State::Null)?;
+		old.unlink(&self.downstream);
+		pipeline.remove(old)?;
+
+		let bin = match Self::link_bin(
+			&pipeline,
+			&source,
+			mode.as_ref(),
+			&self.downstream,
+			&self.flowing,
+		) {
+			Ok(bin) => bin,
+			Err(err) => {
+				// Keep the previous source rather than none at all.
+				pipeline.add(old)?;
+				old.link(&self.downstream)?;
+				old.sync_state_with_parent()?;
+				return Err(err);
+			}
+		};
+		bin.sync_state_with_parent()?;
+		*current = Current { source, mode, bin };
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::media::testing;
+
+	#[test]
+	fn sets_url_as_property() {
+		if !testing::has_elements(&["rtspsrc", "decodebin", "videoconvert"]) {
+			return;
+		}
+		let url = "rtsp://camera/\" ! fakesink name=\"injected".to_string();
+		let source =
+			CaptureSource::Rtsp { url: url.clone(), latency: 50, transport: RtspTransport::Udp };
+		let bin = source.make_bin(None).unwrap().downcast::<gst::Bin>().unwrap();
+		let rtspsrc = bin.by_name("source").unwrap();
+		assert_eq!(rtspsrc.property::<String>("location"), url);
+		assert_eq!(rtspsrc.property::<u32>("latency"), 50);
+		assert!(bin.by_name("injected").is_none());
+	}
+
+	#[test]
+	fn parses_sources() {
+		assert_eq!("test".parse::<CaptureSource>().unwrap(), CaptureSource::Test);
+		let source = "udp://239.0.0.1:5000".parse::<CaptureSource>().unwrap();
+		assert_eq!(
+			source,
+			CaptureSource::UdpRtp { address: "239.0.0.1".to_string(), port: 5000, latency: 200 }
+		);
+		assert!(source.is_network());
+		assert!(!CaptureSource::Device.is_network());
+		assert!("udp://239.0.0.1".parse::<CaptureSource>().is_err());
+	}
+}
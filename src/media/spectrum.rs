@@ -0,0 +1,166 @@
+// Audio spectrum measured on the capture and playback branches.
+
+// ... - {level} - {spectrum} - ...
+
+// The `spectrum` element splits everything below the Nyquist frequency into
+// evenly spaced bands and posts their magnitudes at a fixed interval. Hum and
+// noise sit in the low frequencies where linear bands are coarsest, so the
+// element runs at a fixed high resolution and its bands are regrouped on a
+// logarithmic scale over the configured frequency range. Since regrouping
+// happens when a message is read, band count and range can change while
+// running.
+use std::{
+	sync::{Arc, Mutex},
+	time::Duration,
+};
+
+use gst::prelude::*;
+use gstreamer as gst;
+
+use crate::media::error::VideoError;
+
+/// Bands computed by the `spectrum` element.
+const RAW_BANDS: u32 = 1024;
+
+/// Band count and frequency range of the displayed spectrum.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpectrumSettings {
+	/// Number of bands shown.
+	pub bands: u32,
+	/// Lowest frequency shown, in Hz.
+	pub min_freq: f64,
+	/// Highest frequency shown, in Hz.
+	pub max_freq: f64,
+	/// Magnitudes below this are reported as silence, in dB.
+	pub threshold: i32,
+}
+
+impl Default for SpectrumSettings {
+	fn default() -> Self {
+		Self { bands: 48, min_freq: 20.0, max_freq: 20_000.0, threshold: -80 }
+	}
+}
+
+impl SpectrumSettings {
+	/// Reject empty or inverted ranges.
+	pub fn validate(&self) -> Result<(), VideoError> {
+		if self.bands == 0 || self.bands > RAW_BANDS {
+			return Err(VideoError::Config(format!(
+				"{} spectrum bands, expected 1 to {}",
+				self.bands, RAW_BANDS
+			)));
+		}
+		if self.min_freq <= 0.0 || self.min_freq >= self.max_freq {
+			return Err(VideoError::Config(format!(
+				"spectrum range {} to {} Hz is empty",
+				self.min_freq, self.max_freq
+			)));
+		}
+		Ok(())
+	}
+}
+
+/// Magnitudes of logarithmically spaced bands.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Spectrum {
+	/// Magnitude of every band from low to high frequency, in dB.
+	pub magnitudes: Vec<f32>,
+	/// Lower edge of the first band, in Hz.
+	pub min_freq: f64,
+	/// Upper edge of the last band, in Hz.
+	pub max_freq: f64,
+	/// Magnitude of silence, in dB.
+	pub threshold: i32,
+}
+
+/// Called with every spectrum measurement.
+pub type SpectrumCallback = Arc<dyn Fn(&Spectrum) + Send + Sync>;
+
+/// Builds `spectrum` elements and turns their messages into [`Spectrum`]s.
+///
+/// Clones share their settings.
+#[derive(Debug, Clone)]
+pub struct SpectrumAnalyzer {
+	settings: Arc<Mutex<SpectrumSettings>>,
+}
+
+impl SpectrumAnalyzer {
+	/// Analyzer showing `settings`.
+	pub fn new(settings: SpectrumSettings) -> Result<Self, VideoError> {
+		settings.validate()?;
+		Ok(Self { settings: Arc::new(Mutex::new(settings)) })
+	}
+
+	/// Settings in use.
+	pub fn settings(&self) -> SpectrumSettings {
+		*self.settings.lock().unwrap()
+	}
+
+	/// Show `settings` from the next measurement on.
+	pub fn set_settings(&self, settings: SpectrumSettings) -> Result<(), VideoError> {
+		settings.validate()?;
+		*self.settings.lock().unwrap() = settings;
+		Ok(())
+	}
+
+	/// Build a `spectrum` element posting a message every `interval`.
+	pub fn make_element(&self, name: &str, interval: Duration) -> Result<gst::Element, VideoError> {
+		let spectrum = gst::ElementFactory::make("spectrum", Some(name))
+			.map_err(|_| VideoError::MissingElement("spectrum".to_string()))?;
+		spectrum.set_property("bands", RAW_BANDS);
+		spectrum.set_property("threshold", self.settings().threshold);
+		spectrum.set_property("interval", interval.as_nanos() as u64);
+		spectrum.set_property("post-messages", true);
+		spectrum.set_property("message-phase", false);
+		Ok(spectrum)
+	}
+
+	/// Spectrum in a message posted by one of the analyzer's elements.
+	pub fn read(&self, msg: &gst::Message) -> Option<Spectrum> {
+		if !matches!(msg.view(), gst::MessageView::Element(..)) {
+			return None;
+		}
+		let s = msg.structure().filter(|s| s.name() == "spectrum")?;
+		let raw: Vec<f32> = s
+			.get::<gst::List>("magnitude")
+			.ok()?
+			.as_slice()
+			.iter()
+			.filter_map(|value| value.get::<f32>().ok())
+			.collect();
+		// The sample rate is only known from the negotiated caps.
+		let element = msg.src()?.downcast::<gst::Element>().ok()?;
+		let caps = element.static_pad("sink")?.current_caps()?;
+		let rate = caps.structure(0)?.get::<i32>("rate").ok()?;
+		Some(self.regroup(&raw, f64::from(rate) / 2.0))
+	}
+
+	/// Group linear `raw` bands up to `nyquist` Hz into the configured
+	/// logarithmic bands, keeping the loudest raw band of each.
+	fn regroup(&self, raw: &[f32], nyquist: f64) -> Spectrum {
+		let settings = self.settings();
+		let silence = settings.threshold as f32;
+		let max_freq = settings.max_freq.min(nyquist);
+		let width = nyquist / raw.len().max(1) as f64;
+		let ratio = max_freq / settings.min_freq;
+		let bands = f64::from(settings.bands);
+		let edge = |i: u32| settings.min_freq * ratio.powf(f64::from(i) / bands);
+		let magnitudes = (0..settings.bands)
+			.map(|i| {
+				let (low, high) = (edge(i), edge(i + 1));
+				let first = (low / width) as usize;
+				// Bands narrower than a raw band still get the one they fall in.
+				let last = ((high / width) as usize).max(first + 1).min(raw.len());
+				raw.get(first..last)
+					.and_then(|bands| bands.iter().copied().reduce(f32::max))
+					.unwrap_or(silence)
+			})
+			.collect();
+		Spectrum {
+			magnitudes,
+			min_freq: settings.min_freq,
+			max_freq,
+			threshold: settings.threshold,
+		}
+	}
+}
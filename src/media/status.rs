@@ -0,0 +1,102 @@
+// Live recorder counters shared with reporting threads.
+use std::{
+	path::{Path, PathBuf},
+	sync::{Arc, Mutex},
+	time::{Duration, Instant},
+};
+
+use gst::prelude::*;
+use gstreamer as gst;
+use serde::Serialize;
+
+/// Snapshot of the recorder state.
+#[derive(Debug, Clone, Serialize)]
+pub struct RecorderStatus {
+	/// Whether the recording is running.
+	pub recording: bool,
+	/// Seconds since the recording started, `0` when not recording.
+	pub elapsed: f64,
+	/// File the recording is written to.
+	pub file: PathBuf,
+	/// Preview frames per second over the last second.
+	pub fps: f64,
+	/// Frames dropped by the recording framerate converter.
+	pub dropped_frames: u64,
+	/// Description of the capture source.
+	pub source: String,
+}
+
+#[derive(Debug)]
+struct Counters {
+	recording_since: Option<Instant>,
+	file: PathBuf,
+	source: String,
+	window_start: Instant,
+	window_frames: u32,
+	fps: f64,
+}
+
+/// Counters of a recorder, cheap to clone into callbacks and servers.
+#[derive(Debug, Clone)]
+pub struct StatusTracker {
+	counters: Arc<Mutex<Counters>>,
+	rate: Option<gst::Element>,
+}
+
+impl StatusTracker {
+	/// Track a recorder writing to `file`, whose dropped frames are counted by
+	/// the `videorate` element `rate`; audio-only recorders have none.
+	pub fn new(file: &Path, rate: Option<&gst::Element>) -> Self {
+		Self {
+			counters: Arc::new(Mutex::new(Counters {
+				recording_since: None,
+				file: file.to_path_buf(),
+				source: String::new(),
+				window_start: Instant::now(),
+				window_frames: 0,
+				fps: 0.0,
+			})),
+			rate: rate.cloned(),
+		}
+	}
+
+	/// Mark the recording as started or stopped.
+	pub fn set_recording(&self, recording: bool) {
+		let mut counters = self.counters.lock().unwrap();
+		match (recording, counters.recording_since) {
+			(true, None) => counters.recording_since = Some(Instant::now()),
+			(false, _) => counters.recording_since = None,
+			_ => {}
+		}
+	}
+
+	/// Remember the description of the capture source.
+	pub fn set_source(&self, source: impl ToString) {
+		self.counters.lock().unwrap().source = source.to_string();
+	}
+
+	/// Count a preview frame.
+	pub fn frame(&self) {
+		let mut counters = self.counters.lock().unwrap();
+		counters.window_frames += 1;
+		let window = counters.window_start.elapsed();
+		if window >= Duration::from_secs(1) {
+			counters.fps = f64::from(counters.window_frames) / window.as_secs_f64();
+			counters.window_frames = 0;
+			counters.window_start = Instant::now();
+		}
+	}
+
+	/// Current state of the recorder.
+	pub fn status(&self) -> RecorderStatus {
+		let counters = self.counters.lock().unwrap();
+		RecorderStatus {
+			recording: counters.recording_since.is_some(),
+			elapsed: counters.recording_since.map_or(0.0, |since| since.elapsed().as_secs_f64()),
+			file: counters.file.clone(),
+			fps: counters.fps,
+			dropped_frames: self.rate.as_ref().map_or(0, |rate| rate.property::<u64>("drop")),
+			source: counters.source.clone(),
+		}
+	}
+}
@@ -0,0 +1,45 @@
+// Settings kept between runs.
+
+// Presets and per-device settings are small JSON files in the user's
+// configuration directory, read whenever they are needed and rewritten as a
+// whole when one entry changes. A missing file is the same as an empty one.
+use std::{
+	fs, io,
+	path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::media::error::VideoError;
+
+/// `name` in the configuration directory of the application.
+pub fn config_path(name: &str) -> Option<PathBuf> {
+	let config = std::env::var_os("XDG_CONFIG_HOME")
+		.map(PathBuf::from)
+		.or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+	Some(config.join("druid_camera").join(name))
+}
+
+/// Value saved at `path`, the default if the file does not exist yet.
+pub fn load<T: DeserializeOwned + Default>(path: &Path) -> Result<T, VideoError> {
+	let json = match fs::read(path) {
+		Ok(json) => json,
+		Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(T::default()),
+		Err(err) => return Err(err.into()),
+	};
+	let value = serde_json::from_slice(&json)
+		.with_context(|| format!("invalid settings in {}", path.display()))?;
+	Ok(value)
+}
+
+/// Write `value` to `path`, creating its directory.
+pub fn save<T: Serialize>(path: &Path, value: &T) -> Result<(), VideoError> {
+	if let Some(directory) = path.parent() {
+		fs::create_dir_all(directory)?;
+	}
+	let json = serde_json::to_vec_pretty(value)
+		.with_context(|| format!("failed to encode {}", path.display()))?;
+	fs::write(path, json)?;
+	Ok(())
+}
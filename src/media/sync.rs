@@ -0,0 +1,193 @@
+// Audio/video sync offset at the muxer.
+
+// ... - {voaacenc} -[audio delay]- {muxer}
+// ... - {queue} -[video delay]- {muxer}
+
+// Cheap USB cameras deliver frames noticeably later or earlier than the
+// microphone hears the same moment. A pad offset shifts the running time of
+// everything crossing the pad, so the muxer stamps one stream later than the
+// other. Running time cannot go below zero, so audio that should come earlier
+// is handled by delaying the video instead. Playback shifts the same way
+// through the `av-offset` property of `playbin`.
+use std::{fmt, time::Duration};
+
+use gst::prelude::*;
+use gstreamer as gst;
+
+use crate::media::{builder::indexed, error::VideoError};
+
+/// Largest offset accepted either way.
+pub const MAX_OFFSET: Duration = Duration::from_secs(2);
+
+/// Audio delay relative to video in milliseconds; negative when audio should
+/// play earlier.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct AvOffset(pub i64);
+
+impl fmt::Display for AvOffset {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{:+} ms", self.0)
+	}
+}
+
+impl AvOffset {
+	/// Reject offsets larger than [`MAX_OFFSET`], more than any camera drifts.
+	pub fn validate(self) -> Result<(), VideoError> {
+		if self.0.unsigned_abs() > MAX_OFFSET.as_millis() as u64 {
+			return Err(VideoError::Config(format!(
+				"A/V offset of {} is more than {} ms",
+				self,
+				MAX_OFFSET.as_millis()
+			)));
+		}
+		Ok(())
+	}
+
+	/// Offset in nanoseconds, as `playbin` takes it.
+	pub fn nanos(self) -> i64 {
+		self.0 * 1_000_000
+	}
+
+	/// Delay the audio pads of the recording muxer or, for a negative offset,
+	/// its video pad, so the recorded streams shift by the offset. Meant for
+	/// a recorder that is not recording yet; shifting mid-file makes the
+	/// timestamps of one stream jump.
+	pub fn apply(self, pipeline: &gst::Pipeline) -> Result<(), VideoError> {
+		self.validate()?;
+		let video = pipeline
+			.by_name("desktop-video-queue-1")
+			.and_then(|queue| queue.static_pad("src"))
+			.ok_or(VideoError::NoVideo)?;
+		video.set_offset(self.nanos().min(0).abs());
+		let encoders = (0..).map_while(|i| pipeline.by_name(&indexed("desktop-audio-encoder", i)));
+		let mut audio = 0;
+		for encoder in encoders {
+			encoder.static_pad("src").ok_or(VideoError::Caps)?.set_offset(self.nanos().max(0));
+			audio += 1;
+		}
+		if audio == 0 {
+			return Err(VideoError::NoAudio);
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{
+		path::Path,
+		sync::{Arc, Mutex},
+		time::Instant,
+	};
+
+	use super::*;
+	use crate::media::{
+		audio::AudioSource, builder::PreviewSink, recorder::Recorder, recording::RecordingOutput,
+		source::CaptureSource, testing,
+	};
+
+	/// Record test sources with `offset` and measure the offset of the file.
+	fn measure(offset: AvOffset) -> Option<AvOffset> {
+		let path = testing::temp_path(&format!("offset{}.mkv", offset.0));
+		let builder = Recorder::builder()
+			.source(CaptureSource::Test)
+			.resolution(320, 240)
+			.audio_source(AudioSource::Test)
+			.av_offset(offset)
+			.output(RecordingOutput::File(path.to_path_buf()))
+			.preview_sink(PreviewSink::Fake);
+		let recorder = testing::build(builder)?;
+		testing::record(&recorder, Duration::from_secs(2));
+		Some(probe_offset(&path).unwrap())
+	}
+
+	#[test]
+	fn shifts_audio_by_offset() {
+		if !testing::has_elements(&["videotestsrc", "audiotestsrc", "filesrc", "matroskademux"]) {
+			return;
+		}
+		let start = match measure(AvOffset(0)) {
+			Some(start) => start,
+			None => return,
+		};
+		for offset in [AvOffset(400), AvOffset(-400)] {
+			let measured = measure(offset).unwrap();
+			// The test sources start within a frame of each other.
+			let shift = measured.0 - start.0;
+			assert!((shift - offset.0).abs() <= 50, "{} shifted the audio by {} ms", offset, shift);
+		}
+	}
+
+	/// How much later the first audio than the first video timestamp is in
+	/// the Matroska file at `path`, negative when audio starts first.
+	fn probe_offset(path: &Path) -> Result<AvOffset, VideoError> {
+		gst::init()?;
+		let pipeline = gst::Pipeline::new(Some("sync-probe"));
+		let source = gst::ElementFactory::make("filesrc", None)
+			.map_err(|_| VideoError::MissingElement("filesrc".to_string()))?;
+		let demux = gst::ElementFactory::make("matroskademux", None)
+			.map_err(|_| VideoError::MissingElement("matroskademux".to_string()))?;
+		source.set_property("location", path.to_str().ok_or(VideoError::Uri)?);
+		pipeline.add_many(&[&source, &demux])?;
+		source.link(&demux)?;
+
+		// First timestamp of the audio and of the video stream.
+		let first = Arc::new(Mutex::new((None::<gst::ClockTime>, None::<gst::ClockTime>)));
+		let pad_first = first.clone();
+		let pad_pipeline = pipeline.downgrade();
+		demux.connect_pad_added(move |_, pad| {
+			if let Some(pipeline) = pad_pipeline.upgrade() {
+				if let Err(err) = probe_first(&pipeline, pad, pad_first.clone()) {
+					log::error!("failed to probe {}: {}", pad.name(), err);
+				}
+			}
+		});
+
+		pipeline.set_state(gst::State::Playing)?;
+		let bus = pipeline.bus().ok_or(VideoError::Bus)?;
+		let started = Instant::now();
+		let result = loop {
+			if let (Some(audio), Some(video)) = *first.lock().unwrap() {
+				let difference = audio.nseconds() as i64 - video.nseconds() as i64;
+				break Ok(AvOffset(difference / 1_000_000));
+			}
+			if started.elapsed() > Duration::from_secs(10) {
+				break Err(VideoError::Duration);
+			}
+			match bus.timed_pop(gst::ClockTime::from_mseconds(50)).as_ref().map(|msg| msg.view()) {
+				Some(gst::MessageView::Error(err)) => break Err(err.error().into()),
+				// A file without audio or video.
+				Some(gst::MessageView::Eos(..)) => break Err(VideoError::Duration),
+				_ => {}
+			}
+		};
+		let _ = pipeline.set_state(gst::State::Null);
+		result
+	}
+
+	/// Drain the demuxer `pad` into a `fakesink`, noting the timestamp of its
+	/// first buffer in `first`.
+	fn probe_first(
+		pipeline: &gst::Pipeline,
+		pad: &gst::Pad,
+		first: Arc<Mutex<(Option<gst::ClockTime>, Option<gst::ClockTime>)>>,
+	) -> Result<(), VideoError> {
+		let audio = pad.name().starts_with("audio");
+		let sink = gst::ElementFactory::make("fakesink", None)
+			.map_err(|_| VideoError::MissingElement("fakesink".to_string()))?;
+		sink.set_property("sync", false);
+		pipeline.add(&sink)?;
+		sink.sync_state_with_parent()?;
+		pad.add_probe(gst::PadProbeType::BUFFER, move |_, info| {
+			if let Some(gst::PadProbeData::Buffer(buffer)) = &info.data {
+				let mut first = first.lock().unwrap();
+				let stream = if audio { &mut first.0 } else { &mut first.1 };
+				*stream = stream.or_else(|| buffer.pts());
+			}
+			gst::PadProbeReturn::Remove
+		});
+		let sink_pad = sink.static_pad("sink").ok_or(VideoError::Caps)?;
+		pad.link(&sink_pad)?;
+		Ok(())
+	}
+}
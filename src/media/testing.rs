@@ -0,0 +1,95 @@
+// Helpers shared by the pipeline tests.
+
+// Tests run against the GStreamer plugins installed on the machine. Tests that
+// need a missing plugin are skipped with a message instead of failing.
+use std::{
+	fs,
+	ops::Deref,
+	path::{Path, PathBuf},
+	process, thread,
+	time::Duration,
+};
+
+use gstreamer as gst;
+use gstreamer_pbutils as gst_pbutils;
+
+use crate::media::{builder::RecorderBuilder, error::VideoError, recorder::Recorder};
+
+/// Initialize GStreamer and tell whether all `factories` are installed,
+/// printing the missing ones otherwise.
+pub fn has_elements(factories: &[&str]) -> bool {
+	gst::init().expect("failed to initialize GStreamer");
+	let missing: Vec<_> =
+		factories.iter().filter(|factory| gst::ElementFactory::find(factory).is_none()).collect();
+	if !missing.is_empty() {
+		eprintln!("skipped, missing GStreamer elements {:?}", missing);
+	}
+	missing.is_empty()
+}
+
+/// Elements of a recorder with video and without audio.
+pub const VIDEO_RECORDER: &[&str] = &[
+	"tee",
+	"queue2",
+	"videorate",
+	"videoconvert",
+	"videoscale",
+	"videobalance",
+	"videocrop",
+	"videoflip",
+	"capsfilter",
+	"x264enc",
+	"matroskamux",
+	"fakesink",
+];
+
+/// Build `builder`, or `None` with a message if a plugin it needs is missing.
+pub fn build(builder: RecorderBuilder) -> Option<Recorder> {
+	match builder.build() {
+		Ok(recorder) => Some(recorder),
+		Err(VideoError::MissingElement(factory)) => {
+			eprintln!("skipped, missing GStreamer element {}", factory);
+			None
+		}
+		Err(err) => panic!("failed to build recorder: {}", err),
+	}
+}
+
+/// Record for `duration` and finish the file.
+pub fn record(recorder: &Recorder, duration: Duration) {
+	recorder.record(true);
+	thread::sleep(duration);
+	recorder.finish(Duration::from_secs(10)).expect("recording did not finish");
+}
+
+/// File in the temporary directory, deleted when dropped.
+#[derive(Debug)]
+pub struct TempPath(PathBuf);
+
+impl Deref for TempPath {
+	type Target = Path;
+
+	fn deref(&self) -> &Path {
+		&self.0
+	}
+}
+
+impl Drop for TempPath {
+	fn drop(&mut self) {
+		// The test may have failed before writing the file.
+		let _ = fs::remove_file(&self.0);
+	}
+}
+
+/// Path in the temporary directory, unique to this test process.
+pub fn temp_path(name: &str) -> TempPath {
+	TempPath(std::env::temp_dir().join(format!("druid_camera-{}-{}", process::id(), name)))
+}
+
+/// Sample rate and channels of every audio stream in the file at `path`.
+pub fn audio_streams(path: &Path) -> Vec<(u32, u32)> {
+	let uri = glib::filename_to_uri(path, None).unwrap();
+	let discoverer = gst_pbutils::Discoverer::new(gst::ClockTime::from_seconds(10)).unwrap();
+	let info = discoverer.discover_uri(&uri).unwrap();
+	info.audio_streams().iter().map(|stream| (stream.sample_rate(), stream.channels())).collect()
+}
@@ -3,21 +3,20 @@
 
 // {uridecodebin} - {videoconvert} - {appsink}
 
-// The appsink enforces RGBx so that the image crate can use it. The sample
-// layout is passed with the correct stride from GStreamer to the image crate as
-// GStreamer does not necessarily produce tightly packed pixels, and in case of
-// RGBx never.
+// The appsink enforces RGBA, the layout of `Frame`, so that the image crate can
+// use it. The sample layout is passed with the correct stride from GStreamer to
+// the image crate as GStreamer does not necessarily produce tightly packed
+// pixels, and in case of RGBx never.
 use std::sync::mpsc::{sync_channel, Receiver, Sender, TryRecvError, TrySendError};
 
 use anyhow::{Error, Result};
 use derive_more::{Display, Error};
-use druid::{piet::ImageFormat, ImageBuf};
 use gst::{element_error, prelude::*};
 use gstreamer as gst;
 use gstreamer::Pipeline;
 use gstreamer_app as gst_app;
 
-use crate::gui::data::video::VideoError;
+use crate::media::{error::VideoError, frame::Frame};
 
 #[derive(Debug, Display, Error)]
 #[display(fmt = "Missing element {}", _0)]
@@ -31,19 +30,25 @@ struct ErrorMessage {
 	debug: Option<String>,
 	source: glib::Error,
 }
+/// Single frame decoded from a media file.
+#[derive(Debug)]
 pub struct Thumbnail {
-	pub receiver: Receiver<ImageBuf>,
+	/// Receives the frame once it was decoded.
+	pub receiver: Receiver<Frame>,
 	pipeline: Pipeline,
+	/// Duration of the file in seconds.
 	pub duration: u64,
 }
 
 impl Thumbnail {
+	/// Decode the frame of the file at `uri` that is shown `position` seconds
+	/// in.
 	pub fn new(uri: &str, position: u64) -> Result<Self> {
 		let (sender, receiver) = sync_channel(1);
 		gst::init()?;
 		// Create our pipeline from a pipeline description string.
 		let pipeline = gst::parse_launch(&format!(
-                    "uridecodebin uri={} ! videoconvert ! appsink name=sink caps=\"video/x-raw, format=BGRA\"",
+                    "uridecodebin uri={} ! videoconvert ! appsink name=sink caps=\"video/x-raw, format=RGBA\"",
             uri
         ))?
             .downcast::<gst::Pipeline>()
@@ -102,12 +107,8 @@ impl Thumbnail {
 					let height = s.get::<i32>("height").map_err(|_| gst::FlowError::Error)?;
 					// println!("W: {:?}, H: {:?}", width, height);
 					// Send original and processed image.
-					let image = ImageBuf::from_raw(
-						map.as_slice().to_owned(),
-						ImageFormat::RgbaSeparate,
-						width as _,
-						height as _,
-					);
+					let image =
+						Frame { data: map.as_slice().into(), width: width as _, height: height as _ };
 					match sender.try_send(image) {
 						Ok(_) => {
 							// Ok(gst::FlowSuccess::Ok)
@@ -0,0 +1,400 @@
+// Mirror, flip, rotation and crop of the camera image.
+
+// ... - {video tee} - {queue} - {videorate} - {videocrop} - {videoflip} - {videoconvert} - ... - {x264enc}
+//                 \- {queue} - {videorate} - {videocrop} - {videoflip} - {videoconvert} - {preview sink}
+
+// Both branches after the tee get their own crop and flip, so a transform can
+// show in the preview alone, like the mirrored view people expect from a
+// webcam, or in the recording too. Mirror, flip and rotation add up to one of
+// the eight directions of `videoflip`. The crop is given in camera pixels and
+// taken before the flip, so it stays on the same part of the picture however
+// the image is turned. Turning or cropping the recording while recording
+// changes the frame size mid-file, which the muxer may refuse. A digital zoom
+// is recorded as a narrower crop that the encoder branch scales back up to
+// the frame size it had, so the zoom can follow the preview while recording.
+use std::{fmt, str::FromStr};
+
+use anyhow::anyhow;
+use gst::prelude::*;
+use gstreamer as gst;
+use serde::{Deserialize, Serialize};
+
+use crate::media::error::VideoError;
+
+/// Branch of the recorder after the video tee.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Branch {
+	/// Frames shown in the preview.
+	Preview,
+	/// Frames going to the encoder, the recording and every network output.
+	Recording,
+}
+
+impl Branch {
+	/// Names of the `videocrop` and `videoflip` elements of the branch.
+	fn names(self) -> (&'static str, &'static str) {
+		match self {
+			Branch::Preview => ("desktop-video-crop1", "desktop-video-flip1"),
+			Branch::Recording => ("desktop-video-crop", "desktop-video-flip"),
+		}
+	}
+}
+
+/// Clockwise rotation in quarter turns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Rotation {
+	/// Upright.
+	None,
+	/// 90° clockwise.
+	Clockwise,
+	/// 180°.
+	UpsideDown,
+	/// 90° counterclockwise.
+	CounterClockwise,
+}
+
+impl Default for Rotation {
+	fn default() -> Self {
+		Self::None
+	}
+}
+
+impl fmt::Display for Rotation {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}°", self.degrees())
+	}
+}
+
+impl FromStr for Rotation {
+	type Err = anyhow::Error;
+
+	/// Degrees clockwise, a multiple of 90; negative for counterclockwise.
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s.trim_end_matches('°').parse::<i32>() {
+			Ok(degrees) if degrees % 90 == 0 => Ok(Self::from_quarter_turns(degrees / 90)),
+			_ => Err(anyhow!("rotation {} is not a multiple of 90°", s)),
+		}
+	}
+}
+
+impl Rotation {
+	/// Rotation by `turns` quarter turns clockwise, counterclockwise when
+	/// negative.
+	pub fn from_quarter_turns(turns: i32) -> Self {
+		match turns.rem_euclid(4) {
+			0 => Self::None,
+			1 => Self::Clockwise,
+			2 => Self::UpsideDown,
+			_ => Self::CounterClockwise,
+		}
+	}
+
+	/// Quarter turns clockwise, from 0 to 3.
+	pub fn quarter_turns(self) -> i32 {
+		match self {
+			Self::None => 0,
+			Self::Clockwise => 1,
+			Self::UpsideDown => 2,
+			Self::CounterClockwise => 3,
+		}
+	}
+
+	/// Degrees clockwise, from 0 to 270.
+	pub fn degrees(self) -> i32 {
+		self.quarter_turns() * 90
+	}
+}
+
+/// Pixels cut off each edge of the camera image.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Crop {
+	/// Pixels cut off the left edge.
+	pub left: u32,
+	/// Pixels cut off the top edge.
+	pub top: u32,
+	/// Pixels cut off the right edge.
+	pub right: u32,
+	/// Pixels cut off the bottom edge.
+	pub bottom: u32,
+}
+
+impl fmt::Display for Crop {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{},{},{},{}", self.left, self.top, self.right, self.bottom)
+	}
+}
+
+impl FromStr for Crop {
+	type Err = anyhow::Error;
+
+	/// Insets as `left,top,right,bottom`.
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let invalid = || anyhow!("crop {} is not left,top,right,bottom", s);
+		let insets = s
+			.split(',')
+			.map(|inset| inset.trim().parse::<u32>().map_err(|_| invalid()))
+			.collect::<Result<Vec<_>, _>>()?;
+		match insets[..] {
+			[left, top, right, bottom] => Ok(Self { left, top, right, bottom }),
+			_ => Err(invalid()),
+		}
+	}
+}
+
+impl Crop {
+	/// Whether nothing is cut off.
+	pub fn is_empty(&self) -> bool {
+		*self == Self::default()
+	}
+
+	/// Reject odd insets, which would leave frames of odd size that 4:2:0
+	/// video cannot hold.
+	pub fn validate(&self) -> Result<(), VideoError> {
+		if [self.left, self.top, self.right, self.bottom].iter().any(|inset| inset % 2 != 0) {
+			return Err(VideoError::Config(format!("crop {} must be even on every edge", self)));
+		}
+		Ok(())
+	}
+}
+
+/// Geometry of the frames of one branch.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Transform {
+	/// Swap left and right.
+	pub mirror: bool,
+	/// Swap top and bottom.
+	pub flip: bool,
+	/// Rotation after mirroring and flipping.
+	pub rotation: Rotation,
+	/// Crop of the camera image, before everything else.
+	pub crop: Crop,
+}
+
+impl Transform {
+	/// Whether frames pass unchanged.
+	pub fn is_identity(&self) -> bool {
+		*self == Self::default()
+	}
+
+	/// `video-direction` of `videoflip` mirroring, flipping and rotating at
+	/// once.
+	pub fn direction(&self) -> &'static str {
+		// A flip is a mirror turned upside down, and two mirrors cancel out.
+		let turns = self.rotation.quarter_turns() + if self.flip { 2 } else { 0 };
+		match (self.mirror != self.flip, turns % 4) {
+			(false, 0) => "identity",
+			(false, 1) => "90r",
+			(false, 2) => "180",
+			(false, _) => "90l",
+			(true, 0) => "horiz",
+			(true, 1) => "ur-ll",
+			(true, 2) => "vert",
+			(true, _) => "ul-lr",
+		}
+	}
+
+	/// Size of the camera image behind frames of `frame` size coming out of
+	/// this transform.
+	pub fn camera_size(&self, frame: (u32, u32)) -> (u32, u32) {
+		let (width, height) =
+			if self.rotation.quarter_turns() % 2 == 1 { (frame.1, frame.0) } else { frame };
+		(width + self.crop.left + self.crop.right, height + self.crop.top + self.crop.bottom)
+	}
+
+	/// Size of the frames coming out of this transform for a camera image of
+	/// `camera` size.
+	pub fn output_size(&self, camera: (u32, u32)) -> (u32, u32) {
+		let width = camera.0.saturating_sub(self.crop.left + self.crop.right);
+		let height = camera.1.saturating_sub(self.crop.top + self.crop.bottom);
+		if self.rotation.quarter_turns() % 2 == 1 {
+			(height, width)
+		} else {
+			(width, height)
+		}
+	}
+
+	/// Point of the frames coming out of this transform showing `point` of a
+	/// camera image of `camera` size.
+	pub fn from_camera(&self, point: (f64, f64), camera: (u32, u32)) -> (f64, f64) {
+		// The reverse of `to_camera`, from the cropped camera image on.
+		let mut width = f64::from(camera.0.saturating_sub(self.crop.left + self.crop.right));
+		let mut height = f64::from(camera.1.saturating_sub(self.crop.top + self.crop.bottom));
+		let mut x = point.0 - f64::from(self.crop.left);
+		let mut y = point.1 - f64::from(self.crop.top);
+		if self.mirror != self.flip {
+			x = width - x;
+		}
+		let turns = self.rotation.quarter_turns() + if self.flip { 2 } else { 0 };
+		for _ in 0..turns % 4 {
+			let turned = (height - y, x);
+			x = turned.0;
+			y = turned.1;
+			std::mem::swap(&mut width, &mut height);
+		}
+		(x, y)
+	}
+
+	/// Point of the camera image shown at `point` of a frame of `frame` size
+	/// coming out of this transform.
+	pub fn to_camera(&self, point: (f64, f64), frame: (u32, u32)) -> (f64, f64) {
+		let (mut x, mut y) = point;
+		let (mut width, mut height) = (f64::from(frame.0), f64::from(frame.1));
+		// Undo the quarter turns one by one, then the mirror, as in `direction`.
+		let turns = self.rotation.quarter_turns() + if self.flip { 2 } else { 0 };
+		for _ in 0..turns % 4 {
+			let turned = (y, width - x);
+			x = turned.0;
+			y = turned.1;
+			std::mem::swap(&mut width, &mut height);
+		}
+		if self.mirror != self.flip {
+			x = width - x;
+		}
+		(x + f64::from(self.crop.left), y + f64::from(self.crop.top))
+	}
+
+	/// Crop of the camera image keeping what frames of `frame` size show
+	/// between the corners `a` and `b`, rounded to even insets.
+	pub fn crop_between(&self, a: (f64, f64), b: (f64, f64), frame: (u32, u32)) -> Crop {
+		let (width, height) = self.camera_size(frame);
+		let (a, b) = (self.to_camera(a, frame), self.to_camera(b, frame));
+		let even = |inset: f64| (inset.max(0.0) / 2.0).round() as u32 * 2;
+		Crop {
+			left: even(a.0.min(b.0)),
+			top: even(a.1.min(b.1)),
+			right: even(f64::from(width) - a.0.max(b.0)),
+			bottom: even(f64::from(height) - a.1.max(b.1)),
+		}
+	}
+
+	/// Crop and flip elements of `branch`, in chain order.
+	pub(crate) fn make_elements(branch: Branch) -> Result<[gst::Element; 2], VideoError> {
+		let (crop, flip) = branch.names();
+		let make = |factory: &str, name: &str| {
+			gst::ElementFactory::make(factory, Some(name))
+				.map_err(|_| VideoError::MissingElement(factory.to_string()))
+		};
+		Ok([make("videocrop", crop)?, make("videoflip", flip)?])
+	}
+
+	/// Configure the elements of `branch` in `bin`, while running or not.
+	pub fn apply(&self, bin: &gst::Bin, branch: Branch) -> Result<(), VideoError> {
+		self.crop.validate()?;
+		let (crop, flip) = branch.names();
+		let crop = bin.by_name(crop).ok_or(VideoError::NoVideo)?;
+		let flip = bin.by_name(flip).ok_or(VideoError::NoVideo)?;
+		crop.set_property("left", self.crop.left as i32);
+		crop.set_property("top", self.crop.top as i32);
+		crop.set_property("right", self.crop.right as i32);
+		crop.set_property("bottom", self.crop.bottom as i32);
+		flip.set_property_from_str("video-direction", self.direction());
+		Ok(())
+	}
+}
+
+/// Digital zoom into the frames of a branch.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Zoom {
+	/// Magnification, 1.0 for the whole frame.
+	pub factor: f64,
+	/// Point of the camera image to keep in the middle, in camera pixels.
+	pub center: (f64, f64),
+}
+
+impl Zoom {
+	/// `transform` with its crop narrowed to the zoomed part of the frames,
+	/// for a camera image of `camera` size. The zoomed part keeps the aspect
+	/// ratio of the frames and stays inside them, off center if need be.
+	pub fn narrow(&self, transform: &Transform, camera: (u32, u32)) -> Transform {
+		let (width, height) = transform.output_size(camera);
+		let (width, height) = (f64::from(width), f64::from(height));
+		let factor = self.factor.max(1.0);
+		let (half_width, half_height) = (width / factor / 2.0, height / factor / 2.0);
+		let (x, y) = transform.from_camera(self.center, camera);
+		let x = x.clamp(half_width, width - half_width);
+		let y = y.clamp(half_height, height - half_height);
+		let frame = (width as u32, height as u32);
+		let crop = transform.crop_between(
+			(x - half_width, y - half_height),
+			(x + half_width, y + half_height),
+			frame,
+		);
+		Transform { crop, ..*transform }
+	}
+}
+
+/// Setting applied to the preview and, if `recording` is set, to the
+/// recording as well.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Scoped<T> {
+	/// Value in the preview.
+	pub value: T,
+	/// Whether the recording gets the value too, or stays untouched.
+	pub recording: bool,
+}
+
+impl<T: Copy + Default> Scoped<T> {
+	/// Setting applied to preview and recording alike.
+	pub fn everywhere(value: T) -> Self {
+		Self { value, recording: true }
+	}
+
+	/// Value in the recording.
+	pub fn recorded(&self) -> T {
+		if self.recording {
+			self.value
+		} else {
+			T::default()
+		}
+	}
+}
+
+/// Transforms of the camera image, each shown in the preview and optionally
+/// recorded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Transforms {
+	/// Swap left and right, in the preview only by default.
+	pub mirror: Scoped<bool>,
+	/// Swap top and bottom.
+	pub flip: Scoped<bool>,
+	/// Turn cameras mounted sideways or upside down.
+	pub rotation: Scoped<Rotation>,
+	/// Cut off the edges of the camera image.
+	pub crop: Scoped<Crop>,
+}
+
+impl Default for Transforms {
+	fn default() -> Self {
+		Self {
+			mirror: Scoped { value: false, recording: false },
+			flip: Scoped::everywhere(false),
+			rotation: Scoped::everywhere(Rotation::None),
+			crop: Scoped::everywhere(Crop::default()),
+		}
+	}
+}
+
+impl Transforms {
+	/// Reject crops the branches cannot apply.
+	pub fn validate(&self) -> Result<(), VideoError> {
+		self.crop.value.validate()
+	}
+
+	/// Transform of `branch`.
+	pub fn get(&self, branch: Branch) -> Transform {
+		match branch {
+			Branch::Preview => Transform {
+				mirror: self.mirror.value,
+				flip: self.flip.value,
+				rotation: self.rotation.value,
+				crop: self.crop.value,
+			},
+			Branch::Recording => Transform {
+				mirror: self.mirror.recorded(),
+				flip: self.flip.recorded(),
+				rotation: self.rotation.recorded(),
+				crop: self.crop.recorded(),
+			},
+		}
+	}
+}
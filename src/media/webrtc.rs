@@ -0,0 +1,296 @@
+// WebRTC preview publishing with a built-in signalling endpoint.
+
+// {encoded tee} - {queue} - {h264parse} - {rtph264pay} - {webrtcbin}
+
+// Each browser opening the viewer page connects to `/ws` on the same port and
+// gets its own webrtcbin attached to the encoded stream. The server sends the
+// offer, the browser answers, and ICE candidates are exchanged in both
+// directions as JSON messages. No STUN/TURN server is configured, host
+// candidates are enough on localhost and the LAN.
+use std::{
+	io::{ErrorKind, Write},
+	net::TcpStream,
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		mpsc, Arc, Mutex,
+	},
+	time::Duration,
+};
+
+use anyhow::{anyhow, Result};
+use gst::prelude::*;
+use gstreamer as gst;
+use gstreamer_sdp as gst_sdp;
+use gstreamer_webrtc as gst_webrtc;
+use serde::{Deserialize, Serialize};
+use tungstenite::{handshake::derive_accept_key, protocol::Role, Message, WebSocket};
+
+use crate::media::{
+	error::VideoError,
+	http::{self, HttpServer, Request},
+	output::{EncodedBranch, EncodedTee},
+};
+
+const VIEWER: &str = include_str!("webrtc.html");
+
+/// Settings of the WebRTC output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebRtcSettings {
+	/// Port serving the viewer page and the signalling WebSocket.
+	pub port: u16,
+}
+
+impl Default for WebRtcSettings {
+	fn default() -> Self {
+		Self { port: 8443 }
+	}
+}
+
+/// Signalling message exchanged with the viewer page.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum Signal {
+	Offer {
+		sdp: String,
+	},
+	Answer {
+		sdp: String,
+	},
+	Ice {
+		candidate: String,
+		#[serde(rename = "sdpMLineIndex")]
+		sdp_mline_index: u32,
+	},
+}
+
+/// Running signalling server publishing the encoded stream to browsers.
+#[derive(Debug)]
+pub struct WebRtcServer {
+	server: HttpServer,
+	running: Arc<AtomicBool>,
+}
+
+impl WebRtcServer {
+	/// Serve the viewer page and attach a peer to `encoded` per connection.
+	pub fn start(settings: &WebRtcSettings, encoded: EncodedTee) -> Result<Self, VideoError> {
+		gst::init()?;
+		let running = Arc::new(AtomicBool::new(true));
+		let server_running = running.clone();
+		let server = HttpServer::start(settings.port, move |request, mut stream| {
+			let result = match request.path.as_str() {
+				"/ws" => signal(&request, stream, &encoded, &server_running),
+				"/" | "/index.html" => http::respond(
+					&mut stream,
+					"200 OK",
+					http::content_type("index.html"),
+					VIEWER.as_bytes(),
+					true,
+				)
+				.map_err(Into::into),
+				_ => http::not_found(&mut stream).map_err(Into::into),
+			};
+			if let Err(err) = result {
+				log::warn!("WebRTC viewer {}: {}", request.path, err);
+			}
+		})?;
+		Ok(WebRtcServer { server, running })
+	}
+
+	/// URL of the viewer page.
+	pub fn url(&self) -> String {
+		self.server.url()
+	}
+}
+
+impl Drop for WebRtcServer {
+	fn drop(&mut self) {
+		self.running.store(false, Ordering::Relaxed);
+	}
+}
+
+/// Peer connection of one viewer.
+struct Session {
+	webrtcbin: gst::Element,
+	branch: EncodedBranch,
+}
+
+impl Session {
+	/// Attach a webrtcbin to `encoded`, sending its offer and candidates to
+	/// `outgoing`.
+	fn start(encoded: &EncodedTee, outgoing: mpsc::Sender<Signal>) -> Result<Self> {
+		let bin = gst::parse_bin_from_description(
+			"queue leaky=downstream max-size-buffers=5 ! h264parse ! \
+			 rtph264pay config-interval=-1 pt=96 ! \
+			 application/x-rtp, media=video, encoding-name=H264, payload=96, clock-rate=90000 ! \
+			 webrtcbin name=webrtc bundle-policy=max-bundle",
+			true,
+		)?;
+		let webrtcbin = bin.by_name("webrtc").ok_or_else(|| anyhow!("webrtcbin missing"))?;
+		// Signal handlers must be Sync, which mpsc senders are not.
+		let outgoing = Arc::new(Mutex::new(outgoing));
+
+		let offer_outgoing = outgoing.clone();
+		webrtcbin.connect("on-negotiation-needed", false, move |values| {
+			let webrtcbin = values[0].get::<gst::Element>().expect("signal arg is webrtcbin");
+			let outgoing = offer_outgoing.clone();
+			let offer_bin = webrtcbin.clone();
+			let promise = gst::Promise::with_change_func(move |reply| {
+				let offer = match reply {
+					Ok(Some(reply)) => reply.get::<gst_webrtc::WebRTCSessionDescription>("offer"),
+					_ => {
+						log::error!("webrtcbin failed to create an offer");
+						return;
+					}
+				};
+				let offer = match offer {
+					Ok(offer) => offer,
+					Err(err) => {
+						log::error!("invalid offer: {}", err);
+						return;
+					}
+				};
+				offer_bin.emit_by_name::<()>(
+					"set-local-description",
+					&[&offer, &None::<gst::Promise>],
+				);
+				match offer.sdp().as_text() {
+					Ok(sdp) => {
+						let _ = outgoing.lock().unwrap().send(Signal::Offer { sdp });
+					}
+					Err(err) => log::error!("failed to serialize offer: {}", err),
+				}
+			});
+			webrtcbin.emit_by_name::<()>("create-offer", &[&None::<gst::Structure>, &promise]);
+			None
+		});
+
+		webrtcbin.connect("on-ice-candidate", false, move |values| {
+			let sdp_mline_index = values[1].get::<u32>().expect("signal arg is mline index");
+			let candidate = values[2].get::<String>().expect("signal arg is candidate");
+			let _ = outgoing.lock().unwrap().send(Signal::Ice { candidate, sdp_mline_index });
+			None
+		});
+
+		let branch = encoded.attach(bin.upcast_ref())?;
+		Ok(Session { webrtcbin, branch })
+	}
+
+	/// Apply a message received from the viewer.
+	fn handle(&self, signal: Signal) -> Result<()> {
+		match signal {
+			Signal::Answer { sdp } => {
+				let sdp = gst_sdp::SDPMessage::parse_buffer(sdp.as_bytes())?;
+				let answer =
+					gst_webrtc::WebRTCSessionDescription::new(gst_webrtc::WebRTCSDPType::Answer, sdp);
+				self.webrtcbin
+					.emit_by_name::<()>("set-remote-description", &[&answer, &None::<gst::Promise>]);
+			}
+			Signal::Ice { candidate, sdp_mline_index } => {
+				self.webrtcbin.emit_by_name::<()>("add-ice-candidate", &[&sdp_mline_index, &candidate]);
+			}
+			Signal::Offer { .. } => log::warn!("ignoring offer from viewer"),
+		}
+		Ok(())
+	}
+}
+
+/// Upgrade `stream` to a WebSocket and run the signalling of one viewer until
+/// it disconnects or the server stops.
+fn signal(
+	request: &Request,
+	mut stream: TcpStream,
+	encoded: &EncodedTee,
+	running: &AtomicBool,
+) -> Result<()> {
+	let key = request.header("sec-websocket-key").ok_or_else(|| anyhow!("not a WebSocket"))?;
+	write!(
+		stream,
+		"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+		 Sec-WebSocket-Accept: {}\r\n\r\n",
+		derive_accept_key(key.as_bytes())
+	)?;
+	// Reads time out so queued outgoing messages get sent in between.
+	stream.set_read_timeout(Some(Duration::from_millis(50)))?;
+	let mut socket = WebSocket::from_raw_socket(stream, Role::Server, None);
+
+	let (outgoing, queued) = mpsc::channel();
+	let session = Session::start(encoded, outgoing)?;
+	let result = relay(&mut socket, &queued, &session, running);
+	encoded.detach(session.branch);
+	log::debug!("WebRTC viewer disconnected");
+	result
+}
+
+/// Pass messages between the viewer and its session until the socket closes
+/// or `running` is cleared, in which case the viewer gets a Close frame.
+fn relay(
+	socket: &mut WebSocket<TcpStream>,
+	queued: &mpsc::Receiver<Signal>,
+	session: &Session,
+	running: &AtomicBool,
+) -> Result<()> {
+	loop {
+		if !running.load(Ordering::Relaxed) {
+			let _ = socket.close(None);
+			let _ = socket.write_pending();
+			return Ok(());
+		}
+		for signal in queued.try_iter() {
+			socket.write_message(Message::Text(serde_json::to_string(&signal)?))?;
+		}
+		match socket.read_message() {
+			Ok(Message::Text(text)) => session.handle(serde_json::from_str(&text)?)?,
+			Ok(Message::Close(_)) | Err(tungstenite::Error::ConnectionClosed) => return Ok(()),
+			Ok(_) => {}
+			Err(tungstenite::Error::Io(err))
+				if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+			Err(err) => return Err(err.into()),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{thread, time::Instant};
+
+	use super::*;
+	use crate::media::testing;
+
+	/// Wait up to five seconds for `tee` to have `count` src pads.
+	fn wait_for_pads(tee: &gst::Element, count: usize) -> bool {
+		let deadline = Instant::now() + Duration::from_secs(5);
+		while Instant::now() < deadline {
+			if tee.src_pads().len() == count {
+				return true;
+			}
+			thread::sleep(Duration::from_millis(20));
+		}
+		false
+	}
+
+	#[test]
+	fn detaches_viewers_when_dropped() {
+		if !testing::has_elements(&["tee", "queue", "h264parse", "rtph264pay", "webrtcbin"]) {
+			return;
+		}
+		let pipeline = gst::Pipeline::new(None);
+		let tee = gst::ElementFactory::make("tee", None).unwrap();
+		pipeline.add(&tee).unwrap();
+		let server =
+			WebRtcServer::start(&WebRtcSettings { port: 0 }, EncodedTee::new(&pipeline, &tee))
+				.unwrap();
+
+		let url = format!("ws://127.0.0.1:{}/ws", server.server.port());
+		let (mut socket, _) = tungstenite::connect(url).unwrap();
+		assert!(wait_for_pads(&tee, 1), "viewer was not attached");
+
+		drop(server);
+		assert!(wait_for_pads(&tee, 0), "viewer is still attached");
+		loop {
+			match socket.read_message() {
+				Ok(Message::Close(_)) | Err(_) => break,
+				Ok(_) => {}
+			}
+		}
+	}
+}